@@ -0,0 +1,862 @@
+use anyhow::Result;
+use clap::Parser;
+use pixelconvert_core::watch::{
+    Debouncer, HistoryEntry, WatchFolder, WatchHistory, WatchOutcome, DEFAULT_SETTLE_TIME,
+};
+use pixelconvert_core::{
+    worker, AlphaPolicy, BatchJob, BatchProgress, BatchReport, BatchStats, BlendMode, Canvas,
+    CanvasFill, CanvasSize, ConversionOptions, ConvertError, Crop, DecodeLimits, DitherMode,
+    Execution, Gravity, ImageConverter, JobState, Journal, Orientation, PngOptimization,
+    Quantization, SupportedFormat, Trim, TrimMode, Watermark,
+};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+/// Convert images between PNG, JPEG, WebP, AVIF, GIF, BMP, TIFF and ICO.
+///
+/// Use `-` as the only input to read from stdin; the result goes to stdout
+/// unless `--output` names a file. With `--watch`, the inputs are folders
+/// whose new and changed images are converted until interrupted.
+#[derive(Parser, Debug)]
+#[command(name = "pixelconvert-cli", version, after_help = EXIT_CODES)]
+struct Cli {
+    /// Input images, `-` for stdin, or folders to watch
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// Output format (png, jpg, webp, avif, gif, bmp, tiff, ico)
+    #[arg(short, long, value_parser = parse_format, required_unless_present = "preset")]
+    format: Option<SupportedFormat>,
+
+    /// Take the conversion settings from a preset file (JSON conversion
+    /// options) instead of the flags below, which can't be combined with
+    /// it; `--format` and `--crop` still apply on top of the preset
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = [
+            "quality", "no_png_optimize", "png_level", "zopfli", "colors", "dither",
+            "alpha_threshold", "alpha", "background", "trim", "rotate", "auto_crop",
+            "flip_horizontal", "flip_vertical", "canvas", "watermark", "max_width",
+            "max_height", "max_pixels", "max_memory", "max_frames",
+        ]
+    )]
+    preset: Option<PathBuf>,
+
+    /// Quality for lossy formats (0-100)
+    #[arg(short, long, default_value_t = 85, value_parser = clap::value_parser!(u8).range(0..=100))]
+    quality: u8,
+
+    /// Output file (single input), directory (several inputs) or `-` for stdout.
+    /// Defaults to next to each source file.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Skip the lossless PNG optimization pass
+    #[arg(long)]
+    no_png_optimize: bool,
+
+    /// PNG optimization level (0-6)
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=6))]
+    png_level: u8,
+
+    /// Use zopfli for PNG deflate (much slower, slightly smaller)
+    #[arg(long)]
+    zopfli: bool,
+
+    /// Reduce PNG/GIF output to a palette of at most this many colours (2-256)
+    #[arg(long, value_parser = clap::value_parser!(u16).range(2..=256))]
+    colors: Option<u16>,
+
+    /// Dithering for palette reduction (none, floyd-steinberg, ordered)
+    #[arg(long, default_value = "floyd-steinberg", value_parser = parse_dither)]
+    dither: DitherMode,
+
+    /// Alpha below which palette pixels become transparent
+    #[arg(long, default_value_t = 128)]
+    alpha_threshold: u8,
+
+    /// Transparency handling (keep, flatten, checkerboard, fail)
+    #[arg(long, default_value = "keep", value_parser = parse_alpha_policy)]
+    alpha: AlphaPolicy,
+
+    /// Background colour for flattening and solid canvases, as #rrggbb
+    #[arg(long, default_value = "#ffffff", value_parser = parse_color)]
+    background: [u8; 3],
+
+    /// Keep only this rectangle of each input, as WIDTHxHEIGHT+X+Y in
+    /// pixels (e.g. 512x512+64+0); applies on top of a preset too
+    #[arg(long, value_name = "GEOMETRY")]
+    crop: Option<Crop>,
+
+    /// Trim a uniform border matching the top-left corner, or with
+    /// `--trim=transparency` a transparent one, after cropping
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "corner",
+        value_parser = parse_trim_mode
+    )]
+    trim: Option<TrimMode>,
+
+    /// How far (0-255) pixels may be from the border colour, or how
+    /// opaque for `--trim transparency`, to be trimmed
+    #[arg(long, requires = "trim", default_value_t = Trim::default().tolerance)]
+    trim_tolerance: u8,
+
+    /// Pixels (0-1000) of border colour added back on each side after
+    /// trimming
+    #[arg(long, requires = "trim", default_value_t = 0, value_parser = clap::value_parser!(u32).range(0..=1000))]
+    trim_padding: u32,
+
+    /// Rotate clockwise by this many degrees (negative for
+    /// counter-clockwise). Angles other than multiples of 90 grow the
+    /// canvas; the corners are transparent or the background colour.
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    rotate: f32,
+
+    /// Crop an angled rotation to the largest rectangle inside it
+    #[arg(long)]
+    auto_crop: bool,
+
+    /// Mirror left to right, after rotating
+    #[arg(long)]
+    flip_horizontal: bool,
+
+    /// Mirror top to bottom, after rotating
+    #[arg(long)]
+    flip_vertical: bool,
+
+    /// Pad each image onto a canvas of this ratio (`4:5`) or size
+    /// (`1080x1350`), after rotating; images are never scaled or cropped
+    #[arg(long, value_name = "SIZE")]
+    canvas: Option<CanvasSize>,
+
+    /// Where the image sits on the canvas: center, north, north-east, east,
+    /// south-east, south, south-west, west or north-west
+    #[arg(long, requires = "canvas", default_value = "center", value_parser = parse_gravity)]
+    canvas_gravity: Gravity,
+
+    /// What fills the canvas: solid (the background colour), transparent,
+    /// blur or edge
+    #[arg(long, requires = "canvas", default_value = "solid", value_parser = parse_canvas_fill)]
+    canvas_fill: CanvasFill,
+
+    /// Composite this logo (PNG, SVG, ...) onto every output, after all
+    /// other image operations
+    #[arg(long, value_name = "FILE")]
+    watermark: Option<PathBuf>,
+
+    /// Where the watermark goes, as for --canvas-gravity
+    #[arg(
+        long,
+        requires = "watermark",
+        default_value = "south-east",
+        value_parser = parse_gravity
+    )]
+    watermark_gravity: Gravity,
+
+    /// Distance of the watermark from the edges it's anchored to, as X,Y
+    /// percent of the image width
+    #[arg(
+        long,
+        requires = "watermark",
+        value_name = "X,Y",
+        default_value = "2,2",
+        value_parser = parse_offset
+    )]
+    watermark_offset: (f32, f32),
+
+    /// Watermark width, in percent of the image width
+    #[arg(
+        long,
+        requires = "watermark",
+        value_name = "PERCENT",
+        default_value_t = 20.0
+    )]
+    watermark_size: f32,
+
+    /// Watermark opacity, in percent
+    #[arg(
+        long,
+        requires = "watermark",
+        value_name = "PERCENT",
+        default_value_t = 50.0
+    )]
+    watermark_opacity: f32,
+
+    /// Repeat the watermark across the whole image
+    #[arg(long, requires = "watermark")]
+    watermark_tile: bool,
+
+    /// How the watermark combines with the image: normal, multiply, screen
+    /// or overlay
+    #[arg(
+        long,
+        requires = "watermark",
+        default_value = "normal",
+        value_parser = parse_blend_mode
+    )]
+    watermark_blend: BlendMode,
+
+    /// Refuse inputs wider than this many pixels
+    #[arg(long, default_value_t = DecodeLimits::default().max_width)]
+    max_width: u32,
+
+    /// Refuse inputs taller than this many pixels
+    #[arg(long, default_value_t = DecodeLimits::default().max_height)]
+    max_height: u32,
+
+    /// Refuse inputs with more pixels than this (width × height)
+    #[arg(long, default_value_t = DecodeLimits::default().max_pixels)]
+    max_pixels: u64,
+
+    /// Maximum memory a decoder may allocate, in MiB
+    #[arg(long, default_value_t = DecodeLimits::default().max_alloc / (1024 * 1024))]
+    max_memory: u64,
+
+    /// Refuse animations with more frames than this
+    #[arg(long, default_value_t = DecodeLimits::default().max_frames)]
+    max_frames: u32,
+
+    /// Convert each input file in its own worker process, so a crashing or
+    /// hanging decoder fails only that file
+    #[arg(long)]
+    isolate: bool,
+
+    /// Stop an isolated conversion after this many seconds
+    #[arg(long, requires = "isolate", value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+
+    /// Record batch progress in this file. Running the same command again
+    /// after an interruption resumes the batch, skipping finished files and
+    /// retrying failed ones; with different settings it starts over.
+    #[arg(long, value_name = "FILE")]
+    journal: Option<PathBuf>,
+
+    /// Skip inputs whose output is up to date: made from the same input
+    /// contents with the same settings, or newer than the input
+    #[arg(long)]
+    incremental: bool,
+
+    /// Write a per-file report (paths, formats, dimensions, sizes, time,
+    /// settings and errors) when the batch finishes; CSV if FILE ends in
+    /// `.csv`, JSON otherwise
+    #[arg(long, value_name = "FILE", conflicts_with = "watch")]
+    report: Option<PathBuf>,
+
+    /// Watch the input folders and convert images as they are added or
+    /// changed, until interrupted. Images already in the folders are
+    /// converted first if their output is missing or out of date.
+    #[arg(long, conflicts_with = "journal")]
+    watch: bool,
+
+    /// Seconds a watched file must stay unchanged before it's converted
+    #[arg(long, requires = "watch", value_parser = parse_seconds)]
+    settle: Option<Duration>,
+
+    /// Log watch-mode conversions to this file (JSON lines). Defaults to
+    /// `$XDG_DATA_HOME/pixelconvert/watch-history.jsonl`.
+    #[arg(long, requires = "watch", value_name = "FILE")]
+    history: Option<PathBuf>,
+}
+
+impl Cli {
+    fn options(&self) -> Result<ConversionOptions> {
+        let mut options = match &self.preset {
+            Some(preset) => ConversionOptions::load_preset(preset)?,
+            None => ConversionOptions {
+                quality: self.quality,
+                png_optimization: (!self.no_png_optimize).then(|| PngOptimization {
+                    level: self.png_level,
+                    zopfli: self.zopfli,
+                    ..Default::default()
+                }),
+                quantization: self.colors.map(|max_colors| Quantization {
+                    max_colors,
+                    dither: self.dither,
+                    alpha_threshold: self.alpha_threshold,
+                }),
+                alpha_policy: self.alpha,
+                background: self.background,
+                trim: self.trim.map(|mode| Trim {
+                    mode,
+                    tolerance: self.trim_tolerance,
+                    padding: self.trim_padding,
+                }),
+                orientation: self.orientation(),
+                canvas: self.canvas.map(|size| Canvas {
+                    size,
+                    gravity: self.canvas_gravity,
+                    fill: self.canvas_fill,
+                }),
+                watermark: self.watermark.clone().map(|path| Watermark {
+                    path,
+                    gravity: self.watermark_gravity,
+                    offset_x: self.watermark_offset.0 / 100.0,
+                    offset_y: self.watermark_offset.1 / 100.0,
+                    scale: self.watermark_size / 100.0,
+                    opacity: self.watermark_opacity / 100.0,
+                    tile: self.watermark_tile,
+                    blend: self.watermark_blend,
+                }),
+                limits: DecodeLimits {
+                    max_width: self.max_width,
+                    max_height: self.max_height,
+                    max_pixels: self.max_pixels,
+                    max_alloc: self.max_memory.saturating_mul(1024 * 1024),
+                    max_frames: self.max_frames,
+                },
+                ..Default::default()
+            },
+        };
+        // Required unless a preset supplies it
+        if let Some(format) = self.format {
+            options.format = format;
+        }
+        if let Some(crop) = self.crop {
+            options.crop = Some(crop);
+        }
+        Ok(options)
+    }
+
+    /// `--rotate` split into quarter turns and the angle left over
+    fn orientation(&self) -> Orientation {
+        let degrees = self.rotate.rem_euclid(360.0);
+        let quarter_turns = (degrees / 90.0).floor();
+        Orientation {
+            quarter_turns: quarter_turns as u8,
+            angle: degrees - quarter_turns * 90.0,
+            auto_crop: self.auto_crop,
+            flip_horizontal: self.flip_horizontal,
+            flip_vertical: self.flip_vertical,
+        }
+    }
+
+    fn execution(&self) -> Result<Execution> {
+        Ok(if self.isolate {
+            Execution::Isolated {
+                worker: worker::WorkerCommand::current_exe()?,
+                timeout: self.timeout,
+            }
+        } else {
+            Execution::InProcess
+        })
+    }
+}
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0    all inputs converted
+  1    other or mixed failures
+  2    invalid arguments
+  3    unsupported input format
+  4    input could not be decoded
+  5    output format can't store the image
+  6    I/O error
+  7    permission denied
+  8    out of memory
+  9    input exceeds the decode limits
+  10   converter crashed (with --isolate)
+  11   conversion timed out (with --timeout)
+  12   image operation can't be applied (e.g. --crop outside the image,
+       an image larger than the --canvas size or an unreadable --watermark)
+  130  cancelled";
+
+/// Exit code for a conversion error, as listed in `EXIT_CODES`
+fn exit_code(error: &ConvertError) -> u8 {
+    match error {
+        ConvertError::UnsupportedInput(_) => 3,
+        ConvertError::Decode(_) => 4,
+        ConvertError::EncoderLimitation { .. } => 5,
+        ConvertError::Io { .. } => 6,
+        ConvertError::PermissionDenied { .. } => 7,
+        ConvertError::OutOfMemory => 8,
+        ConvertError::LimitExceeded(_) => 9,
+        ConvertError::Crashed(_) => 10,
+        ConvertError::TimedOut(_) => 11,
+        ConvertError::InvalidOperation(_) => 12,
+        ConvertError::Cancelled => 130,
+    }
+}
+
+fn parse_format(s: &str) -> Result<SupportedFormat, String> {
+    SupportedFormat::from_extension(s).ok_or_else(|| format!("unsupported format '{}'", s))
+}
+
+fn parse_dither(s: &str) -> Result<DitherMode, String> {
+    match s {
+        "none" => Ok(DitherMode::None),
+        "floyd-steinberg" | "fs" => Ok(DitherMode::FloydSteinberg),
+        "ordered" | "bayer" => Ok(DitherMode::Ordered),
+        _ => Err(format!("unknown dithering '{}'", s)),
+    }
+}
+
+fn parse_alpha_policy(s: &str) -> Result<AlphaPolicy, String> {
+    match s {
+        "keep" => Ok(AlphaPolicy::Keep),
+        "flatten" => Ok(AlphaPolicy::Flatten),
+        "checkerboard" => Ok(AlphaPolicy::Checkerboard),
+        "fail" => Ok(AlphaPolicy::Fail),
+        _ => Err(format!("unknown alpha policy '{}'", s)),
+    }
+}
+
+fn parse_trim_mode(s: &str) -> Result<TrimMode, String> {
+    match s {
+        "corner" => Ok(TrimMode::Corner),
+        "transparency" | "alpha" => Ok(TrimMode::Transparency),
+        _ => Err(format!("unknown trim mode '{}'", s)),
+    }
+}
+
+fn parse_gravity(s: &str) -> Result<Gravity, String> {
+    match s {
+        "north-west" | "top-left" => Ok(Gravity::NorthWest),
+        "north" | "top" => Ok(Gravity::North),
+        "north-east" | "top-right" => Ok(Gravity::NorthEast),
+        "west" | "left" => Ok(Gravity::West),
+        "center" | "centre" => Ok(Gravity::Center),
+        "east" | "right" => Ok(Gravity::East),
+        "south-west" | "bottom-left" => Ok(Gravity::SouthWest),
+        "south" | "bottom" => Ok(Gravity::South),
+        "south-east" | "bottom-right" => Ok(Gravity::SouthEast),
+        _ => Err(format!("unknown gravity '{}'", s)),
+    }
+}
+
+fn parse_canvas_fill(s: &str) -> Result<CanvasFill, String> {
+    match s {
+        "solid" => Ok(CanvasFill::Solid),
+        "transparent" => Ok(CanvasFill::Transparent),
+        "blur" => Ok(CanvasFill::Blur),
+        "edge" => Ok(CanvasFill::Edge),
+        _ => Err(format!("unknown canvas fill '{}'", s)),
+    }
+}
+
+fn parse_offset(s: &str) -> Result<(f32, f32), String> {
+    let invalid = || format!("expected X,Y, got '{}'", s);
+    let (x, y) = s.split_once(',').ok_or_else(invalid)?;
+    let number = |part: &str| part.trim().parse::<f32>().map_err(|_| invalid());
+    Ok((number(x)?, number(y)?))
+}
+
+fn parse_blend_mode(s: &str) -> Result<BlendMode, String> {
+    match s {
+        "normal" => Ok(BlendMode::Normal),
+        "multiply" => Ok(BlendMode::Multiply),
+        "screen" => Ok(BlendMode::Screen),
+        "overlay" => Ok(BlendMode::Overlay),
+        _ => Err(format!("unknown blend mode '{}'", s)),
+    }
+}
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|seconds| *seconds > 0.0)
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("expected a positive number of seconds, got '{}'", s))
+}
+
+fn parse_color(s: &str) -> Result<[u8; 3], String> {
+    let hex = s.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("expected #rrggbb, got '{}'", s));
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| format!("invalid colour '{}'", s))
+    };
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn main() -> ExitCode {
+    if worker::is_worker_invocation() {
+        return ExitCode::from(worker::serve());
+    }
+
+    let cli = Cli::parse();
+
+    match run(&cli) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("pixelconvert-cli: {:#}", e);
+            match e.downcast_ref::<ConvertError>() {
+                Some(error) => {
+                    if let Some(hint) = error.hint() {
+                        eprintln!("hint: {}", hint);
+                    }
+                    ExitCode::from(exit_code(error))
+                }
+                None => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<ExitCode> {
+    let options = cli.options()?;
+    let stdio = Path::new("-");
+
+    if cli.watch {
+        return watch(cli, options);
+    }
+
+    if cli.inputs.len() == 1 && cli.inputs[0] == stdio {
+        convert_stdin(options, cli.output.as_deref())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if cli.inputs.iter().any(|input| input == stdio) {
+        anyhow::bail!("`-` (stdin) can't be combined with other inputs");
+    }
+
+    // A single input with an output that isn't a directory is a plain file conversion
+    if let [input] = cli.inputs.as_slice() {
+        if let Some(output) = cli.output.as_deref().filter(|o| !o.is_dir()) {
+            if cli.journal.is_some() || cli.report.is_some() {
+                anyhow::bail!(
+                    "--journal and --report only apply to batches written to a directory"
+                );
+            }
+            let converter = ImageConverter::new(options.clone());
+            let stats = if output == stdio {
+                let data = std::fs::read(input)
+                    .map_err(|e| ConvertError::io("Failed to open image", e))?;
+                converter.convert_stream(data.as_slice(), std::io::stdout().lock())?
+            } else if let Execution::Isolated { worker, timeout } = cli.execution()? {
+                worker::convert_isolated(&worker, input, output, &options, timeout)?
+            } else {
+                converter.convert(input, output)?
+            };
+            eprintln!(
+                "{} → {} ({} bytes)",
+                input.display(),
+                output.display(),
+                stats.output_bytes
+            );
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+
+    if cli.output.as_deref() == Some(stdio) {
+        anyhow::bail!("only a single input can be written to stdout");
+    }
+    if let Some(dir) = &cli.output {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| ConvertError::io("Failed to create output directory", e))?;
+    }
+
+    let jobs = cli
+        .inputs
+        .iter()
+        .map(|input| BatchJob {
+            input_path: input.clone(),
+            output_path: pixelconvert_core::output_path_for(
+                input,
+                cli.output.as_deref(),
+                options.format,
+            ),
+            options: options.clone(),
+            incremental: cli.incremental,
+        })
+        .collect();
+
+    run_batch(
+        jobs,
+        cli.execution()?,
+        cli.journal.as_deref(),
+        cli.report.as_deref(),
+    )
+}
+
+fn convert_stdin(options: ConversionOptions, output: Option<&Path>) -> Result<()> {
+    let converter = ImageConverter::new(options);
+    let stdin = std::io::stdin().lock();
+
+    match output {
+        Some(path) if path != Path::new("-") => {
+            let file = std::fs::File::create(path)
+                .map_err(|e| ConvertError::io("Failed to create output file", e))?;
+            converter.convert_stream(stdin, std::io::BufWriter::new(file))?;
+        }
+        _ => {
+            converter.convert_stream(stdin, std::io::stdout().lock())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Convert several files in parallel, reporting progress on stderr and
+/// optionally in a report file. Exits with a failure's own code if every
+/// failure has the same cause.
+fn run_batch(
+    jobs: Vec<BatchJob>,
+    execution: Execution,
+    journal_path: Option<&Path>,
+    report_path: Option<&Path>,
+) -> Result<ExitCode> {
+    let mut failure_codes = Vec::new();
+    let (jobs, mut journal) = match journal_path {
+        Some(path) => match Journal::load(path)? {
+            Some(mut batch) if batch.jobs.iter().map(|(job, _)| job).eq(&jobs) => {
+                // Failures may have been transient, so they're tried again
+                let retried = batch.failed();
+                for (_, state) in &mut batch.jobs {
+                    if matches!(state, JobState::Failed(_)) {
+                        *state = JobState::Pending;
+                    }
+                }
+                if retried > 0 {
+                    eprintln!(
+                        "Resuming: {} already converted, {} remaining, including {} that failed",
+                        batch.completed(),
+                        batch.pending(),
+                        retried
+                    );
+                } else {
+                    eprintln!(
+                        "Resuming: {} already converted, {} remaining",
+                        batch.completed(),
+                        batch.pending()
+                    );
+                }
+                let (journal, pending) = Journal::resume(path, &batch)?;
+                (pending, Some(journal))
+            }
+            Some(batch) => {
+                let same_files = batch.jobs.len() == jobs.len()
+                    && batch.jobs.iter().zip(&jobs).all(|((journaled, _), job)| {
+                        journaled.input_path == job.input_path
+                            && journaled.output_path == job.output_path
+                    });
+                if !same_files {
+                    anyhow::bail!(
+                        "{} belongs to a different batch; delete it to start over",
+                        path.display()
+                    );
+                }
+                eprintln!(
+                    "{} was recorded with different settings; starting the batch over",
+                    path.display()
+                );
+                let journal = Journal::create(path, &jobs)?;
+                (jobs, Some(journal))
+            }
+            None => {
+                let journal = Journal::create(path, &jobs)?;
+                (jobs, Some(journal))
+            }
+        },
+        None => (jobs, None),
+    };
+
+    let mut stats = BatchStats::new(jobs.len());
+    let mut report = report_path.map(|_| BatchReport::new(&jobs));
+    let batch = pixelconvert_core::run_batch_with(jobs, execution);
+
+    for progress in batch.progress {
+        if let Some(journal) = journal.as_mut() {
+            journal.record(&progress)?;
+        }
+        if let Some(report) = report.as_mut() {
+            report.record(&progress);
+        }
+        stats.record(&progress);
+        match progress {
+            BatchProgress::Measured { .. }
+            | BatchProgress::Processing { .. }
+            | BatchProgress::Paused
+            | BatchProgress::Resumed
+            | BatchProgress::Added { .. }
+            | BatchProgress::Removed { .. } => {}
+            BatchProgress::Completed {
+                file,
+                stats: job_stats,
+                ..
+            } => {
+                eprintln!(
+                    "[{}] {} ({} bytes)",
+                    stats.progress_line(),
+                    file,
+                    job_stats.output_bytes
+                );
+            }
+            BatchProgress::Failed { file, error, .. } => {
+                eprintln!("[{}] {} failed: {}", stats.progress_line(), file, error);
+                failure_codes.push(exit_code(&error));
+            }
+            BatchProgress::Skipped { file, .. } => {
+                eprintln!("[{}] {} is up to date", stats.progress_line(), file);
+            }
+            BatchProgress::CacheNotSaved { error } => {
+                eprintln!("Output cache not saved: {}", error);
+            }
+            BatchProgress::Finished {
+                successful,
+                failed,
+                skipped,
+            } => {
+                if skipped > 0 {
+                    eprintln!(
+                        "Completed: {} succeeded, {} failed, {} up to date",
+                        successful, failed, skipped
+                    );
+                } else {
+                    eprintln!("Completed: {} succeeded, {} failed", successful, failed);
+                }
+                if successful > 0 {
+                    eprintln!("Statistics: {}", stats.summary());
+                }
+                if let Some(journal) = journal.take() {
+                    journal.finish()?;
+                }
+                if let (Some(mut report), Some(path)) = (report.take(), report_path) {
+                    report.save(path)?;
+                    eprintln!("Report written to {}", path.display());
+                }
+                failure_codes.sort_unstable();
+                failure_codes.dedup();
+                return Ok(match failure_codes.as_slice() {
+                    [] => ExitCode::SUCCESS,
+                    [code] => ExitCode::from(*code),
+                    _ => ExitCode::FAILURE,
+                });
+            }
+        }
+    }
+
+    anyhow::bail!("batch processor stopped unexpectedly")
+}
+
+/// How often watch mode checks for settled files
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Convert images added to the input folders until interrupted
+fn watch(cli: &Cli, options: ConversionOptions) -> Result<ExitCode> {
+    use inotify::{EventMask, Inotify, WatchMask};
+
+    for folder in &cli.inputs {
+        if !folder.is_dir() {
+            anyhow::bail!("--watch needs folders, but {} isn't one", folder.display());
+        }
+    }
+    if let Some(dir) = &cli.output {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| ConvertError::io("Failed to create output directory", e))?;
+    }
+
+    let watch = WatchFolder {
+        folders: cli.inputs.clone(),
+        output_dir: cli.output.clone(),
+        options,
+    };
+    let history = cli
+        .history
+        .clone()
+        .or_else(WatchHistory::default_path)
+        .map(WatchHistory::new);
+    let execution = cli.execution()?;
+
+    let mut inotify =
+        Inotify::init().map_err(|e| ConvertError::io("Failed to watch folders", e))?;
+    let mut folders = std::collections::HashMap::new();
+    for folder in &watch.folders {
+        // CLOSE_WRITE and MOVED_TO mark finished files; the debouncer
+        // covers writers that keep a file open between writes
+        let descriptor = inotify
+            .watches()
+            .add(
+                folder,
+                WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::MODIFY,
+            )
+            .map_err(|e| ConvertError::io("Failed to watch folder", e))?;
+        folders.insert(descriptor, folder.clone());
+    }
+
+    convert_watched(watch.existing_jobs()?, &execution, history.as_ref());
+    eprintln!("Watching {} folder(s), press Ctrl+C to stop", folders.len());
+
+    let mut debouncer = Debouncer::new(cli.settle.unwrap_or(DEFAULT_SETTLE_TIME));
+    let mut buffer = [0; 4096];
+    loop {
+        match inotify.read_events(&mut buffer) {
+            Ok(events) => {
+                for event in events {
+                    if event.mask.contains(EventMask::Q_OVERFLOW) {
+                        // Events were dropped, so look at everything again
+                        for job in watch.existing_jobs()? {
+                            debouncer.notice(job.input_path);
+                        }
+                    } else if let (Some(folder), Some(name)) = (folders.get(&event.wd), event.name)
+                    {
+                        debouncer.notice(folder.join(name));
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(ConvertError::io("Failed to watch folders", e).into()),
+        }
+
+        let jobs: Vec<BatchJob> = debouncer
+            .settled()
+            .iter()
+            .filter_map(|path| watch.job_for(path))
+            .collect();
+        if !jobs.is_empty() {
+            convert_watched(jobs, &execution, history.as_ref());
+        }
+        std::thread::sleep(WATCH_POLL_INTERVAL);
+    }
+}
+
+/// Convert settled files from watched folders, reporting each on stderr and
+/// in the history. Failures are reported but don't stop watching.
+fn convert_watched(jobs: Vec<BatchJob>, execution: &Execution, history: Option<&WatchHistory>) {
+    if jobs.is_empty() {
+        return;
+    }
+
+    let batch = pixelconvert_core::run_batch_with(jobs.clone(), execution.clone());
+
+    for progress in batch.progress {
+        match progress {
+            BatchProgress::Finished { .. } => return,
+            BatchProgress::CacheNotSaved { error } => {
+                eprintln!("Output cache not saved: {}", error);
+                continue;
+            }
+            _ => {}
+        }
+        let Some(entry) = HistoryEntry::from_progress(&jobs, &progress) else {
+            continue;
+        };
+        match &entry.outcome {
+            WatchOutcome::Converted { output_bytes } => eprintln!(
+                "{} → {} ({} bytes)",
+                entry.input_path.display(),
+                entry.output_path.display(),
+                output_bytes
+            ),
+            WatchOutcome::Unchanged => {}
+            WatchOutcome::Failed(error) => {
+                eprintln!("{} failed: {}", entry.input_path.display(), error)
+            }
+        }
+        if let Some(history) = history {
+            if let Err(e) = history.append(&entry) {
+                eprintln!("pixelconvert-cli: {}", e);
+            }
+        }
+    }
+}
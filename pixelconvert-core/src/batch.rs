@@ -0,0 +1,422 @@
+use crate::converter::{ConversionOptions, ConversionStats, ImageConverter, SupportedFormat};
+use crate::error::{ConvertError, Result};
+use crate::incremental::{self, CacheEntry, OutputCache};
+use crate::progress::{progress_channel, ProgressReceiver, ProgressSender};
+use crate::queue::{BatchQueue, Next};
+use crate::worker::{self, WorkerCommand};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Progress update from batch processor. `index` is the job's position in
+/// the list passed to `run_batch()`, or for jobs added later, the one
+/// `BatchQueue::push()` returned. Every job reports `Processing` when a
+/// worker takes it, in queue order, then one of `Completed`, `Failed` or
+/// `Skipped` — unless it is removed from the queue first.
+#[derive(Debug, Clone)]
+pub enum BatchProgress {
+    /// Size of a job's input, read in the background as the batch starts
+    /// so progress can be weighted by it before the job runs. Jobs added
+    /// later and inputs that can't be read aren't measured.
+    Measured {
+        index: usize,
+        input_bytes: u64,
+    },
+    Processing {
+        index: usize,
+        file: String,
+    },
+    Completed {
+        index: usize,
+        file: String,
+        stats: ConversionStats,
+        /// Time the conversion took
+        elapsed: Duration,
+    },
+    Failed {
+        index: usize,
+        file: String,
+        error: ConvertError,
+        elapsed: Duration,
+    },
+    /// An incremental job whose output was already up to date
+    Skipped {
+        index: usize,
+        file: String,
+    },
+    /// `BatchQueue::pause()` was called; jobs already running still report
+    /// their results
+    Paused,
+    /// `BatchQueue::resume()` was called
+    Resumed,
+    /// A job pushed to the running batch with `BatchQueue::push()`
+    Added {
+        index: usize,
+        job: BatchJob,
+    },
+    /// A queued job taken off the batch with `BatchQueue::remove()`
+    Removed {
+        index: usize,
+        file: String,
+    },
+    /// The incremental output cache couldn't be written when the batch
+    /// finished, so its files will be converted again next time
+    CacheNotSaved {
+        error: ConvertError,
+    },
+    Finished {
+        successful: usize,
+        failed: usize,
+        skipped: usize,
+    },
+}
+
+/// Batch conversion job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchJob {
+    pub input_path: PathBuf,
+    pub output_path: PathBuf,
+    pub options: ConversionOptions,
+    /// Skip the job if its output is already up to date (see `incremental`)
+    #[serde(default)]
+    pub incremental: bool,
+}
+
+/// Where batch jobs run
+#[derive(Debug, Clone, Default)]
+pub enum Execution {
+    /// On threads in this process (fastest)
+    #[default]
+    InProcess,
+    /// Each job in its own worker process, killed after `timeout`; a codec
+    /// crash or hang then fails only that file
+    Isolated {
+        worker: WorkerCommand,
+        timeout: Option<Duration>,
+    },
+}
+
+impl Execution {
+    fn convert(&self, job: &BatchJob) -> Result<ConversionStats> {
+        match self {
+            Self::InProcess => {
+                let converter = ImageConverter::new(job.options.clone());
+                // A panicking codec fails the job instead of the whole batch
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    converter.convert(&job.input_path, &job.output_path)
+                }))
+                .unwrap_or_else(|panic| {
+                    let message = panic
+                        .downcast_ref::<&str>()
+                        .map(|s| s.to_string())
+                        .or_else(|| panic.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown panic".to_string());
+                    Err(ConvertError::Crashed(format!("panicked: {}", message)))
+                })
+            }
+            Self::Isolated { worker, timeout } => worker::convert_isolated(
+                worker,
+                &job.input_path,
+                &job.output_path,
+                &job.options,
+                *timeout,
+            ),
+        }
+    }
+}
+
+/// Output path for `input` converted to `format`: next to the source, or
+/// in `out_dir` if given, with the format's extension
+pub fn output_path_for(input: &Path, out_dir: Option<&Path>, format: SupportedFormat) -> PathBuf {
+    match out_dir {
+        Some(dir) => {
+            let file_stem = input.file_stem().unwrap_or_default();
+            let mut out = dir.join(file_stem);
+            out.set_extension(format.extension());
+            out
+        }
+        None => input.with_extension(format.extension()),
+    }
+}
+
+/// How often a batch writes the output cache while it runs
+const CACHE_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A batch running on the worker pool
+#[derive(Debug)]
+pub struct Batch {
+    /// Adds, reorders and removes jobs, and pauses or resumes the batch,
+    /// while it runs
+    pub queue: BatchQueue,
+    /// Every job's progress, ending with `Finished`
+    pub progress: ProgressReceiver,
+}
+
+/// Run batch conversion on a background worker pool, one worker per CPU
+pub fn run_batch(jobs: Vec<BatchJob>) -> Batch {
+    run_batch_with(jobs, Execution::InProcess)
+}
+
+/// Like `run_batch()`, choosing where each job runs
+pub fn run_batch_with(jobs: Vec<BatchJob>, execution: Execution) -> Batch {
+    let (sender, progress) = progress_channel();
+
+    // Measured here so a slow disk doesn't hold up whoever started the batch
+    let inputs: Vec<PathBuf> = jobs.iter().map(|job| job.input_path.clone()).collect();
+    let measure = sender.clone();
+    std::thread::spawn(move || {
+        for (index, input) in inputs.iter().enumerate() {
+            if let Ok(metadata) = std::fs::metadata(input) {
+                let input_bytes = metadata.len();
+                if !measure.send(BatchProgress::Measured { index, input_bytes }) {
+                    return;
+                }
+            }
+        }
+    });
+
+    let queue = BatchQueue::new(jobs, sender.clone());
+    let pool = Arc::new(Pool {
+        queue: queue.clone(),
+        execution,
+        cache_path: OutputCache::default_path(),
+        cache: Mutex::new(None),
+        cache_saved: Mutex::new(Instant::now()),
+        counts: Mutex::new(Counts::default()),
+    });
+
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4);
+    for _ in 0..workers {
+        let pool = Arc::clone(&pool);
+        let sender = sender.clone();
+        std::thread::spawn(move || pool.work(&sender));
+    }
+    Batch { queue, progress }
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    successful: usize,
+    failed: usize,
+    skipped: usize,
+}
+
+/// State shared by a batch's workers
+struct Pool {
+    queue: BatchQueue,
+    execution: Execution,
+    cache_path: Option<PathBuf>,
+    /// Loaded when the first incremental job runs
+    cache: Mutex<Option<OutputCache>>,
+    /// When the cache was last written, so a crash loses at most
+    /// `CACHE_SAVE_INTERVAL` of entries. Held while writing it, so
+    /// periodic saves don't overlap.
+    cache_saved: Mutex<Instant>,
+    counts: Mutex<Counts>,
+}
+
+impl Pool {
+    /// Worker loop: run jobs until the batch finishes. The worker that
+    /// finds nothing left to do reports `Finished`.
+    fn work(&self, sender: &ProgressSender) {
+        loop {
+            let next = self.queue.take(|index, job| {
+                sender.send(BatchProgress::Processing {
+                    index,
+                    file: file_name(job),
+                });
+            });
+            match next {
+                Next::Job(index, job) => {
+                    self.run(index, &job, sender);
+                    self.queue.done();
+                }
+                Next::Finish => return self.finish(sender),
+                Next::Exit => return,
+            }
+        }
+    }
+
+    fn run(&self, index: usize, job: &BatchJob, sender: &ProgressSender) {
+        let file = file_name(job);
+
+        // Hashing happens outside the lock so workers don't queue on it
+        let up_to_date = job.incremental && {
+            let entry = self
+                .cache
+                .lock()
+                .unwrap()
+                .get_or_insert_with(|| self.load_cache())
+                .get(&job.output_path)
+                .cloned();
+            incremental::is_up_to_date(job, entry.as_ref())
+        };
+        if up_to_date {
+            self.counts.lock().unwrap().skipped += 1;
+            sender.send(BatchProgress::Skipped { index, file });
+            return;
+        }
+
+        let started = Instant::now();
+        match self.execution.convert(job) {
+            Ok(stats) => {
+                if job.incremental {
+                    if let Ok(entry) = CacheEntry::for_job(job, stats.output_bytes) {
+                        if let Some(cache) = self.cache.lock().unwrap().as_mut() {
+                            cache.insert(&job.output_path, entry);
+                        }
+                        // Another worker holding this is already saving
+                        if let Ok(mut saved) = self.cache_saved.try_lock() {
+                            if saved.elapsed() >= CACHE_SAVE_INTERVAL {
+                                // A failure here is retried, and reported, at the end
+                                let _ = self.save_cache();
+                                *saved = Instant::now();
+                            }
+                        }
+                    }
+                }
+                self.counts.lock().unwrap().successful += 1;
+                sender.send(BatchProgress::Completed {
+                    index,
+                    file,
+                    stats,
+                    elapsed: started.elapsed(),
+                });
+            }
+            Err(error) => {
+                self.counts.lock().unwrap().failed += 1;
+                sender.send(BatchProgress::Failed {
+                    index,
+                    file,
+                    error,
+                    elapsed: started.elapsed(),
+                });
+            }
+        }
+    }
+
+    fn load_cache(&self) -> OutputCache {
+        self.cache_path
+            .as_deref()
+            .map(OutputCache::load)
+            .unwrap_or_default()
+    }
+
+    /// Write the cache's new entries. The cache is locked only to take
+    /// them out, so workers don't wait on the file being rewritten.
+    fn save_cache(&self) -> Result<()> {
+        let Some(path) = self.cache_path.as_deref() else {
+            return Ok(());
+        };
+        let Some(changes) = self
+            .cache
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map(OutputCache::take_changes)
+        else {
+            return Ok(());
+        };
+        let result = changes.save(path);
+        if result.is_err() {
+            if let Some(cache) = self.cache.lock().unwrap().as_mut() {
+                cache.restore_changes(changes);
+            }
+        }
+        result
+    }
+
+    fn finish(&self, sender: &ProgressSender) {
+        // An unsaved cache only means converting some files again next time
+        if let Err(error) = self.save_cache() {
+            sender.send(BatchProgress::CacheNotSaved { error });
+        }
+
+        let counts = self.counts.lock().unwrap();
+        sender.send(BatchProgress::Finished {
+            successful: counts.successful,
+            failed: counts.failed,
+            skipped: counts.skipped,
+        });
+    }
+}
+
+pub(crate) fn file_name(job: &BatchJob) -> String {
+    job.input_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_worker_pool_runs_every_job() {
+        let dir = TempDir::new("pool");
+
+        let jobs: Vec<BatchJob> = (0..6)
+            .map(|n| {
+                let input_path = dir.join(format!("{}.png", n));
+                // Every other input is missing, so its job fails
+                if n % 2 == 0 {
+                    image::RgbImage::new(4, 4).save(&input_path).unwrap();
+                }
+                BatchJob {
+                    input_path,
+                    output_path: dir.join(format!("{}.webp", n)),
+                    options: ConversionOptions {
+                        format: SupportedFormat::WebP,
+                        ..Default::default()
+                    },
+                    incremental: false,
+                }
+            })
+            .collect();
+
+        let batch = run_batch(jobs);
+        let mut started = Vec::new();
+        let mut finished = None;
+        for progress in batch.progress {
+            match progress {
+                BatchProgress::Processing { index, .. } => started.push(index),
+                BatchProgress::Finished {
+                    successful, failed, ..
+                } => finished = Some((successful, failed)),
+                _ => {}
+            }
+        }
+
+        // Taken in submission order, however many workers there are
+        assert_eq!(started, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(finished, Some((3, 3)));
+        assert!(batch.queue.is_finished());
+        let late = BatchJob {
+            input_path: dir.join("late.png"),
+            output_path: dir.join("late.webp"),
+            options: ConversionOptions::default(),
+            incremental: false,
+        };
+        assert_eq!(batch.queue.push(late), None);
+    }
+
+    #[test]
+    fn test_empty_batch_finishes() {
+        let batch = run_batch(Vec::new());
+        assert!(matches!(
+            batch.progress.recv(),
+            Some(BatchProgress::Finished {
+                successful: 0,
+                failed: 0,
+                skipped: 0
+            })
+        ));
+        assert!(batch.progress.recv().is_none());
+    }
+}
@@ -0,0 +1,1030 @@
+use crate::error::{ConvertError, IoContext, Result};
+use crate::icon;
+use crate::limits::DecodeLimits;
+use crate::quantize::{self, Quantization};
+use crate::transform::{self, Canvas, Crop, Orientation, Trim};
+use crate::watermark::Watermark;
+use image::{DynamicImage, ImageDecoder, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Supported image formats for conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SupportedFormat {
+    Png,
+    Jpeg,
+    WebP,
+    Avif,
+    Gif,
+    Bmp,
+    Tiff,
+    Ico,
+}
+
+impl SupportedFormat {
+    pub fn extension(&self) -> &str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::WebP => "webp",
+            Self::Avif => "avif",
+            Self::Gif => "gif",
+            Self::Bmp => "bmp",
+            Self::Tiff => "tiff",
+            Self::Ico => "ico",
+        }
+    }
+
+    pub fn mime_type(&self) -> &str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::WebP => "image/webp",
+            Self::Avif => "image/avif",
+            Self::Gif => "image/gif",
+            Self::Bmp => "image/bmp",
+            Self::Tiff => "image/tiff",
+            Self::Ico => "image/x-icon",
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match self {
+            Self::Png => "PNG",
+            Self::Jpeg => "JPEG",
+            Self::WebP => "WebP",
+            Self::Avif => "AVIF",
+            Self::Gif => "GIF",
+            Self::Bmp => "BMP",
+            Self::Tiff => "TIFF",
+            Self::Ico => "ICO",
+        }
+    }
+
+    /// Map a file extension (case-insensitive) to a format
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "webp" => Some(Self::WebP),
+            "avif" => Some(Self::Avif),
+            "gif" => Some(Self::Gif),
+            "bmp" => Some(Self::Bmp),
+            "tif" | "tiff" => Some(Self::Tiff),
+            "ico" => Some(Self::Ico),
+            _ => None,
+        }
+    }
+
+    /// Map an `image` crate format to one we support
+    pub fn from_image_format(format: ImageFormat) -> Option<Self> {
+        match format {
+            ImageFormat::Png => Some(Self::Png),
+            ImageFormat::Jpeg => Some(Self::Jpeg),
+            ImageFormat::WebP => Some(Self::WebP),
+            ImageFormat::Avif => Some(Self::Avif),
+            ImageFormat::Gif => Some(Self::Gif),
+            ImageFormat::Bmp => Some(Self::Bmp),
+            ImageFormat::Tiff => Some(Self::Tiff),
+            ImageFormat::Ico => Some(Self::Ico),
+            _ => None,
+        }
+    }
+
+    /// Whether the format can store an alpha channel
+    pub fn supports_alpha(&self) -> bool {
+        !matches!(self, Self::Jpeg | Self::Bmp)
+    }
+
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::Png,
+            Self::Jpeg,
+            Self::WebP,
+            Self::Avif,
+            Self::Gif,
+            Self::Bmp,
+            Self::Tiff,
+            Self::Ico,
+        ]
+    }
+}
+
+/// Lossless PNG optimization settings (applied through oxipng)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PngOptimization {
+    pub level: u8, // 0-6, oxipng preset
+    /// Try every row filter strategy instead of the preset's subset
+    pub filter_search: bool,
+    /// Reduce bit depth and colour type (RGBA→RGB, grayscale, palette)
+    pub reduce: bool,
+    /// Remove ancillary chunks that don't affect rendering
+    pub strip_metadata: bool,
+    /// Use zopfli instead of libdeflate (much slower, slightly smaller)
+    pub zopfli: bool,
+}
+
+impl Default for PngOptimization {
+    fn default() -> Self {
+        Self {
+            level: 2,
+            filter_search: false,
+            reduce: true,
+            strip_metadata: true,
+            zopfli: false,
+        }
+    }
+}
+
+impl PngOptimization {
+    fn to_oxipng(&self) -> oxipng::Options {
+        let mut opts = oxipng::Options::from_preset(self.level.min(6));
+
+        if self.filter_search {
+            use oxipng::RowFilter;
+            opts.filter = oxipng::indexset! {
+                RowFilter::None,
+                RowFilter::Sub,
+                RowFilter::Up,
+                RowFilter::Average,
+                RowFilter::Paeth,
+                RowFilter::MinSum,
+                RowFilter::Entropy,
+                RowFilter::Bigrams,
+                RowFilter::BigEnt,
+                RowFilter::Brute,
+            };
+            opts.fast_evaluation = false;
+        }
+
+        opts.bit_depth_reduction = self.reduce;
+        opts.color_type_reduction = self.reduce;
+        opts.palette_reduction = self.reduce;
+        opts.grayscale_reduction = self.reduce;
+
+        opts.strip = if self.strip_metadata {
+            oxipng::StripChunks::Safe
+        } else {
+            oxipng::StripChunks::None
+        };
+
+        if self.zopfli {
+            opts.deflate = oxipng::Deflaters::Zopfli {
+                iterations: std::num::NonZeroU8::new(15).unwrap(),
+            };
+        }
+
+        opts
+    }
+}
+
+/// How transparent pixels are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlphaPolicy {
+    /// Keep transparency; flatten onto the background colour only when
+    /// the output format can't store alpha
+    Keep,
+    /// Always flatten onto the background colour
+    Flatten,
+    /// Always flatten onto a light checkerboard
+    Checkerboard,
+    /// Refuse to convert transparent images to formats without alpha
+    Fail,
+}
+
+impl AlphaPolicy {
+    pub fn display_name(&self) -> &str {
+        match self {
+            Self::Keep => "Keep",
+            Self::Flatten => "Flatten onto Colour",
+            Self::Checkerboard => "Flatten onto Checkerboard",
+            Self::Fail => "Fail",
+        }
+    }
+
+    pub fn all() -> Vec<Self> {
+        vec![Self::Keep, Self::Flatten, Self::Checkerboard, Self::Fail]
+    }
+}
+
+/// Conversion quality/compression settings. Saved as JSON, they double as
+/// presets; fields a preset leaves out keep their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversionOptions {
+    pub quality: u8, // 0-100
+    pub format: SupportedFormat,
+    /// Optimization pass for PNG output; `None` writes the encoder's output as-is
+    pub png_optimization: Option<PngOptimization>,
+    /// Palette reduction for PNG (indexed) and GIF output
+    pub quantization: Option<Quantization>,
+    pub alpha_policy: AlphaPolicy,
+    /// Colour transparent pixels are flattened onto
+    pub background: [u8; 3],
+    /// Resolutions embedded in ICO output (each at most 256)
+    pub ico_sizes: Vec<u32>,
+    /// Limits on the input images this converter will decode
+    pub limits: DecodeLimits,
+    /// Part of the source image to keep; usually set per file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crop: Option<Crop>,
+    /// Uniform border to trim, after the crop
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim: Option<Trim>,
+    /// Rotation and flips, applied after the crop and trim
+    #[serde(skip_serializing_if = "Orientation::is_identity")]
+    pub orientation: Orientation,
+    /// Larger canvas to place the image on, last; filled with `background`
+    /// for `CanvasFill::Solid`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canvas: Option<Canvas>,
+    /// Logo composited onto the finished image
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<Watermark>,
+}
+
+impl Default for ConversionOptions {
+    fn default() -> Self {
+        Self {
+            quality: 85,
+            format: SupportedFormat::Png,
+            png_optimization: Some(PngOptimization::default()),
+            quantization: None,
+            alpha_policy: AlphaPolicy::Keep,
+            background: [255, 255, 255],
+            ico_sizes: icon::DEFAULT_ICO_SIZES.to_vec(),
+            limits: DecodeLimits::default(),
+            crop: None,
+            trim: None,
+            orientation: Orientation::default(),
+            canvas: None,
+            watermark: None,
+        }
+    }
+}
+
+impl ConversionOptions {
+    /// Read options saved with `save_preset()` or written by hand
+    pub fn load_preset<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read(path.as_ref()).io_context("Failed to read preset")?;
+        serde_json::from_slice(&data).map_err(|e| ConvertError::io("Invalid preset", e.into()))
+    }
+
+    pub fn save_preset<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|e| ConvertError::io("Failed to write preset", e.into()))?;
+        std::fs::write(path.as_ref(), data).io_context("Failed to write preset")
+    }
+}
+
+/// Summary of a finished conversion
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConversionStats {
+    /// Size of the source image in bytes, if it was converted from encoded data
+    pub input_bytes: Option<u64>,
+    /// Format of the source image, if it was converted from encoded data
+    pub input_format: Option<SupportedFormat>,
+    /// Dimensions of the source image
+    pub width: u32,
+    pub height: u32,
+    /// Size of the written output file in bytes
+    pub output_bytes: u64,
+    /// PNG size before the optimization pass, if one ran
+    pub unoptimized_bytes: Option<u64>,
+    /// Number of palette entries, if the output was quantized
+    pub palette_size: Option<usize>,
+}
+
+impl ConversionStats {
+    /// Pixel count of the source image
+    pub fn pixels(&self) -> u64 {
+        u64::from(self.width) * u64::from(self.height)
+    }
+
+    /// Bytes saved by the PNG optimization pass
+    pub fn optimization_savings(&self) -> u64 {
+        self.unoptimized_bytes
+            .map(|before| before.saturating_sub(self.output_bytes))
+            .unwrap_or(0)
+    }
+}
+
+/// What transparent pixels are composited onto
+enum Background {
+    Solid([u8; 3]),
+    Checkerboard,
+}
+
+impl Background {
+    const CHECKER_SIZE: u32 = 8;
+    const CHECKER_LIGHT: [u8; 3] = [255, 255, 255];
+    const CHECKER_DARK: [u8; 3] = [204, 204, 204];
+
+    fn color_at(&self, x: u32, y: u32) -> [u8; 3] {
+        match self {
+            Self::Solid(color) => *color,
+            Self::Checkerboard => {
+                if (x / Self::CHECKER_SIZE + y / Self::CHECKER_SIZE).is_multiple_of(2) {
+                    Self::CHECKER_LIGHT
+                } else {
+                    Self::CHECKER_DARK
+                }
+            }
+        }
+    }
+}
+
+/// An encoded image along with its conversion summary
+#[derive(Debug, Clone)]
+pub struct EncodedImage {
+    pub data: Vec<u8>,
+    pub stats: ConversionStats,
+}
+
+/// Image converter
+pub struct ImageConverter {
+    options: ConversionOptions,
+}
+
+impl ImageConverter {
+    pub fn new(options: ConversionOptions) -> Self {
+        Self { options }
+    }
+
+    /// Load an image from a file path. The format is taken from the file's
+    /// contents, so misleading or missing extensions don't matter.
+    pub fn load_image<P: AsRef<Path>>(path: P) -> Result<DynamicImage> {
+        Self::load_image_with_limits(path, &DecodeLimits::default())
+    }
+
+    /// Load an image from a file path, refusing it if it exceeds `limits`
+    pub fn load_image_with_limits<P: AsRef<Path>>(
+        path: P,
+        limits: &DecodeLimits,
+    ) -> Result<DynamicImage> {
+        let data = std::fs::read(path.as_ref()).io_context("Failed to open image")?;
+        Self::decode_bytes_with_limits(&data, limits)
+    }
+
+    /// Decode an image from memory, detecting the format from its contents.
+    /// Applies the default `DecodeLimits`.
+    pub fn decode_bytes(data: &[u8]) -> Result<DynamicImage> {
+        Self::decode_bytes_with_limits(data, &DecodeLimits::default())
+    }
+
+    /// Decode an image from memory, refusing it before any pixels are
+    /// decoded if it exceeds `limits`
+    pub fn decode_bytes_with_limits(data: &[u8], limits: &DecodeLimits) -> Result<DynamicImage> {
+        let mut reader = image::ImageReader::new(std::io::Cursor::new(data))
+            .with_guessed_format()
+            .io_context("Failed to read image header")?;
+        let Some(format) = reader.format() else {
+            return Err(ConvertError::UnsupportedInput(
+                "the file isn't a recognized image format".to_string(),
+            ));
+        };
+        reader.limits(limits.to_image_limits());
+
+        let decoder = reader.into_decoder().map_err(ConvertError::decode)?;
+        limits.check_header(decoder.dimensions(), decoder.total_bytes())?;
+        limits.check_frames(data, format)?;
+
+        DynamicImage::from_decoder(decoder).map_err(ConvertError::decode)
+    }
+
+    /// Decode an image from a stream (e.g. stdin), reading it to the end
+    pub fn decode_reader<R: Read>(mut reader: R) -> Result<DynamicImage> {
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .io_context("Failed to read image data")?;
+        Self::decode_bytes(&data)
+    }
+
+    /// Detect a file's format from its magic bytes.
+    /// Returns `None` if the contents aren't a format we support.
+    pub fn detect_format<P: AsRef<Path>>(path: P) -> Result<Option<SupportedFormat>> {
+        let mut header = Vec::with_capacity(16);
+        std::fs::File::open(path.as_ref())
+            .io_context("Failed to open image")?
+            .take(16)
+            .read_to_end(&mut header)
+            .io_context("Failed to read image header")?;
+
+        Ok(Self::detect_format_bytes(&header))
+    }
+
+    /// Detect the format of an in-memory image from its magic bytes
+    pub fn detect_format_bytes(data: &[u8]) -> Option<SupportedFormat> {
+        image::guess_format(data)
+            .ok()
+            .and_then(SupportedFormat::from_image_format)
+    }
+
+    /// Convert and save an image
+    pub fn convert<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        input_path: P,
+        output_path: Q,
+    ) -> Result<ConversionStats> {
+        let data = std::fs::read(input_path.as_ref()).io_context("Failed to open image")?;
+        let encoded = self.convert_bytes(&data)?;
+        std::fs::write(output_path.as_ref(), &encoded.data)
+            .io_context("Failed to write output file")?;
+        Ok(encoded.stats)
+    }
+
+    /// Convert an in-memory image, applying the image operations (crop,
+    /// trim, rotation, flips, canvas, watermark) between decoding and
+    /// encoding
+    pub fn convert_bytes(&self, data: &[u8]) -> Result<EncodedImage> {
+        let img = Self::decode_bytes_with_limits(data, &self.options.limits)?;
+        let (width, height) = (img.width(), img.height());
+        let img = transform::apply(img, &self.options)?;
+        let mut encoded = self.encode(&img)?;
+        encoded.stats.width = width;
+        encoded.stats.height = height;
+        encoded.stats.input_bytes = Some(data.len() as u64);
+        encoded.stats.input_format = Self::detect_format_bytes(data);
+        Ok(encoded)
+    }
+
+    /// Convert from a reader into a writer (e.g. stdin to stdout)
+    pub fn convert_stream<R: Read, W: Write>(
+        &self,
+        mut reader: R,
+        mut writer: W,
+    ) -> Result<ConversionStats> {
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .io_context("Failed to read image data")?;
+        let encoded = self.convert_bytes(&data)?;
+        writer
+            .write_all(&encoded.data)
+            .io_context("Failed to write output")?;
+        writer.flush().io_context("Failed to write output")?;
+        Ok(encoded.stats)
+    }
+
+    /// Generate a favicon bundle (ICO, touch icons, web manifest) into a directory
+    pub fn write_favicon_bundle<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        input_path: P,
+        out_dir: Q,
+    ) -> Result<Vec<std::path::PathBuf>> {
+        let data = std::fs::read(input_path.as_ref()).io_context("Failed to open image")?;
+        let img = Self::decode_bytes_with_limits(&data, &self.options.limits)?;
+        let img = transform::apply(img, &self.options)?;
+        icon::write_favicon_bundle(&img, out_dir.as_ref(), self.options.background)
+    }
+
+    /// Encode an image with the configured format and quality, applying
+    /// the alpha policy first
+    pub fn encode(&self, img: &DynamicImage) -> Result<EncodedImage> {
+        let mut stats = ConversionStats {
+            width: img.width(),
+            height: img.height(),
+            ..Default::default()
+        };
+        let img = self.apply_alpha_policy(img)?;
+        let img = img.as_ref();
+
+        let data = match self.options.format {
+            SupportedFormat::Png => self.encode_png(img, &mut stats)?,
+            SupportedFormat::Jpeg => Self::encode_with_image(img, ImageFormat::Jpeg)?,
+            SupportedFormat::WebP => self.encode_webp(img)?,
+            SupportedFormat::Avif => self.encode_avif(img)?,
+            SupportedFormat::Gif => self.encode_gif(img, &mut stats)?,
+            SupportedFormat::Bmp => Self::encode_with_image(img, ImageFormat::Bmp)?,
+            SupportedFormat::Tiff => Self::encode_with_image(img, ImageFormat::Tiff)?,
+            SupportedFormat::Ico => icon::encode_ico(img, &self.options.ico_sizes)?,
+        };
+
+        stats.output_bytes = data.len() as u64;
+
+        Ok(EncodedImage { data, stats })
+    }
+
+    /// Encode an image and write it to `writer`
+    pub fn encode_to_writer<W: Write>(
+        &self,
+        img: &DynamicImage,
+        mut writer: W,
+    ) -> Result<ConversionStats> {
+        let encoded = self.encode(img)?;
+        writer
+            .write_all(&encoded.data)
+            .io_context("Failed to write output")?;
+        writer.flush().io_context("Failed to write output")?;
+        Ok(encoded.stats)
+    }
+
+    /// Flatten or reject transparency according to the alpha policy and
+    /// what the output format can store
+    fn apply_alpha_policy<'a>(&self, img: &'a DynamicImage) -> Result<Cow<'a, DynamicImage>> {
+        if !img.color().has_alpha() {
+            return Ok(Cow::Borrowed(img));
+        }
+
+        let format = self.options.format;
+        let background = match self.options.alpha_policy {
+            AlphaPolicy::Keep if format.supports_alpha() => return Ok(Cow::Borrowed(img)),
+            AlphaPolicy::Fail if format.supports_alpha() => return Ok(Cow::Borrowed(img)),
+            AlphaPolicy::Keep | AlphaPolicy::Flatten | AlphaPolicy::Fail => {
+                Background::Solid(self.options.background)
+            }
+            AlphaPolicy::Checkerboard => Background::Checkerboard,
+        };
+
+        let rgba = img.to_rgba8();
+        if rgba.pixels().all(|p| p[3] == 255) {
+            // Alpha channel present but unused: just drop it
+            return Ok(Cow::Owned(DynamicImage::ImageRgb8(img.to_rgb8())));
+        }
+
+        if self.options.alpha_policy == AlphaPolicy::Fail {
+            return Err(ConvertError::limitation(
+                format,
+                "the image has transparency and the alpha policy is set to fail",
+            ));
+        }
+
+        let flattened =
+            image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+                let p = rgba.get_pixel(x, y);
+                let bg = background.color_at(x, y);
+                let alpha = p[3] as u32;
+                image::Rgb([0, 1, 2].map(|c| {
+                    ((p[c] as u32 * alpha + bg[c] as u32 * (255 - alpha) + 127) / 255) as u8
+                }))
+            });
+
+        Ok(Cow::Owned(DynamicImage::ImageRgb8(flattened)))
+    }
+
+    /// Encode with the `image` crate's default encoder for a format
+    fn encode_with_image(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut data), format)
+            .map_err(|e| {
+                let format = SupportedFormat::from_image_format(format)
+                    .expect("only called with formats we support");
+                ConvertError::encode(format, e)
+            })?;
+        Ok(data)
+    }
+
+    /// Encode as PNG (indexed if quantization is enabled), running the
+    /// optimization pass if enabled
+    fn encode_png(&self, img: &DynamicImage, stats: &mut ConversionStats) -> Result<Vec<u8>> {
+        let encoded = match &self.options.quantization {
+            Some(quantization) => {
+                let indexed = quantize::quantize(&img.to_rgba8(), quantization);
+                stats.palette_size = Some(indexed.palette_size());
+                indexed.encode_png()?
+            }
+            None => Self::encode_with_image(img, ImageFormat::Png)?,
+        };
+
+        match &self.options.png_optimization {
+            Some(optimization) => {
+                let optimized = oxipng::optimize_from_memory(&encoded, &optimization.to_oxipng())
+                    .map_err(|e| {
+                    ConvertError::limitation(
+                        SupportedFormat::Png,
+                        format!("optimization failed: {}", e),
+                    )
+                })?;
+                stats.unoptimized_bytes = Some(encoded.len() as u64);
+                Ok(optimized)
+            }
+            None => Ok(encoded),
+        }
+    }
+
+    /// Encode as GIF, using our quantizer if enabled instead of the `image` crate's default
+    fn encode_gif(&self, img: &DynamicImage, stats: &mut ConversionStats) -> Result<Vec<u8>> {
+        let Some(quantization) = &self.options.quantization else {
+            return Self::encode_with_image(img, ImageFormat::Gif);
+        };
+
+        let indexed = quantize::quantize(&img.to_rgba8(), quantization);
+        stats.palette_size = Some(indexed.palette_size());
+        indexed.encode_gif()
+    }
+
+    /// Encode as WebP with quality settings
+    fn encode_webp(&self, img: &DynamicImage) -> Result<Vec<u8>> {
+        // Get quality setting
+        let quality = self.options.quality as f32;
+
+        // Create encoder from image
+        let encoder = webp::Encoder::from_image(img)
+            .map_err(|e| ConvertError::limitation(SupportedFormat::WebP, e.to_string()))?;
+
+        // Encode with quality setting
+        let webp_data = encoder.encode(quality);
+
+        Ok(webp_data.to_vec())
+    }
+
+    /// Encode as AVIF with quality settings
+    fn encode_avif(&self, img: &DynamicImage) -> Result<Vec<u8>> {
+        let (width, height) = (img.width(), img.height());
+
+        // Convert image to RGBA (ravif works with RGBA)
+        let rgba = img.to_rgba8();
+        let pixels: Vec<rgb::RGBA8> = rgba
+            .pixels()
+            .map(|p| rgb::RGBA8::new(p[0], p[1], p[2], p[3]))
+            .collect();
+
+        // Create AVIF encoder with quality settings
+        let quality = self.options.quality as f32;
+        let speed = 6; // Balance between speed and compression (1-10, 6 is reasonable)
+
+        let encoder = ravif::Encoder::new()
+            .with_quality(quality)
+            .with_alpha_quality(quality)
+            .with_speed(speed)
+            .with_num_threads(Some(
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(4),
+            ));
+
+        // Encode to AVIF
+        let encoded = encoder
+            .encode_rgba(ravif::Img::new(
+                &pixels[..],
+                width as usize,
+                height as usize,
+            ))
+            .map_err(|e| ConvertError::limitation(SupportedFormat::Avif, e.to_string()))?;
+
+        Ok(encoded.avif_file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_format_extensions() {
+        assert_eq!(SupportedFormat::Png.extension(), "png");
+        assert_eq!(SupportedFormat::WebP.extension(), "webp");
+        assert_eq!(SupportedFormat::Avif.extension(), "avif");
+    }
+
+    #[test]
+    fn test_partial_preset() {
+        // Nested settings a hand-written preset leaves out keep their defaults
+        let options: ConversionOptions = serde_json::from_str(
+            r#"{
+                "format": "png",
+                "png_optimization": { "zopfli": true },
+                "quantization": { "max_colors": 16 },
+                "limits": { "max_width": 1000 }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            options.png_optimization,
+            Some(PngOptimization {
+                zopfli: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            options.quantization,
+            Some(Quantization {
+                max_colors: 16,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            options.limits,
+            DecodeLimits {
+                max_width: 1000,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_content_detection_ignores_extension() {
+        let dir = TempDir::new("detect");
+
+        // A PNG with a misleading extension, and one with no extension at all
+        let mislabeled = dir.join("photo.jpg");
+        let extensionless = dir.join("download");
+        let img = image::RgbImage::from_pixel(4, 4, image::Rgb([1, 2, 3]));
+        img.save_with_format(&mislabeled, ImageFormat::Png).unwrap();
+        img.save_with_format(&extensionless, ImageFormat::Png)
+            .unwrap();
+
+        for path in [&mislabeled, &extensionless] {
+            assert_eq!(
+                ImageConverter::detect_format(path).unwrap(),
+                Some(SupportedFormat::Png)
+            );
+            let loaded = ImageConverter::load_image(path).unwrap();
+            assert_eq!(loaded.to_rgb8(), img);
+        }
+
+        std::fs::write(dir.join("notes.png"), b"not an image").unwrap();
+        assert_eq!(
+            ImageConverter::detect_format(dir.join("notes.png")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_error_classification() {
+        assert!(matches!(
+            ImageConverter::decode_bytes(b"not an image"),
+            Err(ConvertError::UnsupportedInput(_))
+        ));
+
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            16,
+            16,
+            image::Rgba([0, 0, 0, 0]),
+        ));
+        let png = ImageConverter::encode_with_image(&img, ImageFormat::Png).unwrap();
+        assert!(matches!(
+            ImageConverter::decode_bytes(&png[..png.len() / 2]),
+            Err(ConvertError::Decode(_))
+        ));
+
+        let converter = ImageConverter::new(ConversionOptions {
+            format: SupportedFormat::Jpeg,
+            alpha_policy: AlphaPolicy::Fail,
+            ..Default::default()
+        });
+        assert!(matches!(
+            converter.encode(&img),
+            Err(ConvertError::EncoderLimitation {
+                format: SupportedFormat::Jpeg,
+                ..
+            })
+        ));
+
+        let missing = std::env::temp_dir().join("pixelconvert-test-missing.png");
+        assert!(matches!(
+            ImageConverter::load_image(&missing),
+            Err(ConvertError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::new(64, 48));
+        let png = ImageConverter::encode_with_image(&img, ImageFormat::Png).unwrap();
+
+        let exceeds = |limits: DecodeLimits| {
+            matches!(
+                ImageConverter::decode_bytes_with_limits(&png, &limits),
+                Err(ConvertError::LimitExceeded(_))
+            )
+        };
+        assert!(exceeds(DecodeLimits {
+            max_width: 32,
+            ..Default::default()
+        }));
+        assert!(exceeds(DecodeLimits {
+            max_height: 32,
+            ..Default::default()
+        }));
+        assert!(exceeds(DecodeLimits {
+            max_pixels: 64 * 48 - 1,
+            ..Default::default()
+        }));
+        assert!(exceeds(DecodeLimits {
+            max_alloc: 1024,
+            ..Default::default()
+        }));
+        assert!(!exceeds(DecodeLimits::default()));
+
+        // Three-frame GIF
+        let mut gif = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut gif, 4, 4, &[0, 0, 0, 255, 255, 255]).unwrap();
+            for i in 0..3u8 {
+                let frame = gif::Frame {
+                    width: 4,
+                    height: 4,
+                    buffer: std::borrow::Cow::Owned(vec![i % 2; 16]),
+                    ..Default::default()
+                };
+                encoder.write_frame(&frame).unwrap();
+            }
+        }
+        let frames = |max_frames| {
+            ImageConverter::decode_bytes_with_limits(
+                &gif,
+                &DecodeLimits {
+                    max_frames,
+                    ..Default::default()
+                },
+            )
+        };
+        assert!(matches!(frames(2), Err(ConvertError::LimitExceeded(_))));
+        assert!(frames(3).is_ok());
+
+        // Limits from the options apply to conversions
+        let converter = ImageConverter::new(ConversionOptions {
+            limits: DecodeLimits {
+                max_pixels: 100,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert!(matches!(
+            converter.convert_bytes(&png),
+            Err(ConvertError::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn test_in_memory_conversion() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            8,
+            8,
+            image::Rgba([200, 100, 50, 255]),
+        ));
+        let png = ImageConverter::encode_with_image(&img, ImageFormat::Png).unwrap();
+
+        let converter = ImageConverter::new(ConversionOptions {
+            format: SupportedFormat::WebP,
+            ..Default::default()
+        });
+        let encoded = converter.convert_bytes(&png).unwrap();
+        assert_eq!(encoded.stats.output_bytes, encoded.data.len() as u64);
+        assert_eq!(encoded.stats.input_bytes, Some(png.len() as u64));
+        assert_eq!(encoded.stats.input_format, Some(SupportedFormat::Png));
+        assert_eq!((encoded.stats.width, encoded.stats.height), (8, 8));
+        assert_eq!(
+            ImageConverter::detect_format_bytes(&encoded.data),
+            Some(SupportedFormat::WebP)
+        );
+
+        // Streams behave the same as byte slices
+        let mut output = Vec::new();
+        let stats = converter
+            .convert_stream(std::io::Cursor::new(&png), &mut output)
+            .unwrap();
+        assert_eq!(stats.output_bytes, output.len() as u64);
+        let decoded = ImageConverter::decode_bytes(&output).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (8, 8));
+    }
+
+    #[test]
+    fn test_png_optimization_reduces_opaque_rgba() {
+        let dir = TempDir::new("png-opt");
+        let input = dir.join("input.png");
+        let output = dir.join("output.png");
+
+        // Opaque RGBA with few colours: should reduce to a palette image
+        let img = image::RgbaImage::from_fn(64, 64, |x, y| {
+            image::Rgba([(x / 16 * 60) as u8, (y / 16 * 60) as u8, 128, 255])
+        });
+        img.save(&input).unwrap();
+
+        let converter = ImageConverter::new(ConversionOptions::default());
+        let stats = converter.convert(&input, &output).unwrap();
+
+        assert!(stats.unoptimized_bytes.is_some());
+        assert!(stats.optimization_savings() > 0);
+        assert_eq!(
+            stats.output_bytes,
+            std::fs::metadata(&output).unwrap().len()
+        );
+        let decoded = image::open(&output).unwrap().to_rgba8();
+        assert_eq!(decoded, img);
+    }
+
+    #[test]
+    fn test_multi_resolution_ico() {
+        let dir = TempDir::new("ico");
+        let input = dir.join("large.png");
+        let output = dir.join("large.ico");
+
+        // Larger than ICO allows, and not square
+        image::RgbaImage::from_pixel(600, 400, image::Rgba([10, 20, 30, 255]))
+            .save(&input)
+            .unwrap();
+
+        let converter = ImageConverter::new(ConversionOptions {
+            format: SupportedFormat::Ico,
+            ..Default::default()
+        });
+        converter.convert(&input, &output).unwrap();
+
+        // ICONDIR header: reserved, type, image count
+        let data = std::fs::read(&output).unwrap();
+        assert_eq!(u16::from_le_bytes([data[2], data[3]]), 1);
+        assert_eq!(u16::from_le_bytes([data[4], data[5]]), 7);
+
+        // Largest embedded size is 256 (stored as 0)
+        let decoded = image::open(&output).unwrap();
+        assert_eq!(decoded.width(), 256);
+
+        let bundle = dir.join("favicon");
+        let written = converter.write_favicon_bundle(&input, &bundle).unwrap();
+        assert!(written.iter().all(|p| p.exists()));
+        assert!(bundle.join("site.webmanifest").exists());
+        let touch = image::open(bundle.join("apple-touch-icon.png")).unwrap();
+        assert_eq!(touch.width(), 180);
+    }
+
+    #[test]
+    fn test_alpha_policy_for_jpeg() {
+        let dir = TempDir::new("alpha");
+        let input = dir.join("logo.png");
+        let output = dir.join("logo.jpg");
+
+        // Fully transparent except for an opaque white square in the middle
+        let img = image::RgbaImage::from_fn(32, 32, |x, y| {
+            if (8..24).contains(&x) && (8..24).contains(&y) {
+                image::Rgba([255, 255, 255, 255])
+            } else {
+                image::Rgba([0, 0, 0, 0])
+            }
+        });
+        img.save(&input).unwrap();
+
+        let options = ConversionOptions {
+            format: SupportedFormat::Jpeg,
+            quality: 100,
+            background: [255, 0, 0],
+            ..Default::default()
+        };
+
+        // Keep can't keep alpha in JPEG, so it flattens onto the background colour
+        ImageConverter::new(options.clone())
+            .convert(&input, &output)
+            .unwrap();
+        let decoded = image::open(&output).unwrap().to_rgb8();
+        let corner = decoded.get_pixel(0, 0);
+        assert!(corner[0] > 200 && corner[1] < 60 && corner[2] < 60);
+        let center = decoded.get_pixel(16, 16);
+        assert!(center.0.iter().all(|&c| c > 200));
+
+        let failing = ImageConverter::new(ConversionOptions {
+            alpha_policy: AlphaPolicy::Fail,
+            ..options
+        });
+        assert!(failing.convert(&input, &output).is_err());
+    }
+
+    #[test]
+    fn test_quantization_limits_palette() {
+        let dir = TempDir::new("quantize");
+        let input = dir.join("gradient.png");
+
+        // Gradient with a transparent corner
+        let img = image::RgbaImage::from_fn(64, 64, |x, y| {
+            let alpha = if x < 8 && y < 8 { 0 } else { 255 };
+            image::Rgba([(x * 4) as u8, (y * 4) as u8, 200, alpha])
+        });
+        img.save(&input).unwrap();
+
+        for (format, dither) in [
+            (
+                SupportedFormat::Png,
+                crate::quantize::DitherMode::FloydSteinberg,
+            ),
+            (SupportedFormat::Gif, crate::quantize::DitherMode::Ordered),
+        ] {
+            let output = dir.join(format!("out.{}", format.extension()));
+            let converter = ImageConverter::new(ConversionOptions {
+                format,
+                quantization: Some(Quantization {
+                    max_colors: 16,
+                    dither,
+                    alpha_threshold: 128,
+                }),
+                ..Default::default()
+            });
+            let stats = converter.convert(&input, &output).unwrap();
+            assert_eq!(stats.palette_size, Some(16));
+
+            let decoded = image::open(&output).unwrap().to_rgba8();
+            assert_eq!(decoded.dimensions(), (64, 64));
+            assert_eq!(decoded.get_pixel(0, 0)[3], 0);
+            assert_eq!(decoded.get_pixel(63, 63)[3], 255);
+
+            let colors: std::collections::HashSet<_> = decoded.pixels().collect();
+            assert!(colors.len() <= 16);
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! Per-user directories from the XDG base directory spec
+
+use std::path::PathBuf;
+
+/// `$XDG_DATA_HOME/pixelconvert`, falling back to `~/.local/share`
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    user_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// `$XDG_CACHE_HOME/pixelconvert`, falling back to `~/.cache`
+pub(crate) fn cache_dir() -> Option<PathBuf> {
+    user_dir("XDG_CACHE_HOME", ".cache")
+}
+
+fn user_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    let base = std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))?;
+    Some(base.join("pixelconvert"))
+}
@@ -0,0 +1,134 @@
+use crate::converter::SupportedFormat;
+use image::error::{ImageError, LimitErrorKind};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Result type used throughout the conversion engine
+pub type Result<T, E = ConvertError> = std::result::Result<T, E>;
+
+/// Why a conversion failed
+///
+/// Errors are `Clone` and serializable so they can travel through
+/// `BatchProgress` and back from worker processes; causes from other crates
+/// are kept as their messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum ConvertError {
+    /// The input isn't an image format we can read
+    #[error("Unsupported input: {0}")]
+    UnsupportedInput(String),
+    /// The input looks like a supported format but is corrupt or truncated
+    #[error("Failed to decode image: {0}")]
+    Decode(String),
+    /// The input is larger than the configured `DecodeLimits` allow
+    #[error("Image exceeds the decode limits: {0}")]
+    LimitExceeded(String),
+    /// An image operation (such as a crop, canvas or watermark) can't be
+    /// applied to this image
+    #[error("Can't edit the image: {0}")]
+    InvalidOperation(String),
+    /// The output format can't represent this image with these settings
+    #[error("{} can't store this image: {reason}", .format.display_name())]
+    EncoderLimitation {
+        format: SupportedFormat,
+        reason: String,
+    },
+    #[error("{context}: {message}")]
+    Io { context: String, message: String },
+    #[error("{context}: permission denied")]
+    PermissionDenied { context: String },
+    #[error("Not enough memory to convert this image")]
+    OutOfMemory,
+    /// The worker process converting the image crashed or panicked
+    #[error("Converter crashed: {0}")]
+    Crashed(String),
+    /// The conversion ran past the per-job timeout and was stopped
+    #[error("Conversion timed out after {0:?}")]
+    TimedOut(Duration),
+    #[error("Conversion cancelled")]
+    Cancelled,
+}
+
+impl ConvertError {
+    /// Wrap an I/O error, singling out permission and memory failures
+    pub fn io(context: impl Into<String>, error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied {
+                context: context.into(),
+            },
+            std::io::ErrorKind::OutOfMemory => Self::OutOfMemory,
+            _ => Self::Io {
+                context: context.into(),
+                message: error.to_string(),
+            },
+        }
+    }
+
+    /// Classify an `image` error raised while decoding in-memory input.
+    /// I/O errors there mean truncated data, not a failing disk.
+    pub fn decode(error: ImageError) -> Self {
+        match error {
+            ImageError::Unsupported(e) => Self::UnsupportedInput(e.to_string()),
+            ImageError::Limits(e) => Self::LimitExceeded(e.to_string()),
+            ImageError::IoError(e) if e.kind() == std::io::ErrorKind::OutOfMemory => {
+                Self::OutOfMemory
+            }
+            e => Self::Decode(e.to_string()),
+        }
+    }
+
+    /// Classify an `image` error raised while writing `format`
+    pub fn encode(format: SupportedFormat, error: ImageError) -> Self {
+        match error {
+            ImageError::Limits(e) if e.kind() == LimitErrorKind::InsufficientMemory => {
+                Self::OutOfMemory
+            }
+            ImageError::IoError(e) => Self::io("Failed to write output", e),
+            e => Self::limitation(format, e.to_string()),
+        }
+    }
+
+    pub fn limitation(format: SupportedFormat, reason: impl Into<String>) -> Self {
+        Self::EncoderLimitation {
+            format,
+            reason: reason.into(),
+        }
+    }
+
+    /// A suggestion for what the user can do about the error, if there is one
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::UnsupportedInput(_) => {
+                Some("Only PNG, JPEG, WebP, AVIF, GIF, BMP, TIFF and ICO files can be converted")
+            }
+            Self::Decode(_) => Some("The file may be damaged or incompletely downloaded"),
+            Self::LimitExceeded(_) => Some("Raise the decode limits if you trust this file"),
+            Self::InvalidOperation(_) => {
+                Some("Check the file's crop, the canvas size and the watermark")
+            }
+            Self::EncoderLimitation { .. } => {
+                Some("Choose another output format or change the conversion settings")
+            }
+            Self::Io { .. } => None,
+            Self::PermissionDenied { .. } => Some("Choose an output folder you can write to"),
+            Self::OutOfMemory => Some("Convert fewer files at once, or use a smaller image"),
+            Self::Crashed(_) => {
+                Some("The file may be malformed; try converting it to another format")
+            }
+            Self::TimedOut(_) => {
+                Some("Increase the timeout, or lower the quality or optimization level")
+            }
+            Self::Cancelled => None,
+        }
+    }
+}
+
+/// `.context()` for I/O results, producing a `ConvertError`
+pub(crate) trait IoContext<T> {
+    fn io_context(self, context: &str) -> Result<T>;
+}
+
+impl<T> IoContext<T> for std::io::Result<T> {
+    fn io_context(self, context: &str) -> Result<T> {
+        self.map_err(|e| ConvertError::io(context, e))
+    }
+}
@@ -0,0 +1,140 @@
+use crate::converter::SupportedFormat;
+use crate::error::{ConvertError, IoContext, Result};
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::{imageops::FilterType, DynamicImage, ExtendedColorType, RgbaImage};
+use std::path::{Path, PathBuf};
+
+/// Sizes embedded in a multi-resolution ICO by default
+pub const DEFAULT_ICO_SIZES: [u32; 7] = [16, 24, 32, 48, 64, 128, 256];
+
+/// Largest image the ICO format can hold
+pub const MAX_ICO_SIZE: u32 = 256;
+
+/// Resample an image into a square icon, padding non-square sources with transparency
+pub fn square_icon(img: &DynamicImage, size: u32) -> RgbaImage {
+    let resized = img.resize(size, size, FilterType::Lanczos3).to_rgba8();
+    if resized.dimensions() == (size, size) {
+        return resized;
+    }
+
+    let mut canvas = RgbaImage::new(size, size);
+    let x = (size - resized.width()) / 2;
+    let y = (size - resized.height()) / 2;
+    image::imageops::overlay(&mut canvas, &resized, x as i64, y as i64);
+    canvas
+}
+
+/// Encode a multi-resolution ICO, skipping sizes larger than the source
+pub fn encode_ico(img: &DynamicImage, sizes: &[u32]) -> Result<Vec<u8>> {
+    let source_size = img.width().max(img.height());
+
+    let mut sizes: Vec<u32> = sizes
+        .iter()
+        .copied()
+        .filter(|&size| size > 0 && size <= MAX_ICO_SIZE && size <= source_size)
+        .collect();
+    if sizes.is_empty() {
+        // Tiny source: embed it once at its own size
+        sizes.push(source_size.clamp(1, MAX_ICO_SIZE));
+    }
+    sizes.sort_unstable();
+    sizes.dedup();
+
+    let frames = sizes
+        .iter()
+        .map(|&size| {
+            let icon = square_icon(img, size);
+            IcoFrame::as_png(icon.as_raw(), size, size, ExtendedColorType::Rgba8)
+                .map_err(|e| ConvertError::encode(SupportedFormat::Ico, e))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut data = Vec::new();
+    IcoEncoder::new(&mut data)
+        .encode_images(&frames)
+        .map_err(|e| ConvertError::encode(SupportedFormat::Ico, e))?;
+
+    Ok(data)
+}
+
+/// PNG icons written by the favicon bundle: (file name, size, opaque)
+const FAVICON_PNGS: [(&str, u32, bool); 8] = [
+    ("favicon-16x16.png", 16, false),
+    ("favicon-32x32.png", 32, false),
+    ("apple-touch-icon.png", 180, true),
+    ("apple-touch-icon-167x167.png", 167, true),
+    ("apple-touch-icon-152x152.png", 152, true),
+    ("apple-touch-icon-120x120.png", 120, true),
+    ("android-chrome-192x192.png", 192, false),
+    ("android-chrome-512x512.png", 512, false),
+];
+
+/// Write `favicon.ico`, the apple-touch-icon and Android PNGs and a
+/// `site.webmanifest` into `out_dir`. Apple icons are flattened onto
+/// `background`, since iOS renders transparency as black.
+pub fn write_favicon_bundle(
+    img: &DynamicImage,
+    out_dir: &Path,
+    background: [u8; 3],
+) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(out_dir).io_context("Failed to create favicon directory")?;
+    let mut written = Vec::new();
+
+    let ico_path = out_dir.join("favicon.ico");
+    std::fs::write(&ico_path, encode_ico(img, &[16, 32, 48])?)
+        .io_context("Failed to write favicon.ico")?;
+    written.push(ico_path);
+
+    for (name, size, opaque) in FAVICON_PNGS {
+        let mut icon = square_icon(img, size);
+        if opaque {
+            for p in icon.pixels_mut() {
+                let alpha = p[3] as u32;
+                for c in 0..3 {
+                    p[c] = ((p[c] as u32 * alpha + background[c] as u32 * (255 - alpha) + 127)
+                        / 255) as u8;
+                }
+                p[3] = 255;
+            }
+        }
+
+        let path = out_dir.join(name);
+        icon.save_with_format(&path, image::ImageFormat::Png)
+            .map_err(|e| match e {
+                image::ImageError::IoError(e) => {
+                    ConvertError::io(format!("Failed to write {}", name), e)
+                }
+                e => ConvertError::encode(SupportedFormat::Png, e),
+            })?;
+        written.push(path);
+    }
+
+    let manifest_path = out_dir.join("site.webmanifest");
+    std::fs::write(&manifest_path, webmanifest(background))
+        .io_context("Failed to write site.webmanifest")?;
+    written.push(manifest_path);
+
+    Ok(written)
+}
+
+/// Minimal web app manifest referencing the Android icons
+fn webmanifest(background: [u8; 3]) -> String {
+    let color = format!(
+        "#{:02x}{:02x}{:02x}",
+        background[0], background[1], background[2]
+    );
+    format!(
+        r#"{{
+  "name": "",
+  "short_name": "",
+  "icons": [
+    {{ "src": "/android-chrome-192x192.png", "sizes": "192x192", "type": "image/png" }},
+    {{ "src": "/android-chrome-512x512.png", "sizes": "512x512", "type": "image/png" }}
+  ],
+  "theme_color": "{color}",
+  "background_color": "{color}",
+  "display": "standalone"
+}}
+"#
+    )
+}
@@ -0,0 +1,273 @@
+//! `make`-style incremental batches: deciding whether a job's output is
+//! already up to date, so re-running a batch over a growing folder only
+//! converts new or changed files.
+//!
+//! After each incremental conversion the output cache records a hash of the
+//! input's contents and of the options used. A job is up to date when its
+//! output is unchanged and was made from the same input contents with the
+//! same options. Outputs the cache doesn't know about, e.g. from before
+//! incremental mode was used, are up to date when they are newer than their
+//! input.
+
+use crate::batch::BatchJob;
+use crate::converter::ConversionOptions;
+use crate::error::{ConvertError, IoContext, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What an output was converted from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    input_len: u64,
+    input_modified: Option<SystemTime>,
+    input_hash: String,
+    options_hash: String,
+    output_len: u64,
+}
+
+impl CacheEntry {
+    /// Fingerprint `job` after converting it to an output of `output_len` bytes
+    pub fn for_job(job: &BatchJob, output_len: u64) -> Result<Self> {
+        let input = std::fs::metadata(&job.input_path).io_context("Failed to read input")?;
+        Ok(Self {
+            input_len: input.len(),
+            input_modified: input.modified().ok(),
+            input_hash: hash_file(&job.input_path)?,
+            options_hash: hash_options(&job.options),
+            output_len,
+        })
+    }
+}
+
+/// Output cache, keyed by absolute output path
+#[derive(Debug, Default)]
+pub struct OutputCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+    /// Keys inserted since the cache was loaded or last saved
+    changed: HashSet<PathBuf>,
+}
+
+impl OutputCache {
+    /// `$XDG_CACHE_HOME/pixelconvert/outputs.json`, falling back to `~/.cache`
+    pub fn default_path() -> Option<PathBuf> {
+        Some(crate::dirs::cache_dir()?.join("outputs.json"))
+    }
+
+    /// Read the cache at `path`. A missing or corrupt cache is empty; the
+    /// worst outcome is converting some files again.
+    pub fn load(path: &Path) -> Self {
+        let entries = std::fs::read(path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            entries,
+            changed: HashSet::new(),
+        }
+    }
+
+    /// Write the entries inserted since loading or the last save to
+    /// `path`
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        let changes = self.take_changes();
+        let result = changes.save(path);
+        if result.is_err() {
+            self.restore_changes(changes);
+        }
+        result
+    }
+
+    /// Take the entries inserted since loading or the last save, to write
+    /// them without holding on to the cache
+    pub fn take_changes(&mut self) -> CacheChanges {
+        let changed = std::mem::take(&mut self.changed);
+        CacheChanges(
+            changed
+                .into_iter()
+                .filter_map(|key| {
+                    let entry = self.entries.get(&key)?.clone();
+                    Some((key, entry))
+                })
+                .collect(),
+        )
+    }
+
+    /// Mark changes that couldn't be saved as unsaved again
+    pub fn restore_changes(&mut self, changes: CacheChanges) {
+        self.changed.extend(changes.0.into_keys());
+    }
+
+    pub fn get(&self, output_path: &Path) -> Option<&CacheEntry> {
+        self.entries.get(&cache_key(output_path))
+    }
+
+    pub fn insert(&mut self, output_path: &Path, entry: CacheEntry) {
+        let key = cache_key(output_path);
+        self.changed.insert(key.clone());
+        self.entries.insert(key, entry);
+    }
+}
+
+/// Entries taken out of an `OutputCache` to be saved
+#[derive(Debug, Default)]
+pub struct CacheChanges(HashMap<PathBuf, CacheEntry>);
+
+impl CacheChanges {
+    /// Write the entries over the cache at `path`, replacing it atomically.
+    /// Entries already there are kept, so batches running at the same time
+    /// don't drop each other's entries.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).io_context("Failed to create cache directory")?;
+        }
+        let mut entries = OutputCache::load(path).entries;
+        entries.extend(self.0.clone());
+        let data = serde_json::to_vec(&entries)
+            .map_err(|e| ConvertError::io("Failed to write output cache", e.into()))?;
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, data).io_context("Failed to write output cache")?;
+        std::fs::rename(&temp, path).io_context("Failed to write output cache")
+    }
+}
+
+/// Whether `job`'s output is up to date, given what the cache recorded
+/// for it. Hashes the input only if its size or modification time changed.
+pub fn is_up_to_date(job: &BatchJob, entry: Option<&CacheEntry>) -> bool {
+    let (Ok(input), Ok(output)) = (
+        std::fs::metadata(&job.input_path),
+        std::fs::metadata(&job.output_path),
+    ) else {
+        return false;
+    };
+
+    match entry {
+        Some(entry) => {
+            let input_unchanged = (input.len() == entry.input_len
+                && input.modified().ok() == entry.input_modified)
+                || hash_file(&job.input_path).is_ok_and(|hash| hash == entry.input_hash);
+            output.len() == entry.output_len
+                && entry.options_hash == hash_options(&job.options)
+                && input_unchanged
+        }
+        None => matches!(
+            (input.modified(), output.modified()),
+            (Ok(input), Ok(output)) if output >= input
+        ),
+    }
+}
+
+fn cache_key(output_path: &Path) -> PathBuf {
+    std::path::absolute(output_path).unwrap_or_else(|_| output_path.to_path_buf())
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher
+        .update_reader(std::fs::File::open(path).io_context("Failed to open image")?)
+        .io_context("Failed to read image")?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Hash of `options` and, with a watermark, of the logo's contents, so
+/// replacing the logo reconverts the outputs it was drawn on
+fn hash_options(options: &ConversionOptions) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&serde_json::to_vec(options).unwrap_or_default());
+    if let Some(watermark) = &options.watermark {
+        // A missing logo fails the conversion anyway
+        if let Ok(logo) = hash_file(&watermark.path) {
+            hasher.update(logo.as_bytes());
+        }
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use crate::watermark::Watermark;
+    use std::time::Duration;
+
+    #[test]
+    fn test_up_to_date() {
+        let dir = TempDir::new("incremental");
+        let mut job = BatchJob {
+            input_path: dir.join("photo.png"),
+            output_path: dir.join("photo.webp"),
+            options: ConversionOptions::default(),
+            incremental: true,
+        };
+        let set_modified = |path: &Path, seconds| {
+            let file = std::fs::File::options().write(true).open(path).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+                .unwrap();
+        };
+
+        // Without a cache entry, only the modification times count
+        std::fs::write(&job.input_path, b"input").unwrap();
+        assert!(!is_up_to_date(&job, None));
+        std::fs::write(&job.output_path, b"output").unwrap();
+        set_modified(&job.input_path, 1000);
+        set_modified(&job.output_path, 2000);
+        assert!(is_up_to_date(&job, None));
+        set_modified(&job.input_path, 3000);
+        assert!(!is_up_to_date(&job, None));
+
+        // With one, touching the input doesn't matter but its contents do
+        let entry = CacheEntry::for_job(&job, 6).unwrap();
+        assert!(is_up_to_date(&job, Some(&entry)));
+        set_modified(&job.input_path, 4000);
+        assert!(is_up_to_date(&job, Some(&entry)));
+        std::fs::write(&job.input_path, b"edited").unwrap();
+        assert!(!is_up_to_date(&job, Some(&entry)));
+        std::fs::write(&job.input_path, b"input").unwrap();
+
+        // ...as do the options, the watermark logo and the output
+        job.options.quality = 50;
+        assert!(!is_up_to_date(&job, Some(&entry)));
+        job.options.quality = ConversionOptions::default().quality;
+        let logo = dir.join("logo.png");
+        std::fs::write(&logo, b"logo").unwrap();
+        job.options.watermark = Some(Watermark {
+            path: logo.clone(),
+            ..Default::default()
+        });
+        let marked = CacheEntry::for_job(&job, 6).unwrap();
+        assert!(is_up_to_date(&job, Some(&marked)));
+        std::fs::write(&logo, b"replaced").unwrap();
+        assert!(!is_up_to_date(&job, Some(&marked)));
+        job.options.watermark = None;
+        std::fs::write(&job.output_path, b"truncated").unwrap();
+        assert!(!is_up_to_date(&job, Some(&entry)));
+
+        let mut cache = OutputCache::default();
+        cache.insert(&job.output_path, entry.clone());
+        let path = dir.join("outputs.json");
+        cache.save(&path).unwrap();
+        assert_eq!(OutputCache::load(&path).get(&job.output_path), Some(&entry));
+
+        // Saving keeps what another batch saved in the meantime
+        let mut other = OutputCache::default();
+        let other_output = dir.join("other.webp");
+        other.insert(&other_output, entry.clone());
+        other.save(&path).unwrap();
+        cache.insert(&job.output_path, entry.clone());
+        cache.save(&path).unwrap();
+        let saved = OutputCache::load(&path);
+        assert_eq!(saved.get(&job.output_path), Some(&entry));
+        assert_eq!(saved.get(&other_output), Some(&entry));
+
+        // Entries that fail to save are saved the next time
+        cache.insert(&job.output_path, entry.clone());
+        assert!(cache.save(&path.join("outputs.json")).is_err());
+        std::fs::remove_file(&path).unwrap();
+        cache.save(&path).unwrap();
+        assert_eq!(OutputCache::load(&path).get(&job.output_path), Some(&entry));
+    }
+}
@@ -0,0 +1,377 @@
+//! On-disk record of a running batch, so a batch interrupted by a crash or
+//! by closing the app can resume without converting finished files again.
+//!
+//! The journal is a JSON-lines file: one `Job` record per job when the batch
+//! starts or a job is added to it, then a `Completed` or `Failed` record as
+//! each job finishes, or `Removed` if it is taken off the queue.
+//! Appending keeps the cost per job constant for batches of tens of thousands
+//! of files, and a last line torn by a crash is ignored when loading.
+
+use crate::batch::{BatchJob, BatchProgress};
+use crate::error::{ConvertError, IoContext, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "lowercase")]
+enum Record {
+    Job { index: usize, job: BatchJob },
+    Completed { index: usize, output_bytes: u64 },
+    Failed { index: usize, error: ConvertError },
+    Removed { index: usize },
+}
+
+/// Outcome of a journaled job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobState {
+    /// Not converted yet, or its output has gone missing since
+    Pending,
+    /// Converted, and the output on disk still has the recorded size
+    Completed {
+        output_bytes: u64,
+    },
+    Failed(ConvertError),
+}
+
+/// An unfinished batch read back from a journal
+#[derive(Debug, Clone)]
+pub struct JournaledBatch {
+    pub jobs: Vec<(BatchJob, JobState)>,
+}
+
+impl JournaledBatch {
+    /// Number of jobs still to convert
+    pub fn pending(&self) -> usize {
+        self.count(|state| *state == JobState::Pending)
+    }
+
+    pub fn completed(&self) -> usize {
+        self.count(|state| matches!(state, JobState::Completed { .. }))
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|state| matches!(state, JobState::Failed(_)))
+    }
+
+    fn count(&self, predicate: impl Fn(&JobState) -> bool) -> usize {
+        self.jobs
+            .iter()
+            .filter(|(_, state)| predicate(state))
+            .count()
+    }
+}
+
+/// Writes the progress of a running batch to a journal file
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    /// Journal index of each job in the list being run
+    indices: Vec<usize>,
+    /// Number of jobs in the journal
+    len: usize,
+}
+
+impl Journal {
+    /// `$XDG_DATA_HOME/pixelconvert/batch.journal`, falling back to
+    /// `~/.local/share`
+    pub fn default_path() -> Option<PathBuf> {
+        Some(crate::dirs::data_dir()?.join("batch.journal"))
+    }
+
+    /// Start a journal for `jobs`, replacing any previous one at `path`
+    pub fn create(path: impl Into<PathBuf>, jobs: &[BatchJob]) -> Result<Self> {
+        let mut journal = Self::open(path.into())?;
+        for (index, job) in jobs.iter().enumerate() {
+            journal.write(&Record::Job {
+                index,
+                job: job.clone(),
+            })?;
+        }
+        journal.indices = (0..jobs.len()).collect();
+        journal.len = jobs.len();
+        journal
+            .writer
+            .flush()
+            .io_context("Failed to write batch journal")?;
+        Ok(journal)
+    }
+
+    /// Rewrite the journal at `path` for `batch` and return the jobs still
+    /// to run. Progress sent for those jobs is recorded against their
+    /// place in the original batch.
+    pub fn resume(
+        path: impl Into<PathBuf>,
+        batch: &JournaledBatch,
+    ) -> Result<(Self, Vec<BatchJob>)> {
+        let mut journal = Self::open(path.into())?;
+        let mut pending = Vec::new();
+        for (index, (job, _)) in batch.jobs.iter().enumerate() {
+            journal.write(&Record::Job {
+                index,
+                job: job.clone(),
+            })?;
+        }
+        journal.len = batch.jobs.len();
+        for (index, (job, state)) in batch.jobs.iter().enumerate() {
+            match state {
+                JobState::Pending => {
+                    journal.indices.push(index);
+                    pending.push(job.clone());
+                }
+                JobState::Completed { output_bytes } => journal.write(&Record::Completed {
+                    index,
+                    output_bytes: *output_bytes,
+                })?,
+                JobState::Failed(error) => journal.write(&Record::Failed {
+                    index,
+                    error: error.clone(),
+                })?,
+            }
+        }
+        journal
+            .writer
+            .flush()
+            .io_context("Failed to write batch journal")?;
+        Ok((journal, pending))
+    }
+
+    /// Read the batch recorded at `path`, if there is one. Completed jobs
+    /// whose output is missing or has changed size are pending again, and
+    /// removed jobs are left out.
+    pub fn load(path: &Path) -> Result<Option<JournaledBatch>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(ConvertError::io("Failed to open batch journal", e)),
+        };
+
+        let mut jobs: Vec<(BatchJob, JobState)> = Vec::new();
+        let mut removed = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.io_context("Failed to read batch journal")?;
+            // Anything after a torn or garbled line can't be trusted
+            let Ok(record) = serde_json::from_str::<Record>(&line) else {
+                break;
+            };
+            match record {
+                Record::Job { index, job } if index == jobs.len() => {
+                    jobs.push((job, JobState::Pending));
+                    removed.push(false);
+                }
+                Record::Completed {
+                    index,
+                    output_bytes,
+                } if index < jobs.len() => {
+                    let (job, state) = &mut jobs[index];
+                    let on_disk = std::fs::metadata(&job.output_path).map(|m| m.len());
+                    if on_disk.is_ok_and(|len| len == output_bytes) {
+                        *state = JobState::Completed { output_bytes };
+                    }
+                }
+                Record::Failed { index, error } if index < jobs.len() => {
+                    jobs[index].1 = JobState::Failed(error);
+                }
+                Record::Removed { index } if index < jobs.len() => removed[index] = true,
+                _ => break,
+            }
+        }
+        let mut removed = removed.into_iter();
+        jobs.retain(|_| !removed.next().unwrap());
+
+        Ok((!jobs.is_empty()).then_some(JournaledBatch { jobs }))
+    }
+
+    /// Delete the journal at `path`, e.g. when the user declines to resume
+    pub fn discard(path: &Path) -> Result<()> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(ConvertError::io("Failed to remove batch journal", e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a finished, added or removed job. Other progress updates are
+    /// ignored.
+    pub fn record(&mut self, progress: &BatchProgress) -> Result<()> {
+        let record = match progress {
+            BatchProgress::Added { job, .. } => {
+                let index = self.len;
+                self.indices.push(index);
+                self.len += 1;
+                Record::Job {
+                    index,
+                    job: job.clone(),
+                }
+            }
+            BatchProgress::Removed { index, .. } => Record::Removed {
+                index: self.indices[*index],
+            },
+            BatchProgress::Completed { index, stats, .. } => Record::Completed {
+                index: self.indices[*index],
+                output_bytes: stats.output_bytes,
+            },
+            BatchProgress::Failed { index, error, .. } => Record::Failed {
+                index: self.indices[*index],
+                error: error.clone(),
+            },
+            _ => return Ok(()),
+        };
+        self.write(&record)?;
+        self.writer
+            .flush()
+            .io_context("Failed to write batch journal")
+    }
+
+    /// The batch has finished; remove its journal
+    pub fn finish(self) -> Result<()> {
+        let Self { path, writer, .. } = self;
+        drop(writer);
+        Self::discard(&path)
+    }
+
+    fn open(path: PathBuf) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).io_context("Failed to create journal directory")?;
+        }
+        let file = File::create(&path).io_context("Failed to create batch journal")?;
+        Ok(Self {
+            path,
+            writer: BufWriter::new(file),
+            indices: Vec::new(),
+            len: 0,
+        })
+    }
+
+    fn write(&mut self, record: &Record) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)
+            .map_err(|e| ConvertError::io("Failed to write batch journal", e.into()))?;
+        self.writer
+            .write_all(b"\n")
+            .io_context("Failed to write batch journal")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::{ConversionOptions, ConversionStats};
+    use crate::testing::TempDir;
+    use std::time::Duration;
+
+    #[test]
+    fn test_journal_resume() {
+        let dir = TempDir::new("journal");
+        let path = dir.join("batch.journal");
+
+        let jobs: Vec<BatchJob> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| BatchJob {
+                input_path: dir.join(format!("{}.png", name)),
+                output_path: dir.join(format!("{}.webp", name)),
+                options: ConversionOptions::default(),
+                incremental: false,
+            })
+            .collect();
+        let completed = |index, output_bytes| BatchProgress::Completed {
+            index,
+            file: String::new(),
+            stats: ConversionStats {
+                output_bytes,
+                ..Default::default()
+            },
+            elapsed: Duration::ZERO,
+        };
+
+        // a is done, b failed, c's output was lost after converting, d never ran
+        let mut journal = Journal::create(&path, &jobs).unwrap();
+        std::fs::write(&jobs[0].output_path, b"1234").unwrap();
+        journal.record(&completed(0, 4)).unwrap();
+        journal
+            .record(&BatchProgress::Failed {
+                index: 1,
+                file: String::new(),
+                error: ConvertError::Decode("truncated".to_string()),
+                elapsed: Duration::ZERO,
+            })
+            .unwrap();
+        journal.record(&completed(2, 10)).unwrap();
+        drop(journal);
+        // A write torn by a crash
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"{\"record\":\"compl").unwrap();
+        drop(file);
+
+        let batch = Journal::load(&path).unwrap().unwrap();
+        let states: Vec<&JobState> = batch.jobs.iter().map(|(_, state)| state).collect();
+        assert_eq!(
+            states,
+            [
+                &JobState::Completed { output_bytes: 4 },
+                &JobState::Failed(ConvertError::Decode("truncated".to_string())),
+                &JobState::Pending,
+                &JobState::Pending,
+            ]
+        );
+
+        // Resuming runs c and d, recording them against their original places
+        let (mut journal, pending) = Journal::resume(&path, &batch).unwrap();
+        let inputs: Vec<&Path> = pending.iter().map(|job| job.input_path.as_path()).collect();
+        assert_eq!(
+            inputs,
+            [jobs[2].input_path.as_path(), jobs[3].input_path.as_path()]
+        );
+        std::fs::write(&jobs[3].output_path, b"12345").unwrap();
+        journal.record(&completed(1, 5)).unwrap();
+
+        // Jobs added to the running batch are journaled, removed ones dropped
+        let added = BatchJob {
+            input_path: dir.join("e.png"),
+            output_path: dir.join("e.webp"),
+            ..jobs[0].clone()
+        };
+        journal
+            .record(&BatchProgress::Added {
+                index: 2,
+                job: added.clone(),
+            })
+            .unwrap();
+        journal
+            .record(&BatchProgress::Removed {
+                index: 0,
+                file: String::new(),
+            })
+            .unwrap();
+        drop(journal);
+
+        let batch = Journal::load(&path).unwrap().unwrap();
+        assert_eq!(
+            (batch.completed(), batch.failed(), batch.pending()),
+            (2, 1, 1)
+        );
+        let inputs: Vec<&Path> = batch
+            .jobs
+            .iter()
+            .map(|(job, _)| job.input_path.as_path())
+            .collect();
+        assert_eq!(
+            inputs,
+            [
+                jobs[0].input_path.as_path(),
+                jobs[1].input_path.as_path(),
+                jobs[3].input_path.as_path(),
+                added.input_path.as_path(),
+            ]
+        );
+
+        let (journal, _) = Journal::resume(&path, &batch).unwrap();
+        journal.finish().unwrap();
+        assert!(Journal::load(&path).unwrap().is_none());
+    }
+}
@@ -0,0 +1,66 @@
+//! Conversion engine behind PixelConvert.
+//!
+//! Decodes PNG, JPEG, WebP, AVIF, GIF, BMP, TIFF and ICO (detected from
+//! content), and encodes to the same set with per-format tuning: quality for
+//! lossy formats, oxipng optimization and palette quantization for PNG,
+//! multi-resolution ICO, and alpha flattening for formats without alpha.
+//! Images can be cropped, trimmed, rotated, flipped, padded onto a larger
+//! canvas and watermarked on the way through (see `transform`).
+//! Has no GTK dependency, so the GUI, the CLI and other tools all link the
+//! same encoding logic.
+//!
+//! ```no_run
+//! use pixelconvert_core::{ConversionOptions, ImageConverter, SupportedFormat};
+//!
+//! let converter = ImageConverter::new(ConversionOptions {
+//!     format: SupportedFormat::WebP,
+//!     quality: 80,
+//!     ..Default::default()
+//! });
+//! let stats = converter.convert("photo.jpg", "photo.webp")?;
+//! println!("wrote {} bytes", stats.output_bytes);
+//! # Ok::<(), pixelconvert_core::ConvertError>(())
+//! ```
+
+pub mod batch;
+pub mod converter;
+mod dirs;
+pub mod error;
+pub mod icon;
+pub mod incremental;
+pub mod journal;
+pub mod limits;
+pub mod progress;
+pub mod quantize;
+pub mod queue;
+pub mod report;
+pub mod stats;
+#[cfg(test)]
+mod testing;
+pub mod transform;
+pub mod watch;
+pub mod watermark;
+pub mod worker;
+
+pub use batch::{
+    output_path_for, run_batch, run_batch_with, Batch, BatchJob, BatchProgress, Execution,
+};
+pub use converter::{
+    AlphaPolicy, ConversionOptions, ConversionStats, EncodedImage, ImageConverter, PngOptimization,
+    SupportedFormat,
+};
+pub use error::ConvertError;
+pub use journal::{JobState, Journal, JournaledBatch};
+pub use limits::DecodeLimits;
+pub use progress::{progress_channel, ProgressReceiver, ProgressSender};
+pub use quantize::{DitherMode, Quantization};
+pub use queue::{BatchQueue, Priority};
+pub use report::{BatchReport, ReportEntry, ReportStatus};
+pub use stats::BatchStats;
+pub use transform::{
+    AspectRatio, Canvas, CanvasFill, CanvasSize, Crop, Gravity, Orientation, Trim, TrimMode,
+};
+pub use watermark::{BlendMode, Watermark};
+
+/// The `image` crate, for working with decoded images
+pub use image;
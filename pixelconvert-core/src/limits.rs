@@ -0,0 +1,171 @@
+use crate::error::{ConvertError, Result};
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+
+/// Resource limits applied before and while decoding untrusted input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DecodeLimits {
+    pub max_width: u32,
+    pub max_height: u32,
+    /// Maximum width × height
+    pub max_pixels: u64,
+    /// Maximum bytes the decoder may allocate, including the decoded image
+    pub max_alloc: u64,
+    /// Maximum frames in an animated GIF, PNG or WebP
+    pub max_frames: u32,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_width: 65535,
+            max_height: 65535,
+            max_pixels: 256 * 1024 * 1024,
+            max_alloc: 1024 * 1024 * 1024,
+            max_frames: 1000,
+        }
+    }
+}
+
+impl DecodeLimits {
+    /// No limits at all, for trusted input
+    pub fn unlimited() -> Self {
+        Self {
+            max_width: u32::MAX,
+            max_height: u32::MAX,
+            max_pixels: u64::MAX,
+            max_alloc: u64::MAX,
+            max_frames: u32::MAX,
+        }
+    }
+
+    /// Limits for the `image` crate's decoders. Dimensions are checked by
+    /// `check_header()` instead, which gives clearer messages.
+    pub(crate) fn to_image_limits(&self) -> image::Limits {
+        let mut limits = image::Limits::no_limits();
+        limits.max_alloc = (self.max_alloc != u64::MAX).then_some(self.max_alloc);
+        limits
+    }
+
+    /// Check the dimensions and buffer size a decoder reports before it
+    /// decodes any pixels
+    pub(crate) fn check_header(&self, (width, height): (u32, u32), total_bytes: u64) -> Result<()> {
+        self.check_size((width, height))?;
+        if total_bytes > self.max_alloc {
+            return Err(ConvertError::LimitExceeded(format!(
+                "decoding needs {} bytes, more than the maximum of {}",
+                total_bytes, self.max_alloc
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check the dimensions of an image before allocating it, whether
+    /// decoded or made by an operation such as a canvas
+    pub(crate) fn check_size(&self, (width, height): (u32, u32)) -> Result<()> {
+        if width > self.max_width {
+            return Err(ConvertError::LimitExceeded(format!(
+                "{}×{} is wider than the maximum of {} pixels",
+                width, height, self.max_width
+            )));
+        }
+        if height > self.max_height {
+            return Err(ConvertError::LimitExceeded(format!(
+                "{}×{} is taller than the maximum of {} pixels",
+                width, height, self.max_height
+            )));
+        }
+        let pixels = width as u64 * height as u64;
+        if pixels > self.max_pixels {
+            return Err(ConvertError::LimitExceeded(format!(
+                "{}×{} has {} pixels, more than the maximum of {}",
+                width, height, pixels, self.max_pixels
+            )));
+        }
+        Ok(())
+    }
+
+    /// Count the frames of animated formats, stopping once past the limit
+    pub(crate) fn check_frames(&self, data: &[u8], format: ImageFormat) -> Result<()> {
+        let frames = match format {
+            ImageFormat::Gif => gif_frames(data, self.max_frames),
+            ImageFormat::Png => apng_frames(data),
+            ImageFormat::WebP => webp_frames(data, self.max_frames),
+            _ => return Ok(()),
+        };
+
+        if frames > self.max_frames as u64 {
+            return Err(ConvertError::LimitExceeded(format!(
+                "more than the maximum of {} animation frames",
+                self.max_frames
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Walk GIF frame descriptors without decompressing the frames. Malformed
+/// files stop the count; the decoder reports them properly.
+fn gif_frames(data: &[u8], max: u32) -> u64 {
+    let mut options = gif::DecodeOptions::new();
+    options.skip_frame_decoding(true);
+    let Ok(mut decoder) = options.read_info(data) else {
+        return 0;
+    };
+
+    let mut frames = 0;
+    while let Ok(Some(_)) = decoder.next_frame_info() {
+        frames += 1;
+        if frames > max as u64 {
+            break;
+        }
+    }
+    frames
+}
+
+/// Frame count from an APNG's `acTL` chunk (1 for still PNGs)
+fn apng_frames(data: &[u8]) -> u64 {
+    let mut chunks = data.get(8..).unwrap_or_default();
+    while chunks.len() >= 8 {
+        let length = u32::from_be_bytes([chunks[0], chunks[1], chunks[2], chunks[3]]) as usize;
+        let kind = &chunks[4..8];
+        if kind == b"acTL" {
+            return chunks
+                .get(8..12)
+                .map(|n| u32::from_be_bytes([n[0], n[1], n[2], n[3]]) as u64)
+                .unwrap_or(1);
+        }
+        if kind == b"IDAT" {
+            // acTL must come before the image data
+            break;
+        }
+        // Chunk data is followed by a 4-byte CRC
+        let Some(rest) = chunks.get(12 + length..) else {
+            break;
+        };
+        chunks = rest;
+    }
+    1
+}
+
+/// Count `ANMF` chunks in a WebP RIFF container
+fn webp_frames(data: &[u8], max: u32) -> u64 {
+    let mut chunks = data.get(12..).unwrap_or_default();
+    let mut frames = 0;
+    while chunks.len() >= 8 {
+        let length = u32::from_le_bytes([chunks[4], chunks[5], chunks[6], chunks[7]]) as usize;
+        if &chunks[..4] == b"ANMF" {
+            frames += 1;
+            if frames > max as u64 {
+                break;
+            }
+        }
+        // Chunks are padded to an even length
+        let Some(rest) = chunks.get(8 + length + (length & 1)..) else {
+            break;
+        };
+        chunks = rest;
+    }
+    frames.max(1)
+}
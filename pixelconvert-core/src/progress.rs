@@ -0,0 +1,228 @@
+//! Channel carrying `BatchProgress` from batch workers to a frontend.
+//!
+//! Receivers wake up only when there is something to read: `recv()` blocks
+//! a thread (the CLI, tests), and `updates()` is a future for a GUI main
+//! loop (e.g. `glib::MainContext::spawn_local`). Both hand over everything
+//! queued since the last read, so a burst of fast jobs is handled in one
+//! wakeup instead of one per job. Built on std only, so the crate stays
+//! free of GLib and async runtimes.
+
+use crate::batch::BatchProgress;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// A connected sender and receiver
+pub fn progress_channel() -> (ProgressSender, ProgressReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            senders: 1,
+            receiver_alive: true,
+            waker: None,
+        }),
+        condvar: Condvar::new(),
+    });
+    (
+        ProgressSender {
+            shared: Arc::clone(&shared),
+        },
+        ProgressReceiver { shared },
+    )
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+    queue: VecDeque<BatchProgress>,
+    senders: usize,
+    receiver_alive: bool,
+    waker: Option<Waker>,
+}
+
+impl Shared {
+    fn wake(&self, state: &mut State) {
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.condvar.notify_all();
+    }
+}
+
+/// Sending half; cloned into every worker. The channel closes when the
+/// last sender is dropped.
+#[derive(Debug)]
+pub struct ProgressSender {
+    shared: Arc<Shared>,
+}
+
+impl ProgressSender {
+    /// Queue an update. Returns `false` if the receiver is gone.
+    pub fn send(&self, progress: BatchProgress) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        if !state.receiver_alive {
+            return false;
+        }
+        state.queue.push_back(progress);
+        self.shared.wake(&mut state);
+        true
+    }
+}
+
+impl Clone for ProgressSender {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders += 1;
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for ProgressSender {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.shared.wake(&mut state);
+        }
+    }
+}
+
+/// Receiving half of a batch's progress
+#[derive(Debug)]
+pub struct ProgressReceiver {
+    shared: Arc<Shared>,
+}
+
+impl ProgressReceiver {
+    /// The next update, blocking until there is one. `None` once every
+    /// sender is gone and the queue is empty.
+    pub fn recv(&self) -> Option<BatchProgress> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(progress) = state.queue.pop_front() {
+                return Some(progress);
+            }
+            if state.senders == 0 {
+                return None;
+            }
+            state = self.shared.condvar.wait(state).unwrap();
+        }
+    }
+
+    /// The next update if one is queued, without blocking
+    pub fn try_recv(&self) -> Option<BatchProgress> {
+        self.shared.state.lock().unwrap().queue.pop_front()
+    }
+
+    /// Wait for updates without blocking the thread. Resolves to everything
+    /// queued (at least one update), or `None` once the channel is closed.
+    pub fn updates(&self) -> Updates<'_> {
+        Updates { receiver: self }
+    }
+}
+
+impl Iterator for ProgressReceiver {
+    type Item = BatchProgress;
+
+    fn next(&mut self) -> Option<BatchProgress> {
+        self.recv()
+    }
+}
+
+impl Drop for ProgressReceiver {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.receiver_alive = false;
+        state.queue.clear();
+    }
+}
+
+/// Future returned by `ProgressReceiver::updates()`
+#[derive(Debug)]
+pub struct Updates<'a> {
+    receiver: &'a ProgressReceiver,
+}
+
+impl Future for Updates<'_> {
+    type Output = Option<Vec<BatchProgress>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.receiver.shared.state.lock().unwrap();
+        if !state.queue.is_empty() {
+            return Poll::Ready(Some(state.queue.drain(..).collect()));
+        }
+        if state.senders == 0 {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Wake;
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn skipped(index: usize) -> BatchProgress {
+        BatchProgress::Skipped {
+            index,
+            file: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_progress_channel() {
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(Arc::clone(&counter));
+        let mut cx = Context::from_waker(&waker);
+
+        let (sender, receiver) = progress_channel();
+        let mut updates = receiver.updates();
+        assert!(Pin::new(&mut updates).poll(&mut cx).is_pending());
+
+        // A burst wakes the receiver and is delivered in one piece
+        let worker = sender.clone();
+        std::thread::spawn(move || {
+            worker.send(skipped(0));
+            worker.send(skipped(1));
+        })
+        .join()
+        .unwrap();
+        assert!(counter.0.load(Ordering::SeqCst) >= 1);
+        match Pin::new(&mut updates).poll(&mut cx) {
+            Poll::Ready(Some(batch)) => assert_eq!(batch.len(), 2),
+            other => panic!("expected two updates, got {:?}", other),
+        }
+
+        // Blocking receives see the same stream, and end when senders do
+        sender.send(skipped(2));
+        drop(sender);
+        assert!(matches!(
+            receiver.recv(),
+            Some(BatchProgress::Skipped { index: 2, .. })
+        ));
+        assert!(receiver.recv().is_none());
+        assert!(matches!(
+            Pin::new(&mut receiver.updates()).poll(&mut cx),
+            Poll::Ready(None)
+        ));
+    }
+}
@@ -0,0 +1,378 @@
+use crate::converter::SupportedFormat;
+use crate::error::{ConvertError, Result};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Dithering algorithm used when mapping pixels onto the palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DitherMode {
+    None,
+    FloydSteinberg,
+    Ordered,
+}
+
+impl DitherMode {
+    pub fn display_name(&self) -> &str {
+        match self {
+            Self::None => "None",
+            Self::FloydSteinberg => "Floyd–Steinberg",
+            Self::Ordered => "Ordered",
+        }
+    }
+
+    pub fn all() -> Vec<Self> {
+        vec![Self::None, Self::FloydSteinberg, Self::Ordered]
+    }
+}
+
+/// Palette quantization settings for indexed PNG and GIF output
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quantization {
+    pub max_colors: u16, // 2-256, including the transparent entry
+    pub dither: DitherMode,
+    /// Pixels with alpha below this become fully transparent, the rest opaque
+    pub alpha_threshold: u8,
+}
+
+impl Default for Quantization {
+    fn default() -> Self {
+        Self {
+            max_colors: 256,
+            dither: DitherMode::FloydSteinberg,
+            alpha_threshold: 128,
+        }
+    }
+}
+
+/// An image reduced to a palette of at most 256 entries
+pub struct IndexedImage {
+    pub width: u32,
+    pub height: u32,
+    /// RGB palette entries
+    pub palette: Vec<[u8; 3]>,
+    /// Palette index used for transparent pixels, if any
+    pub transparent: Option<u8>,
+    pub indices: Vec<u8>,
+}
+
+impl IndexedImage {
+    /// Number of palette entries, including the transparent one
+    pub fn palette_size(&self) -> usize {
+        self.palette.len()
+    }
+
+    /// Palette as a flat `[r, g, b, ...]` buffer
+    pub fn palette_bytes(&self) -> Vec<u8> {
+        self.palette.iter().flatten().copied().collect()
+    }
+
+    /// Encode as an 8-bit indexed PNG
+    pub fn encode_png(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+
+        let mut encoder = png::Encoder::new(&mut data, self.width, self.height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(self.palette_bytes());
+        if let Some(index) = self.transparent {
+            // tRNS only needs entries up to the transparent one
+            let mut trns = vec![255u8; index as usize + 1];
+            trns[index as usize] = 0;
+            encoder.set_trns(trns);
+        }
+
+        let png_error =
+            |e: png::EncodingError| ConvertError::limitation(SupportedFormat::Png, e.to_string());
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        writer.write_image_data(&self.indices).map_err(png_error)?;
+        writer.finish().map_err(png_error)?;
+
+        Ok(data)
+    }
+
+    /// Encode as a single-frame GIF
+    pub fn encode_gif(&self) -> Result<Vec<u8>> {
+        let (Ok(width), Ok(height)) = (u16::try_from(self.width), u16::try_from(self.height))
+        else {
+            return Err(ConvertError::limitation(
+                SupportedFormat::Gif,
+                "dimensions are limited to 65535x65535",
+            ));
+        };
+
+        let gif_error =
+            |e: gif::EncodingError| ConvertError::limitation(SupportedFormat::Gif, e.to_string());
+        let palette = self.palette_bytes();
+        let mut encoder =
+            gif::Encoder::new(Vec::new(), width, height, &palette).map_err(gif_error)?;
+
+        let frame = gif::Frame {
+            width,
+            height,
+            transparent: self.transparent,
+            buffer: std::borrow::Cow::Borrowed(&self.indices),
+            ..Default::default()
+        };
+        encoder.write_frame(&frame).map_err(gif_error)?;
+
+        encoder.into_inner().map_err(gif_error)
+    }
+}
+
+/// 8x8 Bayer threshold matrix for ordered dithering
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Reduce an RGBA image to an indexed image
+pub fn quantize(img: &RgbaImage, settings: &Quantization) -> IndexedImage {
+    let (width, height) = img.dimensions();
+    let max_colors = settings.max_colors.clamp(2, 256) as usize;
+
+    let is_opaque = |a: u8| a >= settings.alpha_threshold;
+    let has_transparency = img.pixels().any(|p| !is_opaque(p[3]));
+    let color_slots = if has_transparency {
+        max_colors - 1
+    } else {
+        max_colors
+    };
+
+    // Histogram of opaque colours
+    let mut histogram: HashMap<[u8; 3], u32> = HashMap::new();
+    for p in img.pixels().filter(|p| is_opaque(p[3])) {
+        *histogram.entry([p[0], p[1], p[2]]).or_insert(0) += 1;
+    }
+
+    let exact = histogram.len() <= color_slots;
+    let mut palette = if exact {
+        let mut colors: Vec<[u8; 3]> = histogram.keys().copied().collect();
+        colors.sort_unstable();
+        colors
+    } else {
+        median_cut(histogram.into_iter().collect(), color_slots)
+    };
+
+    let transparent = if has_transparency {
+        palette.push([0, 0, 0]);
+        Some((palette.len() - 1) as u8)
+    } else {
+        None
+    };
+
+    let opaque_entries = palette.len() - transparent.map_or(0, |_| 1);
+    let mut lookup = NearestLookup::new(&palette[..opaque_entries]);
+    let mut indices = vec![0u8; (width * height) as usize];
+
+    // Exact palettes never need dithering
+    let dither = if exact {
+        DitherMode::None
+    } else {
+        settings.dither
+    };
+
+    match dither {
+        DitherMode::None | DitherMode::Ordered => {
+            let spread = 255.0 / (opaque_entries.max(1) as f32).cbrt();
+            for (x, y, p) in img.enumerate_pixels() {
+                let i = (y * width + x) as usize;
+                if !is_opaque(p[3]) {
+                    indices[i] = transparent.unwrap_or(0);
+                    continue;
+                }
+                let offset = if dither == DitherMode::Ordered {
+                    (BAYER_8X8[(y % 8) as usize][(x % 8) as usize] as f32 + 0.5) / 64.0 - 0.5
+                } else {
+                    0.0
+                };
+                let color = [0, 1, 2].map(|c| (p[c] as f32 + offset * spread).clamp(0.0, 255.0));
+                indices[i] = lookup.nearest(color.map(|v| v.round() as u8));
+            }
+        }
+        DitherMode::FloydSteinberg => {
+            // Error buffers for the current and next row, padded by one on each side
+            let row_len = width as usize + 2;
+            let mut current = vec![[0f32; 3]; row_len];
+            let mut next = vec![[0f32; 3]; row_len];
+
+            for y in 0..height {
+                for x in 0..width {
+                    let p = img.get_pixel(x, y);
+                    let i = (y * width + x) as usize;
+                    if !is_opaque(p[3]) {
+                        indices[i] = transparent.unwrap_or(0);
+                        continue;
+                    }
+
+                    let e = current[x as usize + 1];
+                    let wanted = [0, 1, 2].map(|c| (p[c] as f32 + e[c]).clamp(0.0, 255.0));
+                    let index = lookup.nearest(wanted.map(|v| v.round() as u8));
+                    indices[i] = index;
+
+                    let chosen = palette[index as usize];
+                    for c in 0..3 {
+                        let err = wanted[c] - chosen[c] as f32;
+                        current[x as usize + 2][c] += err * 7.0 / 16.0;
+                        next[x as usize][c] += err * 3.0 / 16.0;
+                        next[x as usize + 1][c] += err * 5.0 / 16.0;
+                        next[x as usize + 2][c] += err * 1.0 / 16.0;
+                    }
+                }
+                std::mem::swap(&mut current, &mut next);
+                next.iter_mut().for_each(|e| *e = [0.0; 3]);
+            }
+        }
+    }
+
+    IndexedImage {
+        width,
+        height,
+        palette,
+        transparent,
+        indices,
+    }
+}
+
+/// A median cut box along with its widest channel
+struct ColorBox {
+    colors: Vec<([u8; 3], u32)>,
+    channel: usize,
+    range: u8,
+}
+
+impl ColorBox {
+    fn new(colors: Vec<([u8; 3], u32)>) -> Self {
+        let (channel, range) = (0..3)
+            .map(|c| {
+                let min = colors.iter().map(|(col, _)| col[c]).min().unwrap_or(0);
+                let max = colors.iter().map(|(col, _)| col[c]).max().unwrap_or(0);
+                (c, max - min)
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap();
+        Self {
+            colors,
+            channel,
+            range,
+        }
+    }
+
+    fn average(&self) -> [u8; 3] {
+        let total: u64 = self.colors.iter().map(|&(_, n)| n as u64).sum();
+        [0, 1, 2].map(|c| {
+            let sum: u64 = self
+                .colors
+                .iter()
+                .map(|&(col, n)| col[c] as u64 * n as u64)
+                .sum();
+            ((sum + total / 2) / total) as u8
+        })
+    }
+}
+
+/// Median cut over a colour histogram
+fn median_cut(colors: Vec<([u8; 3], u32)>, target: usize) -> Vec<[u8; 3]> {
+    let mut boxes = vec![ColorBox::new(colors)];
+
+    while boxes.len() < target {
+        // Split the box with the widest channel range that still has more than one colour
+        let Some(box_idx) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.colors.len() > 1)
+            .max_by_key(|(_, b)| b.range)
+            .map(|(i, _)| i)
+        else {
+            break;
+        };
+
+        let ColorBox {
+            mut colors,
+            channel,
+            ..
+        } = boxes.swap_remove(box_idx);
+        colors.sort_unstable_by_key(|(col, _)| col[channel]);
+
+        // Split at the weighted median
+        let total: u64 = colors.iter().map(|&(_, n)| n as u64).sum();
+        let mut acc = 0u64;
+        let mut split = 1;
+        for (i, &(_, n)) in colors.iter().enumerate() {
+            acc += n as u64;
+            if acc * 2 >= total {
+                split = (i + 1).clamp(1, colors.len() - 1);
+                break;
+            }
+        }
+
+        let upper = colors.split_off(split);
+        boxes.push(ColorBox::new(colors));
+        boxes.push(ColorBox::new(upper));
+    }
+
+    boxes.iter().map(ColorBox::average).collect()
+}
+
+/// Nearest-colour search, cached on a 6-bit-per-channel grid for
+/// colours that aren't palette entries themselves
+struct NearestLookup<'a> {
+    palette: &'a [[u8; 3]],
+    exact: HashMap<[u8; 3], u8>,
+    cache: Vec<Option<u8>>,
+}
+
+impl<'a> NearestLookup<'a> {
+    fn new(palette: &'a [[u8; 3]]) -> Self {
+        let exact = palette
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| (color, i as u8))
+            .collect();
+        Self {
+            palette,
+            exact,
+            cache: vec![None; 1 << 18],
+        }
+    }
+
+    fn nearest(&mut self, color: [u8; 3]) -> u8 {
+        if let Some(&index) = self.exact.get(&color) {
+            return index;
+        }
+
+        let key = ((color[0] as usize >> 2) << 12)
+            | ((color[1] as usize >> 2) << 6)
+            | (color[2] as usize >> 2);
+
+        if let Some(index) = self.cache[key] {
+            return index;
+        }
+
+        let index = self
+            .palette
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, p)| {
+                (0..3)
+                    .map(|c| {
+                        let d = p[c] as i32 - color[c] as i32;
+                        d * d
+                    })
+                    .sum::<i32>()
+            })
+            .map(|(i, _)| i as u8)
+            .unwrap_or(0);
+
+        self.cache[key] = Some(index);
+        index
+    }
+}
@@ -0,0 +1,388 @@
+//! The queue a batch's worker pool pulls jobs from.
+//!
+//! Workers take the highest-priority job first, and jobs of equal priority
+//! in the order they were submitted, so `Processing` updates arrive in a
+//! predictable order. The queue stays live while the batch runs: jobs can
+//! be pushed, promoted, reordered and removed until the last worker goes
+//! idle. Pushed jobs take the next free index.
+//!
+//! A paused queue hands out no jobs; the ones already running finish, and
+//! the rest wait until it is resumed.
+
+use crate::batch::{BatchJob, BatchProgress};
+use crate::progress::ProgressSender;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// How soon a queued job runs relative to the others
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// Handle to a batch's job queue; clones share the same queue
+#[derive(Debug, Clone)]
+pub struct BatchQueue {
+    shared: Arc<Shared>,
+}
+
+/// Where a pending job sits in the queue: by priority, then by position,
+/// which starts out as the job's index
+type Slot = (Reverse<Priority>, i64);
+
+/// What a worker should do next
+pub(crate) enum Next {
+    Job(usize, Box<BatchJob>),
+    /// Every job is done; this worker reports the end of the batch
+    Finish,
+    /// Another worker finished the batch
+    Exit,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+    /// Waiting jobs and their indices, in the order workers take them
+    pending: BTreeMap<Slot, (usize, BatchJob)>,
+    submitted: usize,
+    running: usize,
+    paused: bool,
+    finished: bool,
+    /// Reports changes made through the queue; dropped when the batch
+    /// finishes so the progress channel can close
+    sender: Option<ProgressSender>,
+}
+
+impl State {
+    fn insert(&mut self, job: BatchJob, priority: Priority) -> usize {
+        let index = self.submitted;
+        self.submitted += 1;
+        self.pending
+            .insert((Reverse(priority), index as i64), (index, job));
+        index
+    }
+
+    fn slot_of(&self, index: usize) -> Option<Slot> {
+        self.pending
+            .iter()
+            .find(|(_, (pending, _))| *pending == index)
+            .map(|(slot, _)| *slot)
+    }
+
+    fn send(&self, progress: BatchProgress) {
+        if let Some(sender) = self.sender.as_ref() {
+            sender.send(progress);
+        }
+    }
+}
+
+impl BatchQueue {
+    pub(crate) fn new(jobs: Vec<BatchJob>, sender: ProgressSender) -> Self {
+        let mut state = State {
+            pending: BTreeMap::new(),
+            submitted: 0,
+            running: 0,
+            paused: false,
+            finished: false,
+            sender: Some(sender),
+        };
+        for job in jobs {
+            state.insert(job, Priority::Normal);
+        }
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(state),
+                condvar: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Add a job at normal priority. Sends `BatchProgress::Added` and
+    /// returns the index its progress updates carry, or `None` if the batch
+    /// has already finished.
+    pub fn push(&self, job: BatchJob) -> Option<usize> {
+        self.push_with_priority(job, Priority::Normal)
+    }
+
+    /// Like `push()`, ahead of or behind jobs of other priorities
+    pub fn push_with_priority(&self, job: BatchJob, priority: Priority) -> Option<usize> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.finished {
+            return None;
+        }
+        let index = state.insert(job.clone(), priority);
+        state.send(BatchProgress::Added { index, job });
+        self.shared.condvar.notify_one();
+        Some(index)
+    }
+
+    /// Make a queued job the next one to run, at high priority. Returns
+    /// `false` if it isn't queued (any more).
+    pub fn promote(&self, index: usize) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        let Some(slot) = state.slot_of(index) else {
+            return false;
+        };
+        let front = state.pending.keys().map(|(_, position)| *position).min();
+        let job = state.pending.remove(&slot).unwrap();
+        let position = front.map_or(0, |front| front - 1);
+        state.pending.insert((Reverse(Priority::High), position), job);
+        true
+    }
+
+    /// Run the queued jobs among `indices` in that order. They trade places
+    /// with each other, priorities included, so jobs not listed keep theirs.
+    /// Indices of jobs that aren't queued are ignored.
+    pub fn reorder(&self, indices: &[usize]) {
+        let mut state = self.shared.state.lock().unwrap();
+        let slots: Vec<Slot> = indices
+            .iter()
+            .filter_map(|&index| state.slot_of(index))
+            .collect();
+        let jobs: Vec<_> = slots
+            .iter()
+            .map(|slot| state.pending.remove(slot).unwrap())
+            .collect();
+        let mut sorted = slots;
+        sorted.sort();
+        for (slot, job) in sorted.into_iter().zip(jobs) {
+            state.pending.insert(slot, job);
+        }
+    }
+
+    /// Take a queued job off the batch. Sends `BatchProgress::Removed` and
+    /// returns the job, or `None` if it isn't queued (any more).
+    pub fn remove(&self, index: usize) -> Option<BatchJob> {
+        let mut state = self.shared.state.lock().unwrap();
+        let slot = state.slot_of(index)?;
+        let (_, job) = state.pending.remove(&slot).unwrap();
+        state.send(BatchProgress::Removed {
+            index,
+            file: crate::batch::file_name(&job),
+        });
+        // Removing the last job may leave the batch with nothing to do
+        self.shared.condvar.notify_all();
+        Some(job)
+    }
+
+    /// Jobs submitted so far, including the initial ones
+    pub fn submitted(&self) -> usize {
+        self.shared.state.lock().unwrap().submitted
+    }
+
+    /// Indices of the jobs no worker has taken yet, in the order they will
+    /// run
+    pub fn pending(&self) -> Vec<usize> {
+        let state = self.shared.state.lock().unwrap();
+        state.pending.values().map(|(index, _)| *index).collect()
+    }
+
+    /// Stop handing out jobs; running ones still finish. Sends
+    /// `BatchProgress::Paused` and returns `true` unless the batch was
+    /// already paused or has finished.
+    pub fn pause(&self) -> bool {
+        self.set_paused(true, BatchProgress::Paused)
+    }
+
+    /// Continue a paused batch with the next queued job. Sends
+    /// `BatchProgress::Resumed` and returns `true` unless the batch wasn't
+    /// paused.
+    pub fn resume(&self) -> bool {
+        self.set_paused(false, BatchProgress::Resumed)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.shared.state.lock().unwrap().paused
+    }
+
+    fn set_paused(&self, paused: bool, progress: BatchProgress) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.finished || state.paused == paused {
+            return false;
+        }
+        state.paused = paused;
+        state.send(progress);
+        self.shared.condvar.notify_all();
+        true
+    }
+
+    /// Whether every job is done and the queue refuses new ones
+    pub fn is_finished(&self) -> bool {
+        self.shared.state.lock().unwrap().finished
+    }
+
+    /// What a worker should do next, waiting while the queue is paused, or
+    /// empty but other jobs still run. Once nothing is queued or running,
+    /// the batch finishes and the queue closes. `started` runs under the
+    /// queue lock, so it sees jobs in the order they are taken.
+    pub(crate) fn take(&self, started: impl FnOnce(usize, &BatchJob)) -> Next {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if state.finished {
+                return Next::Exit;
+            }
+            if state.pending.is_empty() && state.running == 0 {
+                state.finished = true;
+                state.sender = None;
+                self.shared.condvar.notify_all();
+                return Next::Finish;
+            }
+            if !state.paused {
+                if let Some((_, (index, job))) = state.pending.pop_first() {
+                    state.running += 1;
+                    started(index, &job);
+                    return Next::Job(index, Box::new(job));
+                }
+            }
+            state = self.shared.condvar.wait(state).unwrap();
+        }
+    }
+
+    /// Mark a job from `take()` as done
+    pub(crate) fn done(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.running -= 1;
+        if state.running == 0 {
+            self.shared.condvar.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::ConversionOptions;
+    use crate::progress::progress_channel;
+    use std::time::Duration;
+
+    fn job(name: &str) -> BatchJob {
+        BatchJob {
+            input_path: name.into(),
+            output_path: format!("{}.webp", name).into(),
+            options: ConversionOptions::default(),
+            incremental: false,
+        }
+    }
+
+    fn take(queue: &BatchQueue) -> Option<usize> {
+        match queue.take(|_, _| {}) {
+            Next::Job(index, _) => Some(index),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_queue_order_and_finish() {
+        let (sender, receiver) = progress_channel();
+        let queue = BatchQueue::new(vec![job("a"), job("b")], sender);
+        assert_eq!(
+            queue.push_with_priority(job("urgent"), Priority::High),
+            Some(2)
+        );
+        assert_eq!(
+            queue.push_with_priority(job("later"), Priority::Low),
+            Some(3)
+        );
+        assert_eq!(queue.submitted(), 4);
+        assert_eq!(take(&queue), Some(2));
+
+        // Jobs pushed while others run still join the batch
+        assert_eq!(queue.push(job("c")), Some(4));
+        assert_eq!(queue.pending(), [0, 1, 4, 3]);
+        let mut started = Vec::new();
+        while !queue.pending().is_empty() {
+            started.extend(take(&queue));
+        }
+        assert_eq!(started, [0, 1, 4, 3]);
+
+        // The last worker to go idle finishes the batch; the others exit
+        for _ in 0..5 {
+            queue.done();
+        }
+        assert!(matches!(queue.take(|_, _| {}), Next::Finish));
+        assert!(matches!(queue.take(|_, _| {}), Next::Exit));
+        assert!(queue.is_finished());
+        assert_eq!(queue.push(job("too late")), None);
+
+        let added: Vec<usize> = receiver
+            .filter_map(|progress| match progress {
+                BatchProgress::Added { index, .. } => Some(index),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(added, [2, 3, 4]);
+    }
+
+    #[test]
+    fn test_promote_reorder_and_remove() {
+        let (sender, receiver) = progress_channel();
+        let jobs = ["a", "b", "c", "d", "e"].map(job).to_vec();
+        let queue = BatchQueue::new(jobs, sender);
+
+        assert!(queue.promote(3));
+        assert_eq!(queue.pending(), [3, 0, 1, 2, 4]);
+        queue.reorder(&[4, 1, 2]);
+        assert_eq!(queue.pending(), [3, 0, 4, 1, 2]);
+
+        assert_eq!(take(&queue), Some(3));
+        assert!(!queue.promote(3));
+        assert!(queue.remove(1).is_some());
+        assert!(queue.remove(1).is_none());
+        assert_eq!(queue.pending(), [0, 4, 2]);
+
+        // Removing every queued job while paused still ends the batch
+        assert!(queue.pause());
+        for index in [0, 4, 2] {
+            queue.remove(index);
+        }
+        queue.done();
+        assert!(matches!(queue.take(|_, _| {}), Next::Finish));
+
+        let removed: Vec<usize> = receiver
+            .filter_map(|progress| match progress {
+                BatchProgress::Removed { index, .. } => Some(index),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(removed, [1, 0, 4, 2]);
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let (sender, receiver) = progress_channel();
+        let queue = BatchQueue::new(vec![job("a"), job("b")], sender);
+        take(&queue).unwrap();
+        assert!(queue.pause());
+        assert!(!queue.pause());
+
+        // The running job finishes, but nothing new starts until resumed
+        queue.done();
+        let worker = {
+            let queue = queue.clone();
+            std::thread::spawn(move || take(&queue))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!worker.is_finished());
+        assert_eq!(queue.pending(), [1]);
+
+        assert!(queue.resume());
+        assert_eq!(worker.join().unwrap(), Some(1));
+        queue.done();
+        assert!(matches!(queue.take(|_, _| {}), Next::Finish));
+        assert!(!queue.pause());
+
+        assert!(matches!(receiver.recv(), Some(BatchProgress::Paused)));
+        assert!(matches!(receiver.recv(), Some(BatchProgress::Resumed)));
+        // The queue let go of its sender when the batch finished
+        assert!(receiver.recv().is_none());
+    }
+}
@@ -0,0 +1,326 @@
+//! Per-job batch reports: sizes, dimensions, timings, settings and errors
+//! for every file in a batch, exported as JSON or CSV.
+//!
+//! A `BatchReport` starts with every job pending and is filled in from the
+//! batch's `BatchProgress` updates, the same way a `Journal` is. Nothing is
+//! read from disk until the report is saved, so building and updating it
+//! doesn't hold up the thread showing progress.
+
+use crate::batch::{BatchJob, BatchProgress};
+use crate::converter::{ConversionOptions, ImageConverter, SupportedFormat};
+use crate::error::{ConvertError, IoContext, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// What happened to a job in the report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+    /// Never ran, e.g. because the batch was interrupted
+    Pending,
+    Converted,
+    /// An incremental job whose output was already up to date
+    Skipped,
+    Failed,
+    /// Taken off the queue before it ran
+    Removed,
+}
+
+/// One job's line in the report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportEntry {
+    pub input_path: PathBuf,
+    pub output_path: PathBuf,
+    pub status: ReportStatus,
+    pub input_format: Option<SupportedFormat>,
+    pub output_format: SupportedFormat,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub input_bytes: Option<u64>,
+    pub output_bytes: Option<u64>,
+    /// Output size as a fraction of the input size
+    pub compression_ratio: Option<f64>,
+    /// Time the conversion took, in milliseconds
+    pub time_ms: Option<f64>,
+    pub settings: ConversionOptions,
+    pub error: Option<String>,
+}
+
+/// Report on every job of a batch, in submission order
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchReport {
+    pub jobs: Vec<ReportEntry>,
+}
+
+impl BatchReport {
+    /// A report with every job pending
+    pub fn new(jobs: &[BatchJob]) -> Self {
+        Self {
+            jobs: jobs.iter().map(ReportEntry::pending).collect(),
+        }
+    }
+
+    /// Fill in a measured or finished job, or add or remove one. Other
+    /// progress updates are ignored.
+    pub fn record(&mut self, progress: &BatchProgress) {
+        match progress {
+            BatchProgress::Measured { index, input_bytes } => {
+                if let Some(entry) = self.jobs.get_mut(*index) {
+                    entry.input_bytes.get_or_insert(*input_bytes);
+                }
+            }
+            BatchProgress::Added { job, .. } => self.jobs.push(ReportEntry::pending(job)),
+            BatchProgress::Removed { index, .. } => {
+                if let Some(entry) = self.jobs.get_mut(*index) {
+                    entry.status = ReportStatus::Removed;
+                }
+            }
+            BatchProgress::Completed {
+                index,
+                stats,
+                elapsed,
+                ..
+            } => {
+                let Some(entry) = self.jobs.get_mut(*index) else {
+                    return;
+                };
+                entry.status = ReportStatus::Converted;
+                entry.input_format = stats.input_format.or(entry.input_format);
+                entry.input_bytes = stats.input_bytes.or(entry.input_bytes);
+                entry.width = Some(stats.width);
+                entry.height = Some(stats.height);
+                entry.output_bytes = Some(stats.output_bytes);
+                entry.time_ms = Some(elapsed.as_secs_f64() * 1000.0);
+                entry.update_ratio();
+            }
+            BatchProgress::Failed {
+                index,
+                error,
+                elapsed,
+                ..
+            } => {
+                let Some(entry) = self.jobs.get_mut(*index) else {
+                    return;
+                };
+                entry.status = ReportStatus::Failed;
+                entry.time_ms = Some(elapsed.as_secs_f64() * 1000.0);
+                entry.error = Some(error.to_string());
+            }
+            BatchProgress::Skipped { index, .. } => {
+                let Some(entry) = self.jobs.get_mut(*index) else {
+                    return;
+                };
+                entry.status = ReportStatus::Skipped;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self)
+            .map_err(|e| ConvertError::io("Failed to write report", e.into()))
+    }
+
+    /// One row per job; the settings column holds the options as JSON
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "input_path,output_path,status,input_format,output_format,width,height,\
+             input_bytes,output_bytes,compression_ratio,time_ms,settings,error\n",
+        );
+        for entry in &self.jobs {
+            let status = match entry.status {
+                ReportStatus::Pending => "pending",
+                ReportStatus::Converted => "converted",
+                ReportStatus::Skipped => "skipped",
+                ReportStatus::Failed => "failed",
+                ReportStatus::Removed => "removed",
+            };
+            let fields = [
+                entry.input_path.to_string_lossy().into_owned(),
+                entry.output_path.to_string_lossy().into_owned(),
+                status.to_string(),
+                optional(entry.input_format.map(|f| f.extension().to_string())),
+                entry.output_format.extension().to_string(),
+                optional(entry.width),
+                optional(entry.height),
+                optional(entry.input_bytes),
+                optional(entry.output_bytes),
+                optional(entry.compression_ratio.map(|ratio| format!("{:.4}", ratio))),
+                optional(entry.time_ms.map(|ms| format!("{:.1}", ms))),
+                serde_json::to_string(&entry.settings).unwrap_or_default(),
+                optional(entry.error.as_ref()),
+            ];
+            let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Read what the progress updates don't carry from disk: the input
+    /// size and format of jobs that weren't converted, and the output size
+    /// of skipped ones. `save()` does this first.
+    pub fn read_files(&mut self) {
+        for entry in &mut self.jobs {
+            if entry.input_bytes.is_none() {
+                entry.input_bytes = file_size(&entry.input_path);
+            }
+            if entry.input_format.is_none() {
+                entry.input_format = ImageConverter::detect_format(&entry.input_path)
+                    .ok()
+                    .flatten();
+            }
+            if entry.status == ReportStatus::Skipped && entry.output_bytes.is_none() {
+                entry.output_bytes = file_size(&entry.output_path);
+            }
+            entry.update_ratio();
+        }
+    }
+
+    /// Write the report as CSV if `path` ends in `.csv`, otherwise as JSON
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        self.read_files();
+        let is_csv = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+        let data = if is_csv {
+            self.to_csv().into_bytes()
+        } else {
+            self.to_json()?
+        };
+        std::fs::write(path, data).io_context("Failed to write report")
+    }
+}
+
+impl ReportEntry {
+    fn pending(job: &BatchJob) -> Self {
+        Self {
+            input_path: job.input_path.clone(),
+            output_path: job.output_path.clone(),
+            status: ReportStatus::Pending,
+            input_format: None,
+            output_format: job.options.format,
+            width: None,
+            height: None,
+            input_bytes: None,
+            output_bytes: None,
+            compression_ratio: None,
+            time_ms: None,
+            settings: job.options.clone(),
+            error: None,
+        }
+    }
+
+    fn update_ratio(&mut self) {
+        self.compression_ratio = match (self.input_bytes, self.output_bytes) {
+            (Some(input), Some(output)) if input > 0 => Some(output as f64 / input as f64),
+            _ => None,
+        };
+    }
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+fn optional(value: Option<impl ToString>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::ConversionStats;
+    use crate::testing::TempDir;
+    use std::time::Duration;
+
+    #[test]
+    fn test_batch_report() {
+        let dir = TempDir::new("report");
+
+        let jobs: Vec<BatchJob> = ["a", "b, c", "d"]
+            .iter()
+            .map(|name| {
+                let input_path = dir.join(format!("{}.png", name));
+                image::RgbImage::new(4, 4).save(&input_path).unwrap();
+                BatchJob {
+                    input_path,
+                    output_path: dir.join(format!("{}.webp", name)),
+                    options: ConversionOptions {
+                        format: SupportedFormat::WebP,
+                        ..Default::default()
+                    },
+                    incremental: false,
+                }
+            })
+            .collect();
+        let input_bytes = file_size(&jobs[0].input_path).unwrap();
+
+        let mut report = BatchReport::new(&jobs);
+        report.record(&BatchProgress::Completed {
+            index: 0,
+            file: String::new(),
+            stats: ConversionStats {
+                input_bytes: Some(1000),
+                input_format: Some(SupportedFormat::Png),
+                width: 4,
+                height: 4,
+                output_bytes: 250,
+                ..Default::default()
+            },
+            elapsed: Duration::from_millis(12),
+        });
+        report.record(&BatchProgress::Failed {
+            index: 1,
+            file: String::new(),
+            error: ConvertError::Decode("truncated".to_string()),
+            elapsed: Duration::from_millis(3),
+        });
+
+        let [converted, failed, pending] = report.jobs.as_slice() else {
+            panic!("expected three entries");
+        };
+        assert_eq!(converted.status, ReportStatus::Converted);
+        assert_eq!((converted.width, converted.height), (Some(4), Some(4)));
+        assert_eq!(converted.compression_ratio, Some(0.25));
+        assert_eq!(converted.time_ms, Some(12.0));
+        assert_eq!(failed.status, ReportStatus::Failed);
+        assert_eq!(
+            failed.error.as_deref(),
+            Some("Failed to decode image: truncated")
+        );
+        assert_eq!(pending.status, ReportStatus::Pending);
+
+        // What the updates didn't carry is read when the report is saved
+        assert_eq!((failed.input_format, pending.input_bytes), (None, None));
+        report.read_files();
+        let [_, failed, pending] = report.jobs.as_slice() else {
+            panic!("expected three entries");
+        };
+        assert_eq!(failed.input_format, Some(SupportedFormat::Png));
+        assert_eq!(pending.input_bytes, Some(input_bytes));
+
+        // Fields with separators or quotes are quoted
+        let csv = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2].starts_with(&format!("\"{}\",", jobs[1].input_path.display())));
+        assert!(lines[1].contains(",converted,png,webp,4,4,1000,250,0.2500,12.0,"));
+        assert!(lines[1].contains("\"{\"\"quality\"\":"));
+
+        let path = dir.join("report.json");
+        report.save(&path).unwrap();
+        let saved: BatchReport =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved.jobs.len(), 3);
+    }
+}
@@ -0,0 +1,422 @@
+//! Live and final statistics for a batch: progress weighted by input size,
+//! throughput, estimated time left, and bytes saved.
+//!
+//! Like a `Journal` or `BatchReport`, a `BatchStats` is fed every
+//! `BatchProgress` update of the batch it was created for.
+
+use crate::batch::BatchProgress;
+use std::time::{Duration, Instant};
+
+/// How far a job has got, for weighting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Queued,
+    /// Converted or failed
+    Ran,
+    Skipped,
+    Removed,
+}
+
+/// Total input size of a set of jobs. Jobs whose size isn't known yet are
+/// counted apart and weigh the average of the known ones.
+#[derive(Debug, Clone, Copy, Default)]
+struct Weight {
+    known: u64,
+    measured: usize,
+    unmeasured: usize,
+}
+
+impl Weight {
+    fn add(&mut self, size: Option<u64>) {
+        match size {
+            Some(size) => {
+                self.known += size;
+                self.measured += 1;
+            }
+            None => self.unmeasured += 1,
+        }
+    }
+
+    fn subtract(&mut self, size: Option<u64>) {
+        match size {
+            Some(size) => {
+                self.known -= size;
+                self.measured -= 1;
+            }
+            None => self.unmeasured -= 1,
+        }
+    }
+
+    fn jobs(&self) -> usize {
+        self.measured + self.unmeasured
+    }
+
+    fn total(&self, estimate: f64) -> f64 {
+        self.known as f64 + self.unmeasured as f64 * estimate
+    }
+}
+
+/// Running totals for a batch
+#[derive(Debug, Clone)]
+pub struct BatchStats {
+    /// Input size of each job once known, at least 1 so empty files still
+    /// count, and how far it has got
+    jobs: Vec<(Option<u64>, Stage)>,
+    /// Jobs not removed
+    all: Weight,
+    /// Finished jobs, skipped ones included
+    done: Weight,
+    /// Jobs that were actually converted or failed, for throughput
+    run: Weight,
+    converted: usize,
+    pixels: u64,
+    input_bytes: u64,
+    output_bytes: u64,
+    ratio_sum: f64,
+    ratios: usize,
+    started: Instant,
+    /// Time spent paused, not counting a pause still in progress
+    paused_for: Duration,
+    paused_at: Option<Instant>,
+}
+
+impl BatchStats {
+    /// Statistics for a batch of `jobs` jobs. Their input sizes, which
+    /// weight the progress, come from `Measured` updates, so nothing is
+    /// read from disk here. The clock for throughput and ETA starts now.
+    pub fn new(jobs: usize) -> Self {
+        Self {
+            jobs: vec![(None, Stage::Queued); jobs],
+            all: Weight {
+                unmeasured: jobs,
+                ..Default::default()
+            },
+            done: Weight::default(),
+            run: Weight::default(),
+            converted: 0,
+            pixels: 0,
+            input_bytes: 0,
+            output_bytes: 0,
+            ratio_sum: 0.0,
+            ratios: 0,
+            started: Instant::now(),
+            paused_for: Duration::ZERO,
+            paused_at: None,
+        }
+    }
+
+    /// Account for a measured, finished, added or removed job, or stop and
+    /// restart the clock while the batch is paused. Other progress updates
+    /// are ignored.
+    pub fn record(&mut self, progress: &BatchProgress) {
+        match progress {
+            BatchProgress::Measured { index, input_bytes } => self.measure(*index, *input_bytes),
+            BatchProgress::Completed { index, stats, .. } => {
+                self.converted += 1;
+                self.pixels += stats.pixels();
+                if let Some(input) = stats.input_bytes {
+                    self.measure(*index, input);
+                }
+                if let Some(input) = stats.input_bytes.filter(|&input| input > 0) {
+                    self.input_bytes += input;
+                    self.output_bytes += stats.output_bytes;
+                    self.ratio_sum += stats.output_bytes as f64 / input as f64;
+                    self.ratios += 1;
+                }
+                self.finish(*index, Stage::Ran);
+            }
+            BatchProgress::Failed { index, .. } => self.finish(*index, Stage::Ran),
+            BatchProgress::Skipped { index, .. } => self.finish(*index, Stage::Skipped),
+            BatchProgress::Added { .. } => {
+                self.jobs.push((None, Stage::Queued));
+                self.all.add(None);
+            }
+            BatchProgress::Removed { index, .. } => {
+                if let Some((size, stage @ Stage::Queued)) = self.jobs.get_mut(*index) {
+                    *stage = Stage::Removed;
+                    self.all.subtract(*size);
+                }
+            }
+            BatchProgress::Paused => {
+                self.paused_at.get_or_insert_with(Instant::now);
+            }
+            BatchProgress::Resumed => {
+                if let Some(paused_at) = self.paused_at.take() {
+                    self.paused_for += paused_at.elapsed();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn measure(&mut self, index: usize, input_bytes: u64) {
+        let Some((size @ None, stage)) = self.jobs.get_mut(index) else {
+            return;
+        };
+        let known = Some(input_bytes.max(1));
+        for (weight, counted) in [
+            (&mut self.all, *stage != Stage::Removed),
+            (&mut self.done, matches!(stage, Stage::Ran | Stage::Skipped)),
+            (&mut self.run, *stage == Stage::Ran),
+        ] {
+            if counted {
+                weight.subtract(None);
+                weight.add(known);
+            }
+        }
+        *size = known;
+    }
+
+    fn finish(&mut self, index: usize, to: Stage) {
+        let Some((size, stage @ Stage::Queued)) = self.jobs.get_mut(index) else {
+            return;
+        };
+        *stage = to;
+        self.done.add(*size);
+        if to == Stage::Ran {
+            self.run.add(*size);
+        }
+    }
+
+    /// Weight of a job whose size isn't known yet: the average known size
+    fn estimate(&self) -> f64 {
+        if self.all.measured == 0 {
+            return 1.0;
+        }
+        self.all.known as f64 / self.all.measured as f64
+    }
+
+    pub fn files_done(&self) -> usize {
+        self.done.jobs()
+    }
+
+    pub fn files_total(&self) -> usize {
+        self.all.jobs()
+    }
+
+    /// Fraction of the batch done, by input size
+    pub fn fraction(&self) -> f64 {
+        let estimate = self.estimate();
+        let total = self.all.total(estimate);
+        if total == 0.0 {
+            return 1.0;
+        }
+        self.done.total(estimate) / total
+    }
+
+    /// Time the batch has been running, pauses excluded
+    pub fn elapsed(&self) -> Duration {
+        let paused = self.paused_for + self.paused_at.map_or(Duration::ZERO, |at| at.elapsed());
+        self.started.elapsed().saturating_sub(paused)
+    }
+
+    /// Source megapixels converted per second of wall-clock time
+    pub fn megapixels_per_second(&self) -> Option<f64> {
+        let seconds = self.elapsed().as_secs_f64();
+        (self.pixels > 0 && seconds > 0.0).then(|| self.pixels as f64 / 1e6 / seconds)
+    }
+
+    /// Time left at the input bytes per second converted so far
+    pub fn eta(&self) -> Option<Duration> {
+        if self.run.jobs() == 0 {
+            return None;
+        }
+        let estimate = self.estimate();
+        let remaining = self.all.total(estimate) - self.done.total(estimate);
+        let seconds = self.elapsed().as_secs_f64() * remaining / self.run.total(estimate);
+        Duration::try_from_secs_f64(seconds).ok()
+    }
+
+    /// Input minus output bytes of the converted files; negative if the
+    /// outputs are larger
+    pub fn bytes_saved(&self) -> i64 {
+        self.input_bytes as i64 - self.output_bytes as i64
+    }
+
+    /// Mean of the converted files' output/input size ratios
+    pub fn average_compression_ratio(&self) -> Option<f64> {
+        (self.ratios > 0).then(|| self.ratio_sum / self.ratios as f64)
+    }
+
+    /// One line of live progress, e.g. `3/10 files, 45% — 12.3 MP/s, about 42 s left`
+    pub fn progress_line(&self) -> String {
+        let mut line = format!(
+            "{}/{} files, {:.0}%",
+            self.files_done(),
+            self.files_total(),
+            self.fraction() * 100.0
+        );
+        let mut details = Vec::new();
+        if let Some(rate) = self.megapixels_per_second() {
+            details.push(format!("{:.1} MP/s", rate));
+        }
+        if let Some(eta) = self
+            .eta()
+            .filter(|_| self.files_done() < self.files_total())
+        {
+            details.push(format!("about {} left", format_duration(eta)));
+        }
+        if !details.is_empty() {
+            line.push_str(" — ");
+            line.push_str(&details.join(", "));
+        }
+        line
+    }
+
+    /// Totals for the finished batch, e.g. `saved 12.4 MB, average output
+    /// 38% of input, 12.3 MP/s over 1 min 3 s`
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.converted > 0 {
+            let saved = self.bytes_saved();
+            parts.push(if saved >= 0 {
+                format!("saved {}", format_bytes(saved as u64))
+            } else {
+                format!("grew by {}", format_bytes(saved.unsigned_abs()))
+            });
+        }
+        if let Some(ratio) = self.average_compression_ratio() {
+            parts.push(format!("average output {:.0}% of input", ratio * 100.0));
+        }
+        match self.megapixels_per_second() {
+            Some(rate) => parts.push(format!(
+                "{:.1} MP/s over {}",
+                rate,
+                format_duration(self.elapsed())
+            )),
+            None => parts.push(format!("took {}", format_duration(self.elapsed()))),
+        }
+        parts.join(", ")
+    }
+}
+
+/// Byte count in decimal units, e.g. `12.4 MB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["kB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} bytes", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "";
+    for next in UNITS {
+        if value < 1000.0 {
+            break;
+        }
+        value /= 1000.0;
+        unit = next;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
+/// Rounded duration, e.g. `42 s` or `3 min 5 s`
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64().round() as u64;
+    match seconds {
+        0..60 => format!("{} s", seconds),
+        60..3600 => format!("{} min {} s", seconds / 60, seconds % 60),
+        _ => format!("{} h {} min", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::BatchJob;
+    use crate::converter::{ConversionOptions, ConversionStats};
+    use crate::error::ConvertError;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_weighted_batch_stats() {
+        let mut stats = BatchStats::new(4);
+        assert_eq!(stats.fraction(), 0.0);
+        assert!(stats.eta().is_none());
+
+        // One large input and three small ones; until they're measured,
+        // jobs weigh the average of the measured ones
+        stats.record(&BatchProgress::Measured {
+            index: 0,
+            input_bytes: 7000,
+        });
+        stats.record(&BatchProgress::Measured {
+            index: 1,
+            input_bytes: 1000,
+        });
+        stats.record(&BatchProgress::Failed {
+            index: 2,
+            file: String::new(),
+            error: ConvertError::Decode("truncated".to_string()),
+            elapsed: Duration::from_millis(1),
+        });
+        assert_eq!(stats.fraction(), 0.25);
+        for index in 2..4 {
+            stats.record(&BatchProgress::Measured {
+                index,
+                input_bytes: 1000,
+            });
+        }
+        assert_eq!(stats.fraction(), 0.1);
+
+        stats.record(&BatchProgress::Completed {
+            index: 0,
+            file: String::new(),
+            stats: ConversionStats {
+                input_bytes: Some(7000),
+                output_bytes: 1750,
+                width: 2000,
+                height: 1000,
+                ..Default::default()
+            },
+            elapsed: Duration::from_millis(5),
+        });
+        assert_eq!(stats.fraction(), 0.8);
+        assert!(stats.eta().is_some());
+        assert!(stats.progress_line().starts_with("2/4 files, 80%"));
+
+        stats.record(&BatchProgress::Completed {
+            index: 1,
+            file: String::new(),
+            stats: ConversionStats {
+                input_bytes: Some(1000),
+                output_bytes: 750,
+                ..Default::default()
+            },
+            elapsed: Duration::from_millis(1),
+        });
+        stats.record(&BatchProgress::Skipped {
+            index: 3,
+            file: String::new(),
+        });
+
+        assert_eq!(stats.files_done(), 4);
+        assert_eq!(stats.fraction(), 1.0);
+        assert_eq!(stats.bytes_saved(), 5500);
+        assert_eq!(stats.average_compression_ratio(), Some(0.5));
+        assert!(stats.summary().starts_with("saved 5.5 kB, average output 50% of input"));
+
+        // A job added mid-batch counts until it is taken off again
+        stats.record(&BatchProgress::Added {
+            index: 4,
+            job: BatchJob {
+                input_path: PathBuf::from("late.png"),
+                output_path: PathBuf::from("late.webp"),
+                options: ConversionOptions::default(),
+                incremental: false,
+            },
+        });
+        assert_eq!(stats.files_total(), 5);
+        assert!(stats.fraction() < 1.0);
+        stats.record(&BatchProgress::Removed {
+            index: 4,
+            file: String::new(),
+        });
+        assert_eq!((stats.files_total(), stats.fraction()), (4, 1.0));
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(999), "999 bytes");
+        assert_eq!(format_bytes(12_400_000), "12.4 MB");
+        assert_eq!(format_duration(Duration::from_secs(185)), "3 min 5 s");
+    }
+}
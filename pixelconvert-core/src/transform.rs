@@ -0,0 +1,1129 @@
+//! Image operations applied between decoding and encoding: the source
+//! image is cropped, its uniform border trimmed, then it's rotated and
+//! flipped and placed on a larger canvas, and finally watermarked, before
+//! it's handed to the encoder
+
+use crate::converter::ConversionOptions;
+use crate::error::{ConvertError, Result};
+use crate::limits::DecodeLimits;
+use image::imageops::FilterType;
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A rectangle of the source image to keep, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Crop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Crop {
+    /// The largest crop of a `width`×`height` image with the given aspect
+    /// ratio, centred
+    pub fn centered(width: u32, height: u32, ratio: (u32, u32)) -> Self {
+        let (ratio_width, ratio_height) = (ratio.0.max(1) as u64, ratio.1.max(1) as u64);
+        let (crop_width, crop_height) = if width as u64 * ratio_height > height as u64 * ratio_width
+        {
+            // Wider than the ratio: full height
+            ((height as u64 * ratio_width / ratio_height) as u32, height)
+        } else {
+            (width, (width as u64 * ratio_height / ratio_width) as u32)
+        };
+        let (crop_width, crop_height) = (crop_width.max(1), crop_height.max(1));
+
+        Self {
+            x: (width.saturating_sub(crop_width)) / 2,
+            y: (height.saturating_sub(crop_height)) / 2,
+            width: crop_width,
+            height: crop_height,
+        }
+    }
+
+    /// The part of the crop inside a `width`×`height` image, or `None` if
+    /// they don't overlap
+    pub fn clamp(&self, width: u32, height: u32) -> Option<Self> {
+        let right = self.x.saturating_add(self.width).min(width);
+        let bottom = self.y.saturating_add(self.height).min(height);
+        (self.x < right && self.y < bottom).then(|| Self {
+            x: self.x,
+            y: self.y,
+            width: right - self.x,
+            height: bottom - self.y,
+        })
+    }
+
+    /// Whether the crop keeps all of a `width`×`height` image
+    pub fn covers(&self, width: u32, height: u32) -> bool {
+        self.clamp(width, height) == Some(Self::full(width, height))
+    }
+
+    fn full(width: u32, height: u32) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    fn apply(&self, img: DynamicImage) -> Result<DynamicImage> {
+        let Some(crop) = self.clamp(img.width(), img.height()) else {
+            return Err(ConvertError::InvalidOperation(format!(
+                "the crop {} lies outside the {}×{} image",
+                self,
+                img.width(),
+                img.height()
+            )));
+        };
+        if crop == Self::full(img.width(), img.height()) {
+            return Ok(img);
+        }
+        Ok(img.crop_imm(crop.x, crop.y, crop.width, crop.height))
+    }
+}
+
+/// Geometry as `WIDTHxHEIGHT+X+Y`
+impl fmt::Display for Crop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}+{}+{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// Parse `WIDTHxHEIGHT+X+Y`, or `WIDTHxHEIGHT` for a crop from the top left
+impl FromStr for Crop {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || format!("expected WIDTHxHEIGHT+X+Y, got '{}'", s);
+        let mut parts = s.split('+');
+        let size = parts.next().ok_or_else(invalid)?;
+        let (width, height) = size.split_once(['x', 'X']).ok_or_else(invalid)?;
+        let number = |part: &str| part.trim().parse::<u32>().map_err(|_| invalid());
+
+        let crop = Self {
+            width: number(width)?,
+            height: number(height)?,
+            x: parts.next().map(number).transpose()?.unwrap_or(0),
+            y: parts.next().map(number).transpose()?.unwrap_or(0),
+        };
+        if parts.next().is_some() || crop.width == 0 || crop.height == 0 {
+            return Err(invalid());
+        }
+        Ok(crop)
+    }
+}
+
+/// Aspect ratios offered by the crop tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AspectRatio {
+    /// Any shape
+    Free,
+    Square,
+    /// 4:3
+    Standard,
+    /// 16:9
+    Wide,
+    /// 9:16
+    Portrait,
+    Custom {
+        width: u32,
+        height: u32,
+    },
+}
+
+impl AspectRatio {
+    /// Width and height of the ratio, `None` for a free crop
+    pub fn ratio(&self) -> Option<(u32, u32)> {
+        match self {
+            Self::Free => None,
+            Self::Square => Some((1, 1)),
+            Self::Standard => Some((4, 3)),
+            Self::Wide => Some((16, 9)),
+            Self::Portrait => Some((9, 16)),
+            Self::Custom { width, height } => Some((*width, *height)),
+        }
+    }
+
+    pub fn display_name(&self) -> String {
+        match self {
+            Self::Free => "Free".to_string(),
+            Self::Custom { .. } => "Custom".to_string(),
+            _ => {
+                let (width, height) = self.ratio().unwrap_or((1, 1));
+                format!("{}:{}", width, height)
+            }
+        }
+    }
+
+    /// The presets, followed by a custom 3:2 ratio
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::Free,
+            Self::Square,
+            Self::Standard,
+            Self::Wide,
+            Self::Portrait,
+            Self::Custom {
+                width: 3,
+                height: 2,
+            },
+        ]
+    }
+}
+
+/// Rotation and flips, applied after the crop in this order: quarter
+/// turns, the angle, then the flips
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Orientation {
+    /// Clockwise quarter turns (0-3)
+    pub quarter_turns: u8,
+    /// Further clockwise rotation in degrees, e.g. to straighten a scan.
+    /// The corners it uncovers are transparent, so the alpha policy fills
+    /// them with the background colour where transparency can't be kept.
+    pub angle: f32,
+    /// Crop an angled rotation to the largest upright rectangle inside it,
+    /// leaving no corners to fill
+    pub auto_crop: bool,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl Orientation {
+    /// Whether the orientation leaves images as they are
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The orientation turned by `turns` more clockwise quarter turns
+    /// (negative for counter-clockwise)
+    pub fn turned(self, turns: i32) -> Self {
+        Self {
+            quarter_turns: (self.quarter_turns as i32 + turns).rem_euclid(4) as u8,
+            ..self
+        }
+    }
+
+    fn apply(&self, img: DynamicImage) -> DynamicImage {
+        let mut img = match self.quarter_turns % 4 {
+            1 => img.rotate90(),
+            2 => img.rotate180(),
+            3 => img.rotate270(),
+            _ => img,
+        };
+
+        let angle = self.angle.rem_euclid(360.0);
+        if angle > 0.01 && angle < 359.99 {
+            let angle = (angle as f64).to_radians();
+            let source_size = (img.width() as f64, img.height() as f64);
+            img = DynamicImage::ImageRgba8(rotate(&img.to_rgba8(), angle));
+            if self.auto_crop {
+                let (width, height) = inscribed_size(source_size, angle);
+                let (width, height) = (width.min(img.width()), height.min(img.height()));
+                img = img.crop_imm(
+                    (img.width() - width) / 2,
+                    (img.height() - height) / 2,
+                    width,
+                    height,
+                );
+            }
+        }
+
+        if self.flip_horizontal {
+            img = img.fliph();
+        }
+        if self.flip_vertical {
+            img = img.flipv();
+        }
+        img
+    }
+}
+
+/// Rotate clockwise by `angle` radians around the centre, growing the
+/// canvas to fit the corners and leaving the uncovered area transparent.
+/// Bilinear sampling on premultiplied colour, so edges don't darken.
+fn rotate(img: &RgbaImage, angle: f64) -> RgbaImage {
+    let (width, height) = (img.width() as f64, img.height() as f64);
+    let (sin, cos) = angle.sin_cos();
+    let out_width = (width * cos.abs() + height * sin.abs()).round().max(1.0);
+    let out_height = (width * sin.abs() + height * cos.abs()).round().max(1.0);
+
+    let pixel = |x: i64, y: i64| -> [f64; 4] {
+        if x < 0 || y < 0 || x >= img.width() as i64 || y >= img.height() as i64 {
+            return [0.0; 4];
+        }
+        let p = img.get_pixel(x as u32, y as u32);
+        let alpha = p[3] as f64 / 255.0;
+        [
+            p[0] as f64 * alpha,
+            p[1] as f64 * alpha,
+            p[2] as f64 * alpha,
+            alpha,
+        ]
+    };
+
+    RgbaImage::from_fn(out_width as u32, out_height as u32, |x, y| {
+        // Back from the output pixel's centre to the source
+        let (u, v) = (
+            x as f64 + 0.5 - out_width / 2.0,
+            y as f64 + 0.5 - out_height / 2.0,
+        );
+        let source_x = u * cos + v * sin + width / 2.0 - 0.5;
+        let source_y = -u * sin + v * cos + height / 2.0 - 0.5;
+
+        let (x0, y0) = (source_x.floor(), source_y.floor());
+        let (fx, fy) = (source_x - x0, source_y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let mut sum = [0.0; 4];
+        for (dx, dy, weight) in [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let p = pixel(x0 + dx, y0 + dy);
+            for c in 0..4 {
+                sum[c] += p[c] * weight;
+            }
+        }
+
+        let alpha = sum[3];
+        if alpha <= 0.0 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        Rgba([
+            (sum[0] / alpha).round().clamp(0.0, 255.0) as u8,
+            (sum[1] / alpha).round().clamp(0.0, 255.0) as u8,
+            (sum[2] / alpha).round().clamp(0.0, 255.0) as u8,
+            (alpha * 255.0).round().clamp(0.0, 255.0) as u8,
+        ])
+    })
+}
+
+/// Size of the largest upright rectangle inside a `width`×`height` image
+/// rotated by `angle` radians
+fn inscribed_size((width, height): (f64, f64), angle: f64) -> (u32, u32) {
+    let (sin, cos) = (angle.sin().abs(), angle.cos().abs());
+    let (long, short) = (width.max(height), width.min(height));
+
+    // Thin or near-45° images: two corners of the rectangle touch the
+    // long sides; otherwise all four corners touch the sides
+    let half_constrained = short <= 2.0 * sin * cos * long || (sin - cos).abs() < 1e-9;
+    let (inner_width, inner_height) = if half_constrained {
+        let half = short / 2.0;
+        if width >= height {
+            (half / sin, half / cos)
+        } else {
+            (half / cos, half / sin)
+        }
+    } else {
+        let cos_2a = cos * cos - sin * sin;
+        (
+            (width * cos - height * sin) / cos_2a,
+            (height * cos - width * sin) / cos_2a,
+        )
+    };
+
+    // Less a pixel, which bilinear sampling blends with the corners
+    (
+        ((inner_width - 1.0).floor() as u32).max(1),
+        ((inner_height - 1.0).floor() as u32).max(1),
+    )
+}
+
+/// What `Trim` treats as border
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrimMode {
+    /// Pixels matching the top-left corner
+    Corner,
+    /// Transparent pixels
+    Transparency,
+}
+
+impl TrimMode {
+    pub fn display_name(&self) -> &str {
+        match self {
+            Self::Corner => "Corner Colour",
+            Self::Transparency => "Transparency",
+        }
+    }
+
+    pub fn all() -> Vec<Self> {
+        vec![Self::Corner, Self::Transparency]
+    }
+}
+
+/// Trim a uniform border, e.g. the white margins around a product photo,
+/// then optionally pad the result evenly with the border colour
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Trim {
+    pub mode: TrimMode,
+    /// How far (0-255, per channel) a pixel may be from the border colour,
+    /// or for `Transparency` how opaque it may be, and still count as
+    /// border
+    pub tolerance: u8,
+    /// Pixels added back on each side after trimming
+    pub padding: u32,
+}
+
+impl Default for Trim {
+    fn default() -> Self {
+        Self {
+            mode: TrimMode::Corner,
+            tolerance: 10,
+            padding: 0,
+        }
+    }
+}
+
+impl Trim {
+    /// The part of `img` inside its border, `None` if there's no border
+    /// or the image is all border
+    pub fn detect(&self, img: &RgbaImage) -> Option<Crop> {
+        let (width, height) = img.dimensions();
+        let background = *img.get_pixel_checked(0, 0)?;
+        let is_border = |x: u32, y: u32| {
+            let pixel = img.get_pixel(x, y);
+            match self.mode {
+                TrimMode::Corner => pixel
+                    .0
+                    .iter()
+                    .zip(background.0)
+                    .all(|(a, b)| a.abs_diff(b) <= self.tolerance),
+                TrimMode::Transparency => pixel[3] <= self.tolerance,
+            }
+        };
+        let row_is_border = |y: u32| (0..width).all(|x| is_border(x, y));
+
+        let top = (0..height).find(|&y| !row_is_border(y))?;
+        let bottom = (top..height).rev().find(|&y| !row_is_border(y))?;
+        let column_is_border = |x: u32| (top..=bottom).all(|y| is_border(x, y));
+        let left = (0..width).find(|&x| !column_is_border(x))?;
+        let right = (left..width).rev().find(|&x| !column_is_border(x))?;
+
+        let crop = Crop {
+            x: left,
+            y: top,
+            width: right - left + 1,
+            height: bottom - top + 1,
+        };
+        (!crop.covers(width, height)).then_some(crop)
+    }
+
+    fn apply(&self, img: DynamicImage, limits: &DecodeLimits) -> Result<DynamicImage> {
+        let rgba = img.to_rgba8();
+        let img = match self.detect(&rgba) {
+            Some(crop) => img.crop_imm(crop.x, crop.y, crop.width, crop.height),
+            None => img,
+        };
+        if self.padding == 0 {
+            return Ok(img);
+        }
+
+        let padded = |side: u32| {
+            self.padding
+                .checked_mul(2)
+                .and_then(|padding| side.checked_add(padding))
+        };
+        let (Some(width), Some(height)) = (padded(img.width()), padded(img.height())) else {
+            return Err(ConvertError::LimitExceeded(format!(
+                "{} pixels of padding is too much",
+                self.padding
+            )));
+        };
+        limits.check_size((width, height))?;
+
+        let background = match self.mode {
+            TrimMode::Corner => rgba.get_pixel_checked(0, 0).copied(),
+            TrimMode::Transparency => None,
+        };
+        let mut canvas =
+            RgbaImage::from_pixel(width, height, background.unwrap_or(Rgba([0, 0, 0, 0])));
+        image::imageops::replace(
+            &mut canvas,
+            &img.to_rgba8(),
+            self.padding as i64,
+            self.padding as i64,
+        );
+        Ok(DynamicImage::ImageRgba8(canvas))
+    }
+}
+
+/// One of nine points to anchor an image to, on a canvas or under a
+/// watermark
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Gravity {
+    NorthWest,
+    North,
+    NorthEast,
+    West,
+    #[default]
+    Center,
+    East,
+    SouthWest,
+    South,
+    SouthEast,
+}
+
+impl Gravity {
+    pub fn display_name(&self) -> &str {
+        match self {
+            Self::NorthWest => "Top Left",
+            Self::North => "Top",
+            Self::NorthEast => "Top Right",
+            Self::West => "Left",
+            Self::Center => "Centre",
+            Self::East => "Right",
+            Self::SouthWest => "Bottom Left",
+            Self::South => "Bottom",
+            Self::SouthEast => "Bottom Right",
+        }
+    }
+
+    /// Row by row, top left first
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::NorthWest,
+            Self::North,
+            Self::NorthEast,
+            Self::West,
+            Self::Center,
+            Self::East,
+            Self::SouthWest,
+            Self::South,
+            Self::SouthEast,
+        ]
+    }
+
+    /// Where something `free_width`×`free_height` pixels smaller than its
+    /// container goes: the offset of its top left corner
+    pub fn offset(&self, free_width: u32, free_height: u32) -> (u32, u32) {
+        let (column, row) = self.thirds();
+        let place = |free: u32, third: u32| match third {
+            0 => 0,
+            1 => free / 2,
+            _ => free,
+        };
+        (place(free_width, column), place(free_height, row))
+    }
+
+    /// Column and row (0-2) of the point
+    pub(crate) fn thirds(&self) -> (u32, u32) {
+        let index = Self::all().iter().position(|g| g == self).unwrap_or(4) as u32;
+        (index % 3, index / 3)
+    }
+}
+
+/// The canvas an image is placed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CanvasSize {
+    /// The smallest canvas of this width:height ratio the image fits in
+    Ratio { width: u32, height: u32 },
+    /// Exactly this many pixels, at least the size of the image
+    Exact { width: u32, height: u32 },
+}
+
+impl CanvasSize {
+    /// The canvas for a `width`×`height` image; an exact size the image
+    /// doesn't fit in is `InvalidOperation`, a ratio past `u32` pixels a
+    /// side `LimitExceeded`
+    fn for_image(&self, width: u32, height: u32) -> Result<(u32, u32)> {
+        match *self {
+            Self::Ratio {
+                width: ratio_width,
+                height: ratio_height,
+            } => {
+                let too_large = || {
+                    ConvertError::LimitExceeded(format!(
+                        "the {} canvas for the {}×{} image is too large",
+                        self, width, height
+                    ))
+                };
+                let (ratio_width, ratio_height) =
+                    (ratio_width.max(1) as u64, ratio_height.max(1) as u64);
+                let (image_width, image_height) = (width as u64, height as u64);
+                let wide = image_width
+                    .checked_mul(ratio_height)
+                    .ok_or_else(too_large)?;
+                let tall = image_height
+                    .checked_mul(ratio_width)
+                    .ok_or_else(too_large)?;
+                let size = if wide >= tall {
+                    (image_width, wide.div_ceil(ratio_width))
+                } else {
+                    (tall.div_ceil(ratio_height), image_height)
+                };
+                Ok((
+                    u32::try_from(size.0).map_err(|_| too_large())?,
+                    u32::try_from(size.1).map_err(|_| too_large())?,
+                ))
+            }
+            Self::Exact {
+                width: canvas_width,
+                height: canvas_height,
+            } => {
+                if canvas_width < width || canvas_height < height {
+                    return Err(ConvertError::InvalidOperation(format!(
+                        "the {}×{} image is larger than the {} canvas",
+                        width, height, self
+                    )));
+                }
+                Ok((canvas_width, canvas_height))
+            }
+        }
+    }
+}
+
+/// `WIDTH:HEIGHT` for a ratio, `WIDTHxHEIGHT` for an exact size
+impl fmt::Display for CanvasSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ratio { width, height } => write!(f, "{}:{}", width, height),
+            Self::Exact { width, height } => write!(f, "{}x{}", width, height),
+        }
+    }
+}
+
+impl FromStr for CanvasSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || format!("expected WIDTH:HEIGHT or WIDTHxHEIGHT, got '{}'", s);
+        let number = |part: &str| match part.trim().parse::<u32>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(invalid()),
+        };
+        if let Some((width, height)) = s.split_once(':') {
+            Ok(Self::Ratio {
+                width: number(width)?,
+                height: number(height)?,
+            })
+        } else if let Some((width, height)) = s.split_once(['x', 'X']) {
+            Ok(Self::Exact {
+                width: number(width)?,
+                height: number(height)?,
+            })
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+/// What fills the canvas around the image
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CanvasFill {
+    /// The background colour
+    #[default]
+    Solid,
+    Transparent,
+    /// A blurred copy of the image, scaled to cover the canvas
+    Blur,
+    /// The image's edge pixels, repeated outwards
+    Edge,
+}
+
+impl CanvasFill {
+    pub fn display_name(&self) -> &str {
+        match self {
+            Self::Solid => "Background Colour",
+            Self::Transparent => "Transparent",
+            Self::Blur => "Blurred Copy",
+            Self::Edge => "Extend Edges",
+        }
+    }
+
+    pub fn all() -> Vec<Self> {
+        vec![Self::Solid, Self::Transparent, Self::Blur, Self::Edge]
+    }
+}
+
+/// Letterbox or pillarbox an image onto a larger canvas. Pixels are never
+/// scaled or cropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Canvas {
+    pub size: CanvasSize,
+    /// Where the image sits on the canvas
+    pub gravity: Gravity,
+    pub fill: CanvasFill,
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        Self {
+            size: CanvasSize::Ratio {
+                width: 1,
+                height: 1,
+            },
+            gravity: Gravity::Center,
+            fill: CanvasFill::Solid,
+        }
+    }
+}
+
+impl Canvas {
+    fn apply(
+        &self,
+        img: DynamicImage,
+        background: [u8; 3],
+        limits: &DecodeLimits,
+    ) -> Result<DynamicImage> {
+        let (width, height) = (img.width(), img.height());
+        let (canvas_width, canvas_height) = self.size.for_image(width, height)?;
+        if (canvas_width, canvas_height) == (width, height) {
+            return Ok(img);
+        }
+        limits.check_size((canvas_width, canvas_height))?;
+        let (x, y) = self
+            .gravity
+            .offset(canvas_width - width, canvas_height - height);
+
+        let rgba = img.to_rgba8();
+        let mut canvas = match self.fill {
+            CanvasFill::Solid => {
+                let [r, g, b] = background;
+                RgbaImage::from_pixel(canvas_width, canvas_height, Rgba([r, g, b, 255]))
+            }
+            CanvasFill::Transparent => RgbaImage::new(canvas_width, canvas_height),
+            CanvasFill::Blur => {
+                // Blurring a small copy is much faster and looks the same
+                let small = img
+                    .resize_to_fill(
+                        (canvas_width / 8).max(1),
+                        (canvas_height / 8).max(1),
+                        FilterType::Triangle,
+                    )
+                    .fast_blur(4.0);
+                small
+                    .resize_exact(canvas_width, canvas_height, FilterType::Triangle)
+                    .to_rgba8()
+            }
+            CanvasFill::Edge => RgbaImage::from_fn(canvas_width, canvas_height, |cx, cy| {
+                let source_x = cx.saturating_sub(x).min(width - 1);
+                let source_y = cy.saturating_sub(y).min(height - 1);
+                *rgba.get_pixel(source_x, source_y)
+            }),
+        };
+        match self.fill {
+            // Blended, so transparent parts of the image show the fill
+            CanvasFill::Solid | CanvasFill::Blur => {
+                image::imageops::overlay(&mut canvas, &rgba, x as i64, y as i64)
+            }
+            CanvasFill::Transparent | CanvasFill::Edge => {
+                image::imageops::replace(&mut canvas, &rgba, x as i64, y as i64)
+            }
+        }
+        Ok(DynamicImage::ImageRgba8(canvas))
+    }
+}
+
+/// The part of `img` left after `options`' crop and trim, in source
+/// pixels, so frontends can show what the trim detected; `None` without
+/// a trim or when it finds no border
+pub fn trim_box(img: &DynamicImage, options: &ConversionOptions) -> Option<Crop> {
+    let trim = options.trim?;
+    let crop = match options.crop {
+        Some(crop) => crop.clamp(img.width(), img.height())?,
+        None => Crop::full(img.width(), img.height()),
+    };
+    let inner = trim.detect(
+        &img.crop_imm(crop.x, crop.y, crop.width, crop.height)
+            .to_rgba8(),
+    )?;
+    Some(Crop {
+        x: crop.x + inner.x,
+        y: crop.y + inner.y,
+        ..inner
+    })
+}
+
+/// Apply the operations in `options` to a decoded image
+pub fn apply(img: DynamicImage, options: &ConversionOptions) -> Result<DynamicImage> {
+    let mut img = img;
+    if let Some(crop) = &options.crop {
+        img = crop.apply(img)?;
+    }
+    if let Some(trim) = &options.trim {
+        img = trim.apply(img, &options.limits)?;
+    }
+    if !options.orientation.is_identity() {
+        img = options.orientation.apply(img);
+    }
+    if let Some(canvas) = &options.canvas {
+        img = canvas.apply(img, options.background, &options.limits)?;
+    }
+    // Last, so the mark's size is relative to the final image
+    if let Some(watermark) = &options.watermark {
+        img = watermark.apply(img, &options.limits)?;
+    }
+    Ok(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crop_geometry() {
+        let crop: Crop = "200x100+10+20".parse().unwrap();
+        assert_eq!(
+            crop,
+            Crop {
+                x: 10,
+                y: 20,
+                width: 200,
+                height: 100
+            }
+        );
+        assert_eq!(crop.to_string(), "200x100+10+20");
+        assert_eq!("64X64".parse::<Crop>().unwrap(), Crop::full(64, 64));
+        for invalid in ["", "200", "0x10", "10x10+1+2+3", "10x10+-1+2", "axb+1+2"] {
+            assert!(invalid.parse::<Crop>().is_err(), "{}", invalid);
+        }
+
+        // Partly outside: clamped to the image
+        assert_eq!(
+            crop.clamp(100, 100),
+            Some(Crop {
+                x: 10,
+                y: 20,
+                width: 90,
+                height: 80
+            })
+        );
+        assert_eq!(crop.clamp(10, 100), None);
+        assert!(Crop::full(50, 40).covers(50, 40));
+        assert!(!crop.covers(300, 300));
+
+        assert_eq!(
+            Crop::centered(400, 300, (1, 1)),
+            Crop {
+                x: 50,
+                y: 0,
+                width: 300,
+                height: 300
+            }
+        );
+        assert_eq!(
+            Crop::centered(400, 300, (9, 16)),
+            Crop {
+                x: 116,
+                y: 0,
+                width: 168,
+                height: 300
+            }
+        );
+        assert_eq!(
+            Crop::centered(400, 300, (16, 9)),
+            Crop {
+                x: 0,
+                y: 37,
+                width: 400,
+                height: 225
+            }
+        );
+    }
+
+    #[test]
+    fn test_apply_crop() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(40, 30, |x, y| {
+            image::Rgb([x as u8, y as u8, 0])
+        }));
+        let options = |crop| ConversionOptions {
+            crop: Some(crop),
+            ..Default::default()
+        };
+
+        let cropped = apply(img.clone(), &options("10x5+30+20".parse().unwrap())).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (10, 5));
+        assert_eq!(cropped.to_rgb8().get_pixel(0, 0).0, [30, 20, 0]);
+
+        // Overhanging crops keep what's inside
+        let cropped = apply(img.clone(), &options("20x20+30+20".parse().unwrap())).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (10, 10));
+
+        assert!(matches!(
+            apply(img, &options("5x5+40+0".parse().unwrap())),
+            Err(ConvertError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_orientation() {
+        // 3×2 image with a distinct pixel in the top left corner
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(3, 2, |x, y| {
+            if (x, y) == (0, 0) {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 255, 255])
+            }
+        }));
+        let red_at = |img: &DynamicImage| {
+            let rgba = img.to_rgba8();
+            let (x, y, _) = rgba
+                .enumerate_pixels()
+                .find(|(_, _, p)| p[0] == 255)
+                .unwrap();
+            (img.width(), img.height(), x, y)
+        };
+        let orient = |orientation: Orientation| {
+            let options = ConversionOptions {
+                orientation,
+                ..Default::default()
+            };
+            red_at(&apply(img.clone(), &options).unwrap())
+        };
+
+        assert_eq!(orient(Orientation::default()), (3, 2, 0, 0));
+        assert_eq!(orient(Orientation::default().turned(1)), (2, 3, 1, 0));
+        assert_eq!(orient(Orientation::default().turned(-1)), (2, 3, 0, 2));
+        assert_eq!(orient(Orientation::default().turned(6)), (3, 2, 2, 1));
+        assert_eq!(
+            orient(Orientation {
+                flip_horizontal: true,
+                ..Default::default()
+            }),
+            (3, 2, 2, 0)
+        );
+        // Quarter turn first, then the flip
+        assert_eq!(
+            orient(Orientation {
+                quarter_turns: 1,
+                flip_vertical: true,
+                ..Default::default()
+            }),
+            (2, 3, 1, 2)
+        );
+    }
+
+    #[test]
+    fn test_angled_rotation() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 100, Rgba([0, 128, 0, 255])));
+        let rotate = |auto_crop| {
+            let options = ConversionOptions {
+                orientation: Orientation {
+                    angle: 45.0,
+                    auto_crop,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            apply(img.clone(), &options).unwrap().to_rgba8()
+        };
+
+        // The canvas grows to the diagonal, with transparent corners
+        let rotated = rotate(false);
+        assert_eq!(rotated.dimensions(), (141, 141));
+        assert_eq!(rotated.get_pixel(0, 0)[3], 0);
+        assert_eq!(*rotated.get_pixel(70, 70), Rgba([0, 128, 0, 255]));
+
+        // Cropped to the inscribed square: side / √2, fully covered
+        let cropped = rotate(true);
+        assert!((68..=70).contains(&cropped.width()));
+        assert_eq!(cropped.width(), cropped.height());
+        assert!(cropped.pixels().all(|p| p[3] == 255 && p[1] == 128));
+    }
+
+    #[test]
+    fn test_trim() {
+        // A 6x4 dark block on an off-white 20x10 margin
+        let img = RgbaImage::from_fn(20, 10, |x, y| {
+            if (5..11).contains(&x) && (3..7).contains(&y) {
+                Rgba([20, 20, 20, 255])
+            } else {
+                Rgba([250, 252, 255 - (x % 3) as u8, 255])
+            }
+        });
+        let trim = Trim::default();
+        let content = Crop {
+            x: 5,
+            y: 3,
+            width: 6,
+            height: 4,
+        };
+        assert_eq!(trim.detect(&img), Some(content));
+        // Too strict to see the noisy margin as uniform
+        assert_eq!(
+            Trim {
+                tolerance: 0,
+                ..trim
+            }
+            .detect(&img)
+            .map(|crop| crop.height),
+            Some(10)
+        );
+        // Nothing but border, or no border at all
+        assert_eq!(trim.detect(&RgbaImage::new(8, 8)), None);
+        assert_eq!(
+            trim.detect(&RgbaImage::from_pixel(1, 1, Rgba([1; 4]))),
+            None
+        );
+
+        let limits = DecodeLimits::default();
+        let padded = Trim { padding: 3, ..trim }
+            .apply(DynamicImage::ImageRgba8(img.clone()), &limits)
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(padded.dimensions(), (12, 10));
+        assert_eq!(*padded.get_pixel(0, 0), *img.get_pixel(0, 0));
+        assert_eq!(*padded.get_pixel(3, 3), Rgba([20, 20, 20, 255]));
+
+        // Transparent margins, padded back with transparency
+        let img = RgbaImage::from_fn(10, 10, |x, y| {
+            Rgba([255, 0, 0, if x >= 2 && y < 5 { 255 } else { 3 }])
+        });
+        let trim = Trim {
+            mode: TrimMode::Transparency,
+            padding: 1,
+            ..trim
+        };
+        let trimmed = trim
+            .apply(DynamicImage::ImageRgba8(img.clone()), &limits)
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(trimmed.dimensions(), (10, 7));
+        assert_eq!(trimmed.get_pixel(0, 0)[3], 0);
+        assert_eq!(trimmed.get_pixel(1, 1)[3], 255);
+
+        // Padding past the limits, or past u32, fails before allocating
+        for padding in [40_000, u32::MAX / 2 + 1] {
+            assert!(matches!(
+                Trim { padding, ..trim }.apply(DynamicImage::ImageRgba8(img.clone()), &limits),
+                Err(ConvertError::LimitExceeded(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_canvas_geometry() {
+        let square: CanvasSize = "1:1".parse().unwrap();
+        assert_eq!(
+            square,
+            CanvasSize::Ratio {
+                width: 1,
+                height: 1
+            }
+        );
+        assert_eq!(square.for_image(300, 200).ok(), Some((300, 300)));
+        let portrait: CanvasSize = "4:5".parse().unwrap();
+        assert_eq!(portrait.for_image(400, 400).ok(), Some((400, 500)));
+        assert_eq!(portrait.for_image(400, 1000).ok(), Some((800, 1000)));
+        // Rounded up, so the image always fits
+        assert_eq!(portrait.for_image(3, 3).ok(), Some((3, 4)));
+
+        let exact: CanvasSize = "1080x1350".parse().unwrap();
+        assert_eq!(exact.to_string(), "1080x1350");
+        assert_eq!(exact.for_image(1080, 720).ok(), Some((1080, 1350)));
+        assert_eq!(exact.for_image(1200, 720).ok(), None);
+        for invalid in ["", "16", "0:1", "4:x", "10x"] {
+            assert!(invalid.parse::<CanvasSize>().is_err(), "{}", invalid);
+        }
+
+        assert_eq!(Gravity::NorthWest.offset(10, 20), (0, 0));
+        assert_eq!(Gravity::Center.offset(10, 20), (5, 10));
+        assert_eq!(Gravity::East.offset(10, 20), (10, 10));
+        assert_eq!(Gravity::South.offset(10, 20), (5, 20));
+    }
+
+    #[test]
+    fn test_apply_canvas() {
+        let red = Rgba([255, 0, 0, 255]);
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(4, 2, |x, _| {
+            if x == 0 {
+                Rgba([0, 0, 255, 255])
+            } else {
+                red
+            }
+        }));
+        let options = |fill, gravity| ConversionOptions {
+            canvas: Some(Canvas {
+                size: CanvasSize::Ratio {
+                    width: 1,
+                    height: 1,
+                },
+                gravity,
+                fill,
+            }),
+            background: [0, 255, 0],
+            ..Default::default()
+        };
+        let canvas = |fill, gravity| {
+            apply(img.clone(), &options(fill, gravity))
+                .unwrap()
+                .to_rgba8()
+        };
+
+        // Letterboxed to 4x4, centred: one row above and below
+        let solid = canvas(CanvasFill::Solid, Gravity::Center);
+        assert_eq!(solid.dimensions(), (4, 4));
+        assert_eq!(*solid.get_pixel(0, 0), Rgba([0, 255, 0, 255]));
+        assert_eq!(*solid.get_pixel(1, 1), red);
+        assert_eq!(*solid.get_pixel(1, 3), Rgba([0, 255, 0, 255]));
+
+        let top = canvas(CanvasFill::Transparent, Gravity::North);
+        assert_eq!(*top.get_pixel(1, 0), red);
+        assert_eq!(top.get_pixel(1, 2)[3], 0);
+
+        let edge = canvas(CanvasFill::Edge, Gravity::South);
+        assert_eq!(*edge.get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+        assert_eq!(*edge.get_pixel(3, 0), red);
+
+        let blur = canvas(CanvasFill::Blur, Gravity::Center);
+        assert_eq!(blur.dimensions(), (4, 4));
+        assert!(blur.pixels().all(|p| p[3] == 255));
+
+        // Already the right shape: unchanged
+        let square = DynamicImage::ImageRgb8(image::RgbImage::new(5, 5));
+        let same = apply(square, &options(CanvasFill::Solid, Gravity::Center)).unwrap();
+        assert!(matches!(same, DynamicImage::ImageRgb8(_)));
+
+        let too_small = ConversionOptions {
+            canvas: Some(Canvas {
+                size: CanvasSize::Exact {
+                    width: 3,
+                    height: 3,
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(matches!(
+            apply(img.clone(), &too_small),
+            Err(ConvertError::InvalidOperation(_))
+        ));
+
+        // An extreme ratio fails before the canvas is allocated
+        let extreme = ConversionOptions {
+            canvas: Some(Canvas {
+                size: CanvasSize::Ratio {
+                    width: 1,
+                    height: 30000,
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(matches!(
+            apply(img.clone(), &extreme),
+            Err(ConvertError::LimitExceeded(_))
+        ));
+        let widest = CanvasSize::Ratio {
+            width: u32::MAX,
+            height: 1,
+        };
+        assert!(matches!(
+            widest.for_image(2, 2),
+            Err(ConvertError::LimitExceeded(_))
+        ));
+    }
+}
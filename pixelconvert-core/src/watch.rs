@@ -0,0 +1,300 @@
+//! Watch-folder mode: converting images as they appear in a folder.
+//!
+//! The frontends own the file monitoring (`gio::FileMonitor` in the GUI,
+//! inotify in the CLI) and feed every created or changed path to a
+//! `Debouncer`. Files it reports as settled are turned into incremental
+//! `BatchJob`s by the `WatchFolder` and run through the batch processor,
+//! and each outcome is appended to the `WatchHistory` log.
+
+use crate::batch::{output_path_for, BatchJob, BatchProgress};
+use crate::converter::{ConversionOptions, ImageConverter};
+use crate::error::{ConvertError, IoContext, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a file must stay unchanged before it's converted
+pub const DEFAULT_SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// Folders to watch and what to do with the images dropped into them
+#[derive(Debug, Clone)]
+pub struct WatchFolder {
+    pub folders: Vec<PathBuf>,
+    /// Where outputs go; `None` writes them next to their source
+    pub output_dir: Option<PathBuf>,
+    pub options: ConversionOptions,
+}
+
+impl WatchFolder {
+    /// The job for a settled file, or `None` if it shouldn't be converted:
+    /// hidden files (usually partial downloads or copies), non-images, and
+    /// files that are their own output, such as outputs written back into
+    /// a watched folder
+    pub fn job_for(&self, path: &Path) -> Option<BatchJob> {
+        let hidden = path
+            .file_name()
+            .is_none_or(|name| name.to_string_lossy().starts_with('.'));
+        if hidden || !path.is_file() {
+            return None;
+        }
+        ImageConverter::detect_format(path).ok().flatten()?;
+
+        let output_path = output_path_for(path, self.output_dir.as_deref(), self.options.format);
+        (output_path != path).then(|| BatchJob {
+            input_path: path.to_path_buf(),
+            output_path,
+            options: self.options.clone(),
+            // Only files whose output is missing or stale are converted
+            incremental: true,
+        })
+    }
+
+    /// Jobs for the images already in the watched folders, so files added
+    /// while nothing was watching are caught up on
+    pub fn existing_jobs(&self) -> Result<Vec<BatchJob>> {
+        let mut jobs = Vec::new();
+        for folder in &self.folders {
+            for entry in std::fs::read_dir(folder).io_context("Failed to read watched folder")? {
+                let entry = entry.io_context("Failed to read watched folder")?;
+                jobs.extend(self.job_for(&entry.path()));
+            }
+        }
+        jobs.sort_by(|a, b| a.input_path.cmp(&b.input_path));
+        Ok(jobs)
+    }
+}
+
+/// Holds changed files back until their size and modification time stop
+/// changing, so files still being written or copied aren't converted half-done
+#[derive(Debug)]
+pub struct Debouncer {
+    settle_time: Duration,
+    pending: HashMap<PathBuf, Snapshot>,
+}
+
+#[derive(Debug)]
+struct Snapshot {
+    len: u64,
+    modified: Option<SystemTime>,
+    unchanged_since: Instant,
+}
+
+impl Debouncer {
+    pub fn new(settle_time: Duration) -> Self {
+        Self {
+            settle_time,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Note that `path` was created or changed
+    pub fn notice(&mut self, path: PathBuf) {
+        let (len, modified) = stat(&path).unwrap_or_default();
+        self.pending.insert(
+            path,
+            Snapshot {
+                len,
+                modified,
+                unchanged_since: Instant::now(),
+            },
+        );
+    }
+
+    /// Files that haven't changed for the settle time. Files that were
+    /// deleted are dropped. Call this periodically.
+    pub fn settled(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        let mut settled = Vec::new();
+        self.pending.retain(|path, snapshot| {
+            let Some((len, modified)) = stat(path) else {
+                return false;
+            };
+            if (len, modified) != (snapshot.len, snapshot.modified) {
+                *snapshot = Snapshot {
+                    len,
+                    modified,
+                    unchanged_since: now,
+                };
+                return true;
+            }
+            if now.duration_since(snapshot.unchanged_since) < self.settle_time {
+                return true;
+            }
+            settled.push(path.clone());
+            false
+        });
+        settled.sort();
+        settled
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+fn stat(path: &Path) -> Option<(u64, Option<SystemTime>)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()))
+}
+
+/// What happened to a file in a watched folder
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchOutcome {
+    Converted { output_bytes: u64 },
+    /// The output was already up to date
+    Unchanged,
+    Failed(ConvertError),
+}
+
+/// One entry in the watch history log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub time: SystemTime,
+    pub input_path: PathBuf,
+    pub output_path: PathBuf,
+    pub outcome: WatchOutcome,
+}
+
+impl HistoryEntry {
+    /// The entry for a finished job from a batch of `jobs`, if `progress`
+    /// reports one
+    pub fn from_progress(jobs: &[BatchJob], progress: &BatchProgress) -> Option<Self> {
+        let (index, outcome) = match progress {
+            BatchProgress::Completed { index, stats, .. } => (
+                *index,
+                WatchOutcome::Converted {
+                    output_bytes: stats.output_bytes,
+                },
+            ),
+            BatchProgress::Skipped { index, .. } => (*index, WatchOutcome::Unchanged),
+            BatchProgress::Failed { index, error, .. } => {
+                (*index, WatchOutcome::Failed(error.clone()))
+            }
+            _ => return None,
+        };
+        let job = jobs.get(index)?;
+        Some(Self {
+            time: SystemTime::now(),
+            input_path: job.input_path.clone(),
+            output_path: job.output_path.clone(),
+            outcome,
+        })
+    }
+}
+
+/// Append-only JSON-lines log of what watch mode converted
+#[derive(Debug, Clone)]
+pub struct WatchHistory {
+    path: PathBuf,
+}
+
+impl WatchHistory {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// `$XDG_DATA_HOME/pixelconvert/watch-history.jsonl`, falling back to
+    /// `~/.local/share`
+    pub fn default_path() -> Option<PathBuf> {
+        Some(crate::dirs::data_dir()?.join("watch-history.jsonl"))
+    }
+
+    pub fn append(&self, entry: &HistoryEntry) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).io_context("Failed to create history directory")?;
+        }
+        let mut line = serde_json::to_vec(entry)
+            .map_err(|e| ConvertError::io("Failed to write watch history", e.into()))?;
+        line.push(b'\n');
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(&line))
+            .io_context("Failed to write watch history")
+    }
+
+    /// The last `count` entries, oldest first. Unreadable lines are skipped.
+    pub fn recent(&self, count: usize) -> Result<Vec<HistoryEntry>> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(ConvertError::io("Failed to open watch history", e)),
+        };
+
+        let mut entries = std::collections::VecDeque::with_capacity(count);
+        for line in BufReader::new(file).lines() {
+            let line = line.io_context("Failed to read watch history")?;
+            let Ok(entry) = serde_json::from_str(&line) else {
+                continue;
+            };
+            if entries.len() == count {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+        Ok(entries.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::SupportedFormat;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_watch_folder() {
+        let dir = TempDir::new("watch");
+
+        let png = dir.join("photo.png");
+        image::RgbImage::new(4, 4).save(&png).unwrap();
+        std::fs::write(dir.join(".photo.png.part"), b"partial").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"not an image").unwrap();
+        image::RgbImage::new(4, 4)
+            .save_with_format(dir.join("photo.webp"), image::ImageFormat::WebP)
+            .unwrap();
+
+        // Outputs written back into the watched folder aren't converted again
+        let watch = WatchFolder {
+            folders: vec![dir.to_path_buf()],
+            output_dir: None,
+            options: ConversionOptions {
+                format: SupportedFormat::WebP,
+                ..Default::default()
+            },
+        };
+        let jobs = watch.existing_jobs().unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].input_path, png);
+        assert_eq!(jobs[0].output_path, dir.join("photo.webp"));
+        assert!(jobs[0].incremental);
+
+        // A file is held back until it stops changing
+        let mut debouncer = Debouncer::new(Duration::from_millis(50));
+        debouncer.notice(png.clone());
+        assert!(debouncer.settled().is_empty());
+        std::thread::sleep(Duration::from_millis(60));
+        std::fs::write(&png, b"still being written").unwrap();
+        assert!(debouncer.settled().is_empty());
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(debouncer.settled(), std::slice::from_ref(&png));
+        assert!(debouncer.is_empty());
+
+        let history = WatchHistory::new(dir.join("history.jsonl"));
+        let progress = BatchProgress::Failed {
+            index: 0,
+            file: "photo.png".to_string(),
+            error: ConvertError::Decode("truncated".to_string()),
+            elapsed: Duration::ZERO,
+        };
+        let entry = HistoryEntry::from_progress(&jobs, &progress).unwrap();
+        for _ in 0..3 {
+            history.append(&entry).unwrap();
+        }
+        assert_eq!(history.recent(2).unwrap(), [entry.clone(), entry]);
+    }
+}
@@ -0,0 +1,427 @@
+use crate::converter::ImageConverter;
+use crate::error::{ConvertError, IoContext, Result};
+use crate::limits::DecodeLimits;
+use crate::transform::Gravity;
+use image::{imageops::FilterType, DynamicImage, Rgba, RgbaImage};
+use resvg::{tiny_skia, usvg};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+/// The last logo decoded, so a batch reads and decodes it once rather than
+/// for every image. Its size and modification time are part of the key, so
+/// an edited logo is picked up.
+static LOADED: Mutex<Option<(LogoKey, Arc<Logo>)>> = Mutex::new(None);
+
+#[derive(PartialEq)]
+struct LogoKey {
+    path: PathBuf,
+    len: u64,
+    modified: Option<SystemTime>,
+    limits: DecodeLimits,
+}
+
+/// A decoded logo, before it is scaled to an image
+enum Logo {
+    Raster(DynamicImage),
+    /// Rendered at each mark's final size, so it stays sharp
+    Svg(Box<usvg::Tree>),
+}
+
+/// How the watermark's colours combine with the image under it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    /// Darkens: white parts of the mark disappear
+    Multiply,
+    /// Lightens: black parts of the mark disappear
+    Screen,
+    /// Multiply on dark areas, screen on light ones
+    Overlay,
+}
+
+impl BlendMode {
+    pub fn display_name(&self) -> &str {
+        match self {
+            Self::Normal => "Normal",
+            Self::Multiply => "Multiply",
+            Self::Screen => "Screen",
+            Self::Overlay => "Overlay",
+        }
+    }
+
+    pub fn all() -> Vec<Self> {
+        vec![Self::Normal, Self::Multiply, Self::Screen, Self::Overlay]
+    }
+
+    /// Blend one channel of the mark over the image, both 0-1
+    fn channel(&self, base: f32, mark: f32) -> f32 {
+        match self {
+            Self::Normal => mark,
+            Self::Multiply => base * mark,
+            Self::Screen => 1.0 - (1.0 - base) * (1.0 - mark),
+            Self::Overlay if base < 0.5 => 2.0 * base * mark,
+            Self::Overlay => 1.0 - 2.0 * (1.0 - base) * (1.0 - mark),
+        }
+    }
+}
+
+/// A logo composited onto every output. Sizes and offsets are fractions
+/// of the image width, so the mark looks the same on images of any size.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Watermark {
+    /// PNG, SVG or any other image the converter reads. SVG text is only
+    /// drawn once converted to paths.
+    pub path: PathBuf,
+    pub gravity: Gravity,
+    /// Distance from the anchored edges (or, on a centred axis, a shift
+    /// right or down)
+    pub offset_x: f32,
+    pub offset_y: f32,
+    /// Width of the mark
+    pub scale: f32,
+    /// 0 (invisible) to 1
+    pub opacity: f32,
+    /// Repeat the mark across the whole image, lined up with the anchored one
+    pub tile: bool,
+    pub blend: BlendMode,
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::new(),
+            gravity: Gravity::SouthEast,
+            offset_x: 0.02,
+            offset_y: 0.02,
+            scale: 0.2,
+            opacity: 0.5,
+            tile: false,
+            blend: BlendMode::Normal,
+        }
+    }
+}
+
+impl Watermark {
+    /// Composite the mark onto `img`. The logo is decoded under `limits`.
+    pub(crate) fn apply(&self, img: DynamicImage, limits: &DecodeLimits) -> Result<DynamicImage> {
+        let (width, height) = (img.width(), img.height());
+        let logo = self.logo(limits)?;
+        let mark = self.render(&logo, (width, height), limits)?;
+        let mut canvas = img.to_rgba8();
+
+        let (x, y) = self.position(width, height, mark.dimensions());
+        if self.tile {
+            // Half a mark between tiles, with one tile where the single mark goes
+            let step_x = mark.width() as i64 * 3 / 2;
+            let step_y = mark.height() as i64 * 3 / 2;
+            let mut tile_y = y.rem_euclid(step_y) - step_y;
+            while tile_y < height as i64 {
+                let mut tile_x = x.rem_euclid(step_x) - step_x;
+                while tile_x < width as i64 {
+                    self.draw(&mut canvas, &mark, tile_x, tile_y);
+                    tile_x += step_x;
+                }
+                tile_y += step_y;
+            }
+        } else {
+            self.draw(&mut canvas, &mark, x, y);
+        }
+        Ok(DynamicImage::ImageRgba8(canvas))
+    }
+
+    /// Top left corner of the anchored mark on a `width`×`height` image
+    fn position(
+        &self,
+        width: u32,
+        height: u32,
+        (mark_width, mark_height): (u32, u32),
+    ) -> (i64, i64) {
+        let (column, row) = self.gravity.thirds();
+        let place = |free: i64, third: u32, offset: f32| {
+            let offset = (offset * width as f32).round() as i64;
+            match third {
+                0 => offset,
+                1 => free / 2 + offset,
+                _ => free - offset,
+            }
+        };
+        (
+            place(width as i64 - mark_width as i64, column, self.offset_x),
+            place(height as i64 - mark_height as i64, row, self.offset_y),
+        )
+    }
+
+    /// The decoded logo, from `LOADED` if this file was the last one used
+    fn logo(&self, limits: &DecodeLimits) -> Result<Arc<Logo>> {
+        let metadata = std::fs::metadata(&self.path).io_context("Failed to read the watermark")?;
+        let key = LogoKey {
+            path: self.path.clone(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            limits: limits.clone(),
+        };
+
+        // Held while decoding, so workers starting together decode it once.
+        // A codec panic poisons it, but the cached logo is still whole.
+        let mut loaded = LOADED.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((loaded_key, logo)) = loaded.as_ref() {
+            if *loaded_key == key {
+                return Ok(Arc::clone(logo));
+            }
+        }
+        let logo = Arc::new(self.decode(limits)?);
+        *loaded = Some((key, Arc::clone(&logo)));
+        Ok(logo)
+    }
+
+    fn decode(&self, limits: &DecodeLimits) -> Result<Logo> {
+        let data = std::fs::read(&self.path).io_context("Failed to read the watermark")?;
+        if is_svg(&self.path) {
+            let tree = usvg::Tree::from_data(&data, &usvg::Options::default())
+                .map_err(|e| self.invalid(&e))?;
+            let size = tree.size();
+            limits.check_size((size.width().ceil() as u32, size.height().ceil() as u32))?;
+            Ok(Logo::Svg(Box::new(tree)))
+        } else {
+            ImageConverter::decode_bytes_with_limits(&data, limits)
+                .map(Logo::Raster)
+                .map_err(|e| self.invalid(&e))
+        }
+    }
+
+    /// The mark, `scale` of the image width but shrunk to fit inside the
+    /// `width`×`height` image
+    fn render(
+        &self,
+        logo: &Logo,
+        (width, height): (u32, u32),
+        limits: &DecodeLimits,
+    ) -> Result<RgbaImage> {
+        let (logo_width, logo_height) = match logo {
+            Logo::Raster(logo) => (logo.width() as f32, logo.height() as f32),
+            Logo::Svg(tree) => (tree.size().width(), tree.size().height()),
+        };
+        let wanted_width = ((width as f32 * self.scale).round() as u32).clamp(1, width);
+        let fit = (wanted_width as f32 / logo_width).min(height as f32 / logo_height);
+        let mark_width = ((logo_width * fit).round() as u32).clamp(1, width);
+        let mark_height = ((logo_height * fit).round() as u32).clamp(1, height);
+        limits.check_size((mark_width, mark_height))?;
+
+        let tree = match logo {
+            Logo::Raster(logo) => {
+                return Ok(logo
+                    .resize_exact(mark_width, mark_height, FilterType::Lanczos3)
+                    .to_rgba8());
+            }
+            Logo::Svg(tree) => tree,
+        };
+
+        let mut pixmap = tiny_skia::Pixmap::new(mark_width, mark_height)
+            .ok_or_else(|| self.invalid(&format!("{}×{} is too large", mark_width, mark_height)))?;
+        resvg::render(
+            tree,
+            tiny_skia::Transform::from_scale(
+                mark_width as f32 / logo_width,
+                mark_height as f32 / logo_height,
+            ),
+            &mut pixmap.as_mut(),
+        );
+        let pixels = pixmap.pixels();
+        Ok(RgbaImage::from_fn(mark_width, mark_height, |x, y| {
+            let color = pixels[(y * mark_width + x) as usize].demultiply();
+            Rgba([color.red(), color.green(), color.blue(), color.alpha()])
+        }))
+    }
+
+    fn invalid(&self, e: &dyn std::fmt::Display) -> ConvertError {
+        ConvertError::InvalidOperation(format!(
+            "the watermark {} can't be read: {}",
+            self.path.display(),
+            e
+        ))
+    }
+
+    /// Composite `mark` onto `canvas` with its top left corner at `(x, y)`
+    fn draw(&self, canvas: &mut RgbaImage, mark: &RgbaImage, x: i64, y: i64) {
+        let opacity = self.opacity.clamp(0.0, 1.0);
+        for (mark_x, mark_y, pixel) in mark.enumerate_pixels() {
+            let (canvas_x, canvas_y) = (x + mark_x as i64, y + mark_y as i64);
+            if canvas_x < 0
+                || canvas_y < 0
+                || canvas_x >= canvas.width() as i64
+                || canvas_y >= canvas.height() as i64
+            {
+                continue;
+            }
+            let alpha = pixel[3] as f32 / 255.0 * opacity;
+            if alpha <= 0.0 {
+                continue;
+            }
+
+            let base = canvas.get_pixel_mut(canvas_x as u32, canvas_y as u32);
+            let base_alpha = base[3] as f32 / 255.0;
+            let out_alpha = alpha + base_alpha * (1.0 - alpha);
+            for c in 0..3 {
+                let (b, m) = (base[c] as f32 / 255.0, pixel[c] as f32 / 255.0);
+                // Where the image is transparent there's nothing to blend with
+                let blended = base_alpha * self.blend.channel(b, m) + (1.0 - base_alpha) * m;
+                let value = (blended * alpha + b * base_alpha * (1.0 - alpha)) / out_alpha;
+                base[c] = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+            base[3] = (out_alpha * 255.0).round() as u8;
+        }
+    }
+}
+
+fn is_svg(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg") || ext.eq_ignore_ascii_case("svgz"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_watermark() {
+        let dir = TempDir::new("watermark");
+        let logo = dir.join("logo.png");
+        RgbaImage::from_pixel(10, 5, Rgba([255, 0, 0, 255]))
+            .save(&logo)
+            .unwrap();
+
+        let limits = DecodeLimits::default();
+        let white = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 50, Rgba([255; 4])));
+        let watermark = Watermark {
+            path: logo.clone(),
+            offset_x: 0.0,
+            offset_y: 0.0,
+            opacity: 1.0,
+            ..Default::default()
+        };
+
+        // 20% of the width, in the bottom right corner
+        let marked = watermark.apply(white.clone(), &limits).unwrap().to_rgba8();
+        assert_eq!(*marked.get_pixel(99, 49), Rgba([255, 0, 0, 255]));
+        assert_eq!(*marked.get_pixel(80, 40), Rgba([255, 0, 0, 255]));
+        assert_eq!(*marked.get_pixel(79, 49), Rgba([255; 4]));
+        assert_eq!(*marked.get_pixel(99, 39), Rgba([255; 4]));
+
+        // Offsets are a fraction of the width too: 5 px in from the top left
+        let offset = Watermark {
+            gravity: Gravity::NorthWest,
+            offset_x: 0.05,
+            offset_y: 0.05,
+            opacity: 0.5,
+            ..watermark.clone()
+        };
+        let marked = offset.apply(white.clone(), &limits).unwrap().to_rgba8();
+        assert_eq!(*marked.get_pixel(4, 4), Rgba([255; 4]));
+        assert_eq!(*marked.get_pixel(5, 5), Rgba([255, 128, 128, 255]));
+
+        // Multiplying red over white and black
+        let half = DynamicImage::ImageRgba8(RgbaImage::from_fn(100, 50, |x, _| {
+            Rgba(if x < 90 { [0, 0, 0, 255] } else { [255; 4] })
+        }));
+        let multiply = Watermark {
+            blend: BlendMode::Multiply,
+            ..watermark.clone()
+        };
+        let marked = multiply.apply(half, &limits).unwrap().to_rgba8();
+        assert_eq!(*marked.get_pixel(85, 45), Rgba([0, 0, 0, 255]));
+        assert_eq!(*marked.get_pixel(95, 45), Rgba([255, 0, 0, 255]));
+
+        // Tiled: repeated every 30 px across, lined up with the corner mark
+        let tiled = Watermark {
+            tile: true,
+            ..watermark.clone()
+        };
+        let marked = tiled.apply(white.clone(), &limits).unwrap().to_rgba8();
+        assert_eq!(*marked.get_pixel(99, 49), Rgba([255, 0, 0, 255]));
+        assert_eq!(*marked.get_pixel(50, 49), Rgba([255, 0, 0, 255]));
+        assert_eq!(*marked.get_pixel(20, 49), Rgba([255, 0, 0, 255]));
+        assert_eq!(*marked.get_pixel(70, 49), Rgba([255; 4]));
+        assert_eq!(*marked.get_pixel(99, 25), Rgba([255, 0, 0, 255]));
+
+        // The logo is decoded under the conversion's limits...
+        let tight = DecodeLimits {
+            max_width: 5,
+            ..DecodeLimits::default()
+        };
+        assert!(matches!(
+            watermark.apply(white.clone(), &tight),
+            Err(ConvertError::InvalidOperation(_))
+        ));
+
+        // ...and decoded again once it's edited
+        RgbaImage::from_pixel(20, 10, Rgba([0, 255, 0, 255]))
+            .save(&logo)
+            .unwrap();
+        let marked = watermark.apply(white.clone(), &limits).unwrap().to_rgba8();
+        assert_eq!(*marked.get_pixel(99, 49), Rgba([0, 255, 0, 255]));
+
+        let svg = dir.join("logo.svg");
+        std::fs::write(
+            &svg,
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10">"#,
+                r#"<rect width="20" height="10" fill="blue"/></svg>"#
+            ),
+        )
+        .unwrap();
+        let marked = Watermark {
+            path: svg,
+            ..watermark.clone()
+        }
+        .apply(white.clone(), &limits)
+        .unwrap()
+        .to_rgba8();
+        assert_eq!(*marked.get_pixel(90, 47), Rgba([0, 0, 255, 255]));
+        assert_eq!(*marked.get_pixel(90, 39), Rgba([255; 4]));
+
+        // A tall logo at full width is shrunk to the image's height
+        let tall = dir.join("tall.svg");
+        std::fs::write(
+            &tall,
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="5000">"#,
+                r#"<rect width="1" height="5000" fill="blue"/></svg>"#
+            ),
+        )
+        .unwrap();
+        let tall = Watermark {
+            path: tall,
+            scale: 1.0,
+            ..watermark.clone()
+        };
+        let marked = tall.apply(white.clone(), &limits).unwrap().to_rgba8();
+        assert_eq!(marked.dimensions(), (100, 50));
+        assert_eq!(*marked.get_pixel(99, 0), Rgba([0, 0, 255, 255]));
+        assert_eq!(*marked.get_pixel(98, 0), Rgba([255; 4]));
+        // Its intrinsic size is checked against the limits too
+        let short = DecodeLimits {
+            max_height: 4000,
+            ..DecodeLimits::default()
+        };
+        assert!(matches!(
+            tall.apply(white.clone(), &short),
+            Err(ConvertError::LimitExceeded(_))
+        ));
+
+        let missing = Watermark {
+            path: dir.join("missing.png"),
+            ..watermark
+        };
+        assert!(matches!(
+            missing.apply(white, &limits),
+            Err(ConvertError::Io { .. })
+        ));
+    }
+}
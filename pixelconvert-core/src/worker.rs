@@ -0,0 +1,257 @@
+//! Running conversions in child processes, so a crash or hang in a codec
+//! fails one file instead of taking down the whole batch.
+//!
+//! The parent starts the worker command, writes one JSON `WorkerRequest` to
+//! its stdin and reads one JSON result from its stdout. Binaries that can act
+//! as workers check `is_worker_invocation()` at the top of `main` and call
+//! `serve()`.
+
+use crate::converter::{ConversionOptions, ConversionStats, ImageConverter};
+use crate::error::{ConvertError, IoContext, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Argument that makes a PixelConvert binary serve one job and exit
+pub const WORKER_ARG: &str = "--pixelconvert-worker";
+
+/// How often a running worker is checked for exit or timeout
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The program to start for each isolated job
+#[derive(Debug, Clone)]
+pub struct WorkerCommand {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+}
+
+impl WorkerCommand {
+    /// The current executable in worker mode
+    pub fn current_exe() -> Result<Self> {
+        Ok(Self {
+            program: std::env::current_exe().io_context("Failed to locate worker executable")?,
+            args: vec![WORKER_ARG.to_string()],
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WorkerRequest {
+    input_path: PathBuf,
+    output_path: PathBuf,
+    options: ConversionOptions,
+}
+
+/// Whether this process was started as a worker
+pub fn is_worker_invocation() -> bool {
+    std::env::args().nth(1).as_deref() == Some(WORKER_ARG)
+}
+
+/// Serve a single request from stdin, writing the result to stdout.
+/// Returns the process exit code.
+pub fn serve() -> u8 {
+    let mut request = Vec::new();
+    if let Err(e) = std::io::stdin().read_to_end(&mut request) {
+        eprintln!("pixelconvert worker: failed to read request: {}", e);
+        return 1;
+    }
+    let request: WorkerRequest = match serde_json::from_slice(&request) {
+        Ok(request) => request,
+        Err(e) => {
+            eprintln!("pixelconvert worker: invalid request: {}", e);
+            return 1;
+        }
+    };
+
+    let converter = ImageConverter::new(request.options);
+    let result = converter.convert(&request.input_path, &request.output_path);
+
+    let mut stdout = std::io::stdout().lock();
+    let written = serde_json::to_writer(&mut stdout, &result)
+        .map_err(std::io::Error::from)
+        .and_then(|()| stdout.flush());
+    match written {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("pixelconvert worker: failed to write result: {}", e);
+            1
+        }
+    }
+}
+
+/// Convert one file in a child process, killing it after `timeout`.
+/// The worker writes next to the output and the file is only moved into
+/// place once it succeeds, so a killed or crashed worker never leaves a
+/// truncated output behind for incremental runs to mistake for a finished one.
+pub fn convert_isolated(
+    worker: &WorkerCommand,
+    input_path: &Path,
+    output_path: &Path,
+    options: &ConversionOptions,
+    timeout: Option<Duration>,
+) -> Result<ConversionStats> {
+    let partial = partial_path(output_path);
+    match run_worker(worker, input_path, &partial, options, timeout) {
+        Ok(stats) => {
+            std::fs::rename(&partial, output_path).io_context("Failed to write output file")?;
+            Ok(stats)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+/// Where the worker writes `output_path` until it has finished
+fn partial_path(output_path: &Path) -> PathBuf {
+    let mut name = output_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    output_path.with_file_name(name)
+}
+
+fn run_worker(
+    worker: &WorkerCommand,
+    input_path: &Path,
+    output_path: &Path,
+    options: &ConversionOptions,
+    timeout: Option<Duration>,
+) -> Result<ConversionStats> {
+    let request = serde_json::to_vec(&WorkerRequest {
+        input_path: input_path.to_path_buf(),
+        output_path: output_path.to_path_buf(),
+        options: options.clone(),
+    })
+    .map_err(|e| ConvertError::io("Failed to send job to worker", e.into()))?;
+
+    let mut child = Command::new(&worker.program)
+        .args(&worker.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .io_context("Failed to start worker")?;
+
+    // Feed the request and collect the response on their own threads, so a
+    // worker that stops reading or writing can't block the timeout
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    std::thread::spawn(move || stdin.write_all(&request));
+    let response = std::thread::spawn(move || {
+        let mut response = Vec::new();
+        stdout.read_to_end(&mut response).map(|_| response)
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().io_context("Failed to wait for worker")? {
+            break status;
+        }
+        if let Some(timeout) = timeout.filter(|&timeout| started.elapsed() >= timeout) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(ConvertError::TimedOut(timeout));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    if !status.success() {
+        return Err(ConvertError::Crashed(describe_exit(status)));
+    }
+
+    let response = response
+        .join()
+        .map_err(|_| ConvertError::Crashed("lost the worker's output".to_string()))?
+        .io_context("Failed to read worker result")?;
+    serde_json::from_slice::<Result<ConversionStats>>(&response)
+        .map_err(|e| ConvertError::Crashed(format!("worker returned an invalid result ({})", e)))?
+}
+
+fn describe_exit(status: std::process::ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("worker killed by signal {}", signal);
+        }
+    }
+    match status.code() {
+        // Rust's exit code for a panic that unwinds out of main
+        Some(101) => "worker panicked".to_string(),
+        Some(code) => format!("worker exited with status {}", code),
+        None => "worker exited abnormally".to_string(),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn shell(script: &str) -> WorkerCommand {
+        WorkerCommand {
+            program: PathBuf::from("sh"),
+            args: vec!["-c".to_string(), script.to_string()],
+        }
+    }
+
+    #[test]
+    fn test_isolated_failures() {
+        let options = ConversionOptions::default();
+        let (input, output) = (Path::new("in.png"), Path::new("out.png"));
+
+        let timeout = Duration::from_millis(100);
+        let result = convert_isolated(&shell("sleep 5"), input, output, &options, Some(timeout));
+        assert_eq!(result.unwrap_err(), ConvertError::TimedOut(timeout));
+
+        let result = convert_isolated(
+            &shell("cat >/dev/null; exit 3"),
+            input,
+            output,
+            &options,
+            None,
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            ConvertError::Crashed("worker exited with status 3".to_string())
+        );
+
+        let result = convert_isolated(&shell("kill -9 $$"), input, output, &options, None);
+        assert_eq!(
+            result.unwrap_err(),
+            ConvertError::Crashed("worker killed by signal 9".to_string())
+        );
+
+        let result = convert_isolated(
+            &shell("cat >/dev/null; echo nonsense"),
+            input,
+            output,
+            &options,
+            None,
+        );
+        assert!(matches!(result, Err(ConvertError::Crashed(_))));
+    }
+
+    #[test]
+    fn test_partial_output_removed() {
+        let dir = TempDir::new("worker");
+        let output = dir.join("out.png");
+
+        // Write half an output to the path in the request, then die
+        let worker = shell(
+            r#"out=$(sed 's/.*"output_path":"\([^"]*\)".*/\1/'); echo partial >"$out"; kill -9 $$"#,
+        );
+        let result = convert_isolated(
+            &worker,
+            Path::new("in.png"),
+            &output,
+            &ConversionOptions::default(),
+            None,
+        );
+        assert!(matches!(result, Err(ConvertError::Crashed(_))));
+        assert!(!output.exists());
+        assert!(!partial_path(&output).exists());
+    }
+}
@@ -0,0 +1,263 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gtk4::glib;
+use libadwaita as adw;
+use pixelconvert_core::{ConvertError, Crop, SupportedFormat};
+use std::path::{Path, PathBuf};
+
+/// Conversion state of a file in the list
+#[derive(Debug, Clone, Default)]
+pub enum FileStatus {
+    /// Added, not part of a conversion yet
+    #[default]
+    Idle,
+    Queued,
+    Running,
+    Done,
+    Failed(ConvertError),
+    Skipped(String),
+}
+
+impl FileStatus {
+    fn icon_name(&self) -> Option<&'static str> {
+        match self {
+            Self::Idle => None,
+            Self::Queued => Some("document-open-recent-symbolic"),
+            Self::Running => Some("emblem-synchronizing-symbolic"),
+            Self::Done => Some("object-select-symbolic"),
+            Self::Failed(_) => Some("dialog-error-symbolic"),
+            Self::Skipped(_) => Some("action-unavailable-symbolic"),
+        }
+    }
+
+    fn css_class(&self) -> &'static str {
+        match self {
+            Self::Done => "success",
+            Self::Failed(_) => "error",
+            Self::Skipped(_) => "warning",
+            _ => "dim-label",
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            Self::Idle => String::new(),
+            Self::Queued => "Waiting to be converted".to_string(),
+            Self::Running => "Converting…".to_string(),
+            Self::Done => "Converted".to_string(),
+            Self::Failed(error) => format!("Failed: {}", error),
+            Self::Skipped(reason) => format!("Skipped: {}", reason),
+        }
+    }
+}
+
+mod imp {
+    use super::*;
+    use std::cell::{Cell, OnceCell, RefCell};
+
+    #[derive(Debug, Default)]
+    pub struct FileRow {
+        pub path: OnceCell<PathBuf>,
+        pub row: adw::ExpanderRow,
+        pub status_icon: gtk4::Image,
+        pub status: RefCell<FileStatus>,
+        pub details: RefCell<Vec<adw::ActionRow>>,
+        /// Suffixes shown only while the file is queued
+        pub queued_suffixes: RefCell<Vec<gtk4::Widget>>,
+        /// Index of the file's job in the running batch
+        pub job: Cell<Option<usize>>,
+        /// Part of the image to convert, set with the crop tool
+        pub crop: Cell<Option<Crop>>,
+        /// Clockwise quarter turns on top of the batch-wide rotation
+        pub quarter_turns: Cell<u8>,
+        /// Summary of the crop and rotation
+        pub edit_label: gtk4::Label,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for FileRow {
+        const NAME: &'static str = "PixelConvertFileRow";
+        type Type = super::FileRow;
+    }
+
+    impl ObjectImpl for FileRow {}
+}
+
+glib::wrapper! {
+    /// A file in the file list, kept in the window's `gio::ListStore`. Its
+    /// widget is an expander row with a status icon that expands into
+    /// error details when the conversion fails.
+    pub struct FileRow(ObjectSubclass<imp::FileRow>);
+}
+
+impl FileRow {
+    pub fn new(path: PathBuf, format: SupportedFormat) -> Self {
+        let file: Self = glib::Object::new();
+        let imp = file.imp();
+
+        let row = &imp.row;
+        row.set_use_markup(false);
+        row.set_title(&path.file_name().unwrap_or_default().to_string_lossy());
+        row.set_subtitle(&format!(
+            "{} · {}",
+            format.display_name(),
+            path.parent().unwrap_or(Path::new("")).to_string_lossy()
+        ));
+        row.set_enable_expansion(false);
+
+        imp.edit_label.set_visible(false);
+        imp.edit_label.set_css_classes(&["dim-label", "numeric"]);
+        row.add_suffix(&imp.edit_label);
+
+        imp.status_icon.set_visible(false);
+        imp.status_icon.set_valign(gtk4::Align::Center);
+        row.add_suffix(&imp.status_icon);
+
+        imp.path.set(path).unwrap();
+        file
+    }
+
+    pub fn path(&self) -> &Path {
+        self.imp().path.get().unwrap()
+    }
+
+    pub fn widget(&self) -> &adw::ExpanderRow {
+        &self.imp().row
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(*self.imp().status.borrow(), FileStatus::Failed(_))
+    }
+
+    pub fn is_queued(&self) -> bool {
+        matches!(*self.imp().status.borrow(), FileStatus::Queued)
+    }
+
+    /// Index of the file's job in the running batch, if it is part of one
+    pub fn job(&self) -> Option<usize> {
+        self.imp().job.get()
+    }
+
+    pub fn set_job(&self, job: Option<usize>) {
+        self.imp().job.set(job);
+    }
+
+    pub fn crop(&self) -> Option<Crop> {
+        self.imp().crop.get()
+    }
+
+    /// Crop the file when it's converted, showing the kept size in the row
+    pub fn set_crop(&self, crop: Option<Crop>) {
+        self.imp().crop.set(crop);
+        self.update_edit_label();
+    }
+
+    pub fn quarter_turns(&self) -> u8 {
+        self.imp().quarter_turns.get()
+    }
+
+    /// Rotate the file by `turns` more clockwise quarter turns (negative
+    /// for counter-clockwise) when it's converted
+    pub fn rotate(&self, turns: i32) {
+        self.set_quarter_turns((self.quarter_turns() as i32 + turns).rem_euclid(4) as u8);
+    }
+
+    pub fn set_quarter_turns(&self, quarter_turns: u8) {
+        self.imp().quarter_turns.set(quarter_turns % 4);
+        self.update_edit_label();
+    }
+
+    fn update_edit_label(&self) {
+        let imp = self.imp();
+        let mut edits = Vec::new();
+        let mut details = Vec::new();
+        if let Some(crop) = self.crop() {
+            edits.push(format!("{}×{}", crop.width, crop.height));
+            details.push(format!(
+                "Cropped to {}×{} at {}, {}",
+                crop.width, crop.height, crop.x, crop.y
+            ));
+        }
+        if self.quarter_turns() != 0 {
+            let degrees = self.quarter_turns() as u32 * 90;
+            edits.push(format!("{}°", degrees));
+            details.push(format!("Rotated {}° clockwise", degrees));
+        }
+
+        imp.edit_label.set_visible(!edits.is_empty());
+        imp.edit_label.set_label(&edits.join(" · "));
+        imp.edit_label.set_tooltip_text(Some(&details.join("\n")));
+    }
+
+    /// Add a suffix that is only shown while the file is queued, such as a
+    /// button to convert it next
+    pub fn add_queued_suffix(&self, widget: &impl IsA<gtk4::Widget>) {
+        widget.set_visible(self.is_queued());
+        self.imp().row.add_suffix(widget);
+        self.imp()
+            .queued_suffixes
+            .borrow_mut()
+            .push(widget.upcast_ref::<gtk4::Widget>().clone());
+    }
+
+    pub fn set_status(&self, status: FileStatus) {
+        let imp = self.imp();
+        match status.icon_name() {
+            Some(icon_name) => {
+                imp.status_icon.set_icon_name(Some(icon_name));
+                imp.status_icon.set_css_classes(&[status.css_class()]);
+                imp.status_icon
+                    .set_tooltip_text(Some(&status.description()));
+                imp.status_icon.set_visible(true);
+            }
+            None => imp.status_icon.set_visible(false),
+        }
+        for suffix in imp.queued_suffixes.borrow().iter() {
+            suffix.set_visible(matches!(status, FileStatus::Queued));
+        }
+
+        for detail in imp.details.borrow_mut().drain(..) {
+            imp.row.remove(&detail);
+        }
+        let details = match &status {
+            FileStatus::Failed(error) => {
+                let mut details = vec![("Error", error.to_string())];
+                if let Some(hint) = error.hint() {
+                    details.push(("Suggestion", hint.to_string()));
+                }
+                details
+            }
+            FileStatus::Skipped(reason) => vec![("Skipped", reason.clone())],
+            _ => Vec::new(),
+        };
+        for (title, text) in &details {
+            let detail = adw::ActionRow::new();
+            detail.set_use_markup(false);
+            detail.set_title(title);
+            detail.set_subtitle(text);
+            detail.set_subtitle_selectable(true);
+            detail.set_css_classes(&["property"]);
+            imp.row.add_row(&detail);
+            imp.details.borrow_mut().push(detail);
+        }
+        imp.row.set_enable_expansion(!details.is_empty());
+        if details.is_empty() {
+            imp.row.set_expanded(false);
+        }
+
+        *imp.status.borrow_mut() = status;
+    }
+
+    /// The failure as a plain-text report entry, if the conversion failed
+    pub fn error_report(&self) -> Option<String> {
+        let FileStatus::Failed(error) = &*self.imp().status.borrow() else {
+            return None;
+        };
+
+        let mut report = format!("{}\n  {}", self.path().display(), error);
+        if let Some(hint) = error.hint() {
+            report.push_str(&format!("\n  Suggestion: {}", hint));
+        }
+        Some(report)
+    }
+}
@@ -1,7 +1,7 @@
-mod batch;
-mod converter;
+mod file_row;
 mod preferences;
 mod preview;
+mod watcher;
 mod window;
 
 use gtk4::gio;
@@ -13,6 +13,11 @@ use libadwaita::prelude::*;
 const APP_ID: &str = "dev.pinkpixel.PixelConvert";
 
 fn main() -> glib::ExitCode {
+    // Isolated conversions re-run this binary as a worker
+    if pixelconvert_core::worker::is_worker_invocation() {
+        return glib::ExitCode::from(i32::from(pixelconvert_core::worker::serve()));
+    }
+
     // Initialize GTK
     gtk4::init().expect("Failed to initialize GTK");
 
@@ -79,6 +84,7 @@ fn build_ui(app: &adw::Application) {
     setup_window_actions(&window);
 
     window.present();
+    window.offer_resume();
 }
 
 fn setup_window_actions(window: &window::PixelConvertWindow) {
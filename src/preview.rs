@@ -1,11 +1,434 @@
 use gtk4::prelude::*;
+use gtk4::{cairo, gdk, gio, glib};
+use pixelconvert_core::image::{DynamicImage, RgbaImage};
+use pixelconvert_core::{transform, ConversionOptions, Crop, ImageConverter};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
 
-/// Preview widget for showing before/after image comparison
+/// Distance in widget pixels within which a crop handle can be grabbed
+const HANDLE_REACH: f64 = 12.0;
+const HANDLE_SIZE: f64 = 8.0;
+/// Longest side of the rendered preview, in pixels
+const PREVIEW_SIZE: u32 = 1024;
+
+/// A rectangle in image pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Rect {
+    fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+}
+
+/// What a drag on the crop overlay changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Handle {
+    /// Drawing a new crop from where the drag started
+    New,
+    Move,
+    North,
+    South,
+    East,
+    West,
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+}
+
+impl Handle {
+    fn cursor_name(&self) -> &'static str {
+        match self {
+            Self::New => "crosshair",
+            Self::Move => "move",
+            Self::North | Self::South => "ns-resize",
+            Self::East | Self::West => "ew-resize",
+            Self::NorthWest | Self::SouthEast => "nwse-resize",
+            Self::NorthEast | Self::SouthWest => "nesw-resize",
+        }
+    }
+
+    /// Where the handle sits on `rect`, as fractions of its width and height
+    fn position(&self) -> Option<(f64, f64)> {
+        match self {
+            Self::New | Self::Move => None,
+            Self::North => Some((0.5, 0.0)),
+            Self::South => Some((0.5, 1.0)),
+            Self::East => Some((1.0, 0.5)),
+            Self::West => Some((0.0, 0.5)),
+            Self::NorthEast => Some((1.0, 0.0)),
+            Self::NorthWest => Some((0.0, 0.0)),
+            Self::SouthEast => Some((1.0, 1.0)),
+            Self::SouthWest => Some((0.0, 1.0)),
+        }
+    }
+
+    /// Corners first, so they win over the edges of small crops
+    const RESIZE: [Self; 8] = [
+        Self::NorthWest,
+        Self::NorthEast,
+        Self::SouthWest,
+        Self::SouthEast,
+        Self::North,
+        Self::South,
+        Self::East,
+        Self::West,
+    ];
+}
+
+/// The crop being edited on the original image
+#[derive(Default)]
+struct CropState {
+    enabled: bool,
+    /// Size of the loaded image
+    image_size: Option<(f64, f64)>,
+    /// `None` keeps the whole image
+    rect: Option<Rect>,
+    /// Width / height the crop is locked to
+    ratio: Option<f64>,
+    /// Crop to show once the image has loaded
+    pending: Option<Crop>,
+    /// The handle being dragged, the crop and the image point where the
+    /// drag started
+    drag: Option<(Handle, Rect, (f64, f64))>,
+    on_changed: Vec<Rc<dyn Fn(Option<Crop>)>>,
+}
+
+impl CropState {
+    fn bounds(&self) -> Option<Rect> {
+        self.image_size.map(|(width, height)| Rect {
+            x: 0.0,
+            y: 0.0,
+            width,
+            height,
+        })
+    }
+
+    /// Scale and offset of the image as `gtk4::Picture` fits it into a
+    /// widget of the given size
+    fn view(&self, width: f64, height: f64) -> Option<(f64, f64, f64)> {
+        let (image_width, image_height) = self.image_size?;
+        let scale = (width / image_width).min(height / image_height);
+        Some((
+            scale,
+            (width - image_width * scale) / 2.0,
+            (height - image_height * scale) / 2.0,
+        ))
+    }
+
+    fn crop(&self) -> Option<Crop> {
+        let (width, height) = self.image_size?;
+        let rect = self.rect?;
+        let crop = Crop {
+            x: rect.x.round() as u32,
+            y: rect.y.round() as u32,
+            width: (rect.width.round() as u32).max(1),
+            height: (rect.height.round() as u32).max(1),
+        };
+        let crop = crop.clamp(width as u32, height as u32)?;
+        (!crop.covers(width as u32, height as u32)).then_some(crop)
+    }
+
+    fn set_crop(&mut self, crop: Option<Crop>) {
+        let Some((width, height)) = self.image_size else {
+            self.pending = crop;
+            return;
+        };
+        self.rect = crop
+            .and_then(|crop| crop.clamp(width as u32, height as u32))
+            .map(|crop| Rect {
+                x: crop.x as f64,
+                y: crop.y as f64,
+                width: crop.width as f64,
+                height: crop.height as f64,
+            });
+    }
+
+    /// Lock the crop to a ratio, starting over from the largest centred
+    /// crop of that shape
+    fn set_ratio(&mut self, ratio: Option<(u32, u32)>) {
+        self.ratio = ratio.map(|(width, height)| width.max(1) as f64 / height.max(1) as f64);
+        if let (Some(ratio), Some((width, height))) = (ratio, self.image_size) {
+            self.set_crop(Some(Crop::centered(width as u32, height as u32, ratio)));
+        }
+    }
+
+    /// The handle under a point of a `width`×`height` widget
+    fn handle_at(&self, x: f64, y: f64, width: f64, height: f64) -> Option<Handle> {
+        let (scale, offset_x, offset_y) = self.view(width, height)?;
+        let rect = self.rect.or(self.bounds())?;
+        let (left, top) = (offset_x + rect.x * scale, offset_y + rect.y * scale);
+        let (right, bottom) = (
+            offset_x + rect.right() * scale,
+            offset_y + rect.bottom() * scale,
+        );
+
+        let near = |handle: &Handle| {
+            let (fx, fy) = handle.position().unwrap_or_default();
+            let hx = left + (right - left) * fx;
+            let hy = top + (bottom - top) * fy;
+            (x - hx).abs() <= HANDLE_REACH && (y - hy).abs() <= HANDLE_REACH
+        };
+        Some(match Handle::RESIZE.iter().find(|handle| near(handle)) {
+            Some(handle) => *handle,
+            None if (left..=right).contains(&x) && (top..=bottom).contains(&y) => Handle::Move,
+            None => Handle::New,
+        })
+    }
+
+    fn notify(&self) {
+        let crop = self.crop();
+        for callback in self.on_changed.clone() {
+            callback(crop);
+        }
+    }
+}
+
+/// What the preview pane shows: the original put through `transform::apply`.
+/// Holds no widgets, so callbacks can keep it alive.
+#[derive(Default)]
+struct RenderState {
+    source: Option<Arc<DynamicImage>>,
+    options: ConversionOptions,
+    /// Quarter turns on top of `options.orientation`
+    turns: u8,
+    /// What the trim keeps of the original, outlined over it
+    trim_box: Option<Crop>,
+    /// Bumped for every render, so a slow render can't replace a newer one
+    generation: u64,
+}
+
+/// Render the preview off the main thread and show it in `view`, with
+/// the detected trim box drawn on `area`
+fn render_preview(
+    render: &Rc<RefCell<RenderState>>,
+    crop: Option<Crop>,
+    view: &gtk4::Picture,
+    area: &gtk4::DrawingArea,
+) {
+    let mut state = render.borrow_mut();
+    let Some(source) = state.source.clone() else {
+        return;
+    };
+    state.generation += 1;
+    let generation = state.generation;
+    let options = ConversionOptions {
+        crop,
+        orientation: state.options.orientation.turned(state.turns as i32),
+        ..state.options.clone()
+    };
+    drop(state);
+
+    let render = render.clone();
+    let view = view.clone();
+    let area = area.clone();
+    glib::spawn_future_local(async move {
+        let rendered = gio::spawn_blocking(move || {
+            let trim_box = transform::trim_box(&source, &options);
+            let img = transform::apply((*source).clone(), &options).map(|img| {
+                if img.width() > PREVIEW_SIZE || img.height() > PREVIEW_SIZE {
+                    img.thumbnail(PREVIEW_SIZE, PREVIEW_SIZE).to_rgba8()
+                } else {
+                    img.to_rgba8()
+                }
+            });
+            (img, trim_box)
+        })
+        .await;
+        let Ok((rendered, trim_box)) = rendered else {
+            return;
+        };
+        let mut state = render.borrow_mut();
+        if state.generation != generation {
+            return;
+        }
+        state.trim_box = trim_box;
+        drop(state);
+        area.queue_draw();
+        match rendered {
+            Ok(rgba) => view.set_paintable(Some(&texture(rgba))),
+            Err(e) => {
+                eprintln!("Failed to render the preview: {}", e);
+                view.set_paintable(gdk::Paintable::NONE);
+            }
+        }
+    });
+}
+
+fn texture(rgba: RgbaImage) -> gdk::MemoryTexture {
+    let (width, height) = rgba.dimensions();
+    gdk::MemoryTexture::new(
+        width as i32,
+        height as i32,
+        gdk::MemoryFormat::R8g8b8a8,
+        &glib::Bytes::from_owned(rgba.into_raw()),
+        width as usize * 4,
+    )
+}
+
+/// The crop `start` becomes when `handle` is dragged by `(dx, dy)` image
+/// pixels from `origin`, kept inside `bounds` and to `ratio`
+fn drag_rect(
+    handle: Handle,
+    start: Rect,
+    origin: (f64, f64),
+    (dx, dy): (f64, f64),
+    bounds: Rect,
+    ratio: Option<f64>,
+) -> Rect {
+    match handle {
+        Handle::Move => Rect {
+            x: (start.x + dx).clamp(0.0, bounds.width - start.width),
+            y: (start.y + dy).clamp(0.0, bounds.height - start.height),
+            ..start
+        },
+        Handle::New => corner_rect(origin, (origin.0 + dx, origin.1 + dy), bounds, ratio),
+        Handle::East | Handle::West => {
+            let east = handle == Handle::East;
+            let anchor = if east { start.x } else { start.right() };
+            let pointer = (if east { start.right() } else { start.x }) + dx;
+            let (x, width, y, height) = edge_rect(
+                anchor,
+                pointer,
+                bounds.width,
+                (start.y, start.height, bounds.height),
+                ratio,
+            );
+            Rect {
+                x,
+                y,
+                width,
+                height,
+            }
+        }
+        Handle::North | Handle::South => {
+            let south = handle == Handle::South;
+            let anchor = if south { start.y } else { start.bottom() };
+            let pointer = (if south { start.bottom() } else { start.y }) + dy;
+            let (y, height, x, width) = edge_rect(
+                anchor,
+                pointer,
+                bounds.height,
+                (start.x, start.width, bounds.width),
+                ratio.map(|ratio| 1.0 / ratio),
+            );
+            Rect {
+                x,
+                y,
+                width,
+                height,
+            }
+        }
+        Handle::NorthEast | Handle::NorthWest | Handle::SouthEast | Handle::SouthWest => {
+            let east = matches!(handle, Handle::NorthEast | Handle::SouthEast);
+            let south = matches!(handle, Handle::SouthEast | Handle::SouthWest);
+            let anchor = (
+                if east { start.x } else { start.right() },
+                if south { start.y } else { start.bottom() },
+            );
+            let corner = (
+                if east { start.right() } else { start.x },
+                if south { start.bottom() } else { start.y },
+            );
+            corner_rect(anchor, (corner.0 + dx, corner.1 + dy), bounds, ratio)
+        }
+    }
+}
+
+/// The crop spanning from a fixed corner to the pointer
+fn corner_rect(anchor: (f64, f64), pointer: (f64, f64), bounds: Rect, ratio: Option<f64>) -> Rect {
+    let (anchor_x, anchor_y) = (
+        anchor.0.clamp(0.0, bounds.width),
+        anchor.1.clamp(0.0, bounds.height),
+    );
+    let (right, down) = (pointer.0 >= anchor_x, pointer.1 >= anchor_y);
+    let max_width = if right {
+        bounds.width - anchor_x
+    } else {
+        anchor_x
+    };
+    let max_height = if down {
+        bounds.height - anchor_y
+    } else {
+        anchor_y
+    };
+
+    let mut width = (pointer.0 - anchor_x).abs().min(max_width);
+    let mut height = (pointer.1 - anchor_y).abs().min(max_height);
+    if let Some(ratio) = ratio {
+        width = width
+            .max(height * ratio)
+            .min(max_width)
+            .min(max_height * ratio);
+        height = width / ratio;
+    }
+
+    Rect {
+        x: if right { anchor_x } else { anchor_x - width },
+        y: if down { anchor_y } else { anchor_y - height },
+        width,
+        height,
+    }
+}
+
+/// Position and size along the dragged axis, then across it, for an edge
+/// dragged away from the fixed opposite edge. With a ratio, the crop grows
+/// or shrinks across the axis around its centre.
+fn edge_rect(
+    anchor: f64,
+    pointer: f64,
+    limit: f64,
+    (cross_start, cross_size, cross_limit): (f64, f64, f64),
+    ratio: Option<f64>,
+) -> (f64, f64, f64, f64) {
+    let forward = pointer >= anchor;
+    let mut size = (pointer - anchor)
+        .abs()
+        .min(if forward { limit - anchor } else { anchor });
+
+    let (cross_start, cross_size) = match ratio {
+        None => (cross_start, cross_size),
+        Some(ratio) => {
+            let center = cross_start + cross_size / 2.0;
+            let max_cross = 2.0 * center.min(cross_limit - center);
+            size = size.min(max_cross * ratio);
+            (center - size / ratio / 2.0, size / ratio)
+        }
+    };
+
+    (
+        if forward { anchor } else { anchor - size },
+        size,
+        cross_start,
+        cross_size,
+    )
+}
+
+/// Preview widget for showing before/after image comparison: the preview
+/// is the original put through the image operations (crop, trim,
+/// rotation, canvas, watermark), with an optional crop overlay on the
+/// original
 #[allow(dead_code)]
 pub struct PreviewWidget {
     container: gtk4::Box,
     original_view: gtk4::Picture,
+    preview_box: gtk4::Box,
     preview_view: gtk4::Picture,
+    crop_area: gtk4::DrawingArea,
+    rotate_box: gtk4::Box,
+    crop: Rc<RefCell<CropState>>,
+    render: Rc<RefCell<RenderState>>,
 }
 
 #[allow(dead_code)]
@@ -28,10 +451,19 @@ impl PreviewWidget {
             .css_classes(vec!["title-4"])
             .build();
 
-        let original_view = gtk4::Picture::builder().can_shrink(true).build();
+        let original_view = gtk4::Picture::builder()
+            .can_shrink(true)
+            .content_fit(gtk4::ContentFit::Contain)
+            .vexpand(true)
+            .build();
+
+        // Crop overlay, drawn over the original
+        let crop_area = gtk4::DrawingArea::builder().visible(false).build();
+        let original_overlay = gtk4::Overlay::builder().child(&original_view).build();
+        original_overlay.add_overlay(&crop_area);
 
         original_box.append(&original_label);
-        original_box.append(&original_view);
+        original_box.append(&original_overlay);
 
         // Preview image view
         let preview_box = gtk4::Box::builder()
@@ -44,21 +476,187 @@ impl PreviewWidget {
             .css_classes(vec!["title-4"])
             .build();
 
-        let preview_view = gtk4::Picture::builder().can_shrink(true).build();
+        let preview_view = gtk4::Picture::builder()
+            .can_shrink(true)
+            .content_fit(gtk4::ContentFit::Contain)
+            .vexpand(true)
+            .build();
+
+        let rotate_box = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .spacing(6)
+            .halign(gtk4::Align::Center)
+            .visible(false)
+            .build();
 
         preview_box.append(&preview_label);
         preview_box.append(&preview_view);
+        preview_box.append(&rotate_box);
 
         container.append(&original_box);
         container.append(&preview_box);
 
-        Self {
+        let widget = Self {
             container,
             original_view,
+            preview_box,
             preview_view,
+            crop_area,
+            rotate_box,
+            crop: Rc::new(RefCell::new(CropState::default())),
+            render: Rc::new(RefCell::new(RenderState::default())),
+        };
+        widget.setup_crop_overlay();
+        widget.setup_rotate_buttons();
+
+        let render = &widget.render;
+        let view = &widget.preview_view;
+        let area = &widget.crop_area;
+        widget.connect_crop_changed(glib::clone!(
+            #[strong]
+            render,
+            #[weak]
+            view,
+            #[weak]
+            area,
+            move |crop: Option<Crop>| render_preview(&render, crop, &view, &area)
+        ));
+        widget
+    }
+
+    fn setup_rotate_buttons(&self) {
+        for (icon, tooltip, turns) in [
+            ("object-rotate-left-symbolic", "Rotate Left", 3),
+            ("object-rotate-right-symbolic", "Rotate Right", 1),
+        ] {
+            let button = gtk4::Button::from_icon_name(icon);
+            button.set_css_classes(&["flat", "circular"]);
+            button.set_tooltip_text(Some(tooltip));
+            let crop = &self.crop;
+            let render = &self.render;
+            let view = &self.preview_view;
+            let area = &self.crop_area;
+            button.connect_clicked(glib::clone!(
+                #[strong]
+                crop,
+                #[strong]
+                render,
+                #[weak]
+                view,
+                #[weak]
+                area,
+                move |_| {
+                    let mut state = render.borrow_mut();
+                    state.turns = (state.turns + turns) % 4;
+                    drop(state);
+                    render_preview(&render, crop.borrow().crop(), &view, &area);
+                }
+            ));
+            self.rotate_box.append(&button);
         }
     }
 
+    fn setup_crop_overlay(&self) {
+        let crop = &self.crop;
+        let render = &self.render;
+        let area = &self.crop_area;
+        self.crop_area.set_draw_func(glib::clone!(
+            #[strong]
+            crop,
+            #[strong]
+            render,
+            move |_, cr, width, height| {
+                let crop = crop.borrow();
+                if let Some((scale, offset_x, offset_y)) = crop.view(width as f64, height as f64) {
+                    draw_crop(cr, &crop, scale, offset_x, offset_y);
+                    // Stale while the crop is dragged; redrawn once it's rendered
+                    if let (Some(trim_box), None) = (render.borrow().trim_box, crop.drag) {
+                        draw_trim_box(cr, trim_box, scale, offset_x, offset_y);
+                    }
+                }
+            }
+        ));
+
+        let motion = gtk4::EventControllerMotion::new();
+        motion.connect_motion(glib::clone!(
+            #[strong]
+            crop,
+            #[weak]
+            area,
+            move |_, x, y| {
+                let handle =
+                    crop.borrow()
+                        .handle_at(x, y, area.width() as f64, area.height() as f64);
+                area.set_cursor_from_name(handle.map(|handle| handle.cursor_name()));
+            }
+        ));
+        self.crop_area.add_controller(motion);
+
+        let drag = gtk4::GestureDrag::new();
+        drag.connect_drag_begin(glib::clone!(
+            #[strong]
+            crop,
+            #[weak]
+            area,
+            move |_, x, y| {
+                let (width, height) = (area.width() as f64, area.height() as f64);
+                let mut crop = crop.borrow_mut();
+                let (Some(handle), Some((scale, offset_x, offset_y)), Some(bounds)) = (
+                    crop.handle_at(x, y, width, height),
+                    crop.view(width, height),
+                    crop.bounds(),
+                ) else {
+                    return;
+                };
+                let origin = (
+                    ((x - offset_x) / scale).clamp(0.0, bounds.width),
+                    ((y - offset_y) / scale).clamp(0.0, bounds.height),
+                );
+                let start = crop.rect.unwrap_or(bounds);
+                crop.drag = Some((handle, start, origin));
+            }
+        ));
+        drag.connect_drag_update(glib::clone!(
+            #[strong]
+            crop,
+            #[weak]
+            area,
+            move |_, dx, dy| {
+                let mut crop = crop.borrow_mut();
+                let (Some((handle, start, origin)), Some((scale, _, _)), Some(bounds)) = (
+                    crop.drag,
+                    crop.view(area.width() as f64, area.height() as f64),
+                    crop.bounds(),
+                ) else {
+                    return;
+                };
+                let rect = drag_rect(
+                    handle,
+                    start,
+                    origin,
+                    (dx / scale, dy / scale),
+                    bounds,
+                    crop.ratio,
+                );
+                // A click without a drag doesn't replace the crop
+                if rect.width >= 1.0 && rect.height >= 1.0 {
+                    crop.rect = Some(rect);
+                }
+                area.queue_draw();
+            }
+        ));
+        drag.connect_drag_end(glib::clone!(
+            #[strong]
+            crop,
+            move |_, _, _| {
+                if crop.borrow_mut().drag.take().is_some() {
+                    crop.borrow().notify();
+                }
+            }
+        ));
+        self.crop_area.add_controller(drag);
+    }
+
     pub fn widget(&self) -> &gtk4::Box {
         &self.container
     }
@@ -69,12 +667,181 @@ impl PreviewWidget {
         }
     }
 
+    /// Decode the original off the main thread, so formats GdkPixbuf
+    /// can't read (AVIF, WebP) show too and the crop is in source pixels
+    pub fn load_original(&self, path: &std::path::Path) {
+        let path = path.to_path_buf();
+        let original_view = self.original_view.clone();
+        let crop_area = self.crop_area.clone();
+        let crop = self.crop.clone();
+        let render = self.render.clone();
+        let limits = render.borrow().options.limits.clone();
+        glib::spawn_future_local(async move {
+            let decoded = gio::spawn_blocking(move || {
+                ImageConverter::load_image_with_limits(&path, &limits).map(|img| {
+                    let rgba = img.to_rgba8();
+                    (Arc::new(img), rgba)
+                })
+            })
+            .await;
+            let (source, rgba) = match decoded {
+                Ok(Ok(decoded)) => decoded,
+                Ok(Err(e)) => {
+                    eprintln!("Failed to load the preview: {}", e);
+                    return;
+                }
+                Err(_) => return,
+            };
+
+            let (width, height) = rgba.dimensions();
+            original_view.set_paintable(Some(&texture(rgba)));
+            render.borrow_mut().source = Some(source);
+
+            let mut state = crop.borrow_mut();
+            state.image_size = Some((width as f64, height as f64));
+            let pending = state.pending.take();
+            state.set_crop(pending);
+            drop(state);
+            crop_area.queue_draw();
+            crop.borrow().notify();
+        });
+    }
+
+    /// Show the crop overlay on the original
+    pub fn set_crop_enabled(&self, enabled: bool) {
+        self.crop.borrow_mut().enabled = enabled;
+        self.crop_area.set_visible(enabled);
+    }
+
+    /// Show rotate buttons under the preview, changing `turns()`
+    pub fn set_rotate_enabled(&self, enabled: bool) {
+        self.rotate_box.set_visible(enabled);
+    }
+
+    /// Settings the preview is rendered with. Their crop is replaced by
+    /// the one drawn on the original.
+    pub fn set_options(&self, options: ConversionOptions) {
+        self.render.borrow_mut().options = options;
+        self.rerender();
+    }
+
+    /// Quarter turns clockwise on top of the settings' orientation, as
+    /// changed by the rotate buttons under the preview
+    pub fn turns(&self) -> u8 {
+        self.render.borrow().turns
+    }
+
+    pub fn set_turns(&self, turns: u8) {
+        self.render.borrow_mut().turns = turns % 4;
+        self.rerender();
+    }
+
+    fn rerender(&self) {
+        render_preview(
+            &self.render,
+            self.crop(),
+            &self.preview_view,
+            &self.crop_area,
+        );
+    }
+
+    /// The crop drawn on the original, `None` if it keeps the whole image
+    pub fn crop(&self) -> Option<Crop> {
+        self.crop.borrow().crop()
+    }
+
+    pub fn set_crop(&self, crop: Option<Crop>) {
+        self.crop.borrow_mut().set_crop(crop);
+        self.crop_area.queue_draw();
+        self.crop.borrow().notify();
+    }
+
+    /// Lock the crop to a width:height ratio, or free it with `None`
+    pub fn set_aspect_ratio(&self, ratio: Option<(u32, u32)>) {
+        self.crop.borrow_mut().set_ratio(ratio);
+        self.crop_area.queue_draw();
+        self.crop.borrow().notify();
+    }
+
+    /// Call `f` with the new crop whenever it changes
+    pub fn connect_crop_changed<F: Fn(Option<Crop>) + 'static>(&self, f: F) {
+        self.crop.borrow_mut().on_changed.push(Rc::new(f));
+    }
+
     pub fn clear(&self) {
+        let mut render = self.render.borrow_mut();
+        render.source = None;
+        render.trim_box = None;
+        drop(render);
+        self.crop_area.queue_draw();
         self.original_view.set_paintable(gtk4::gdk::Paintable::NONE);
         self.preview_view.set_paintable(gtk4::gdk::Paintable::NONE);
     }
 
-    // TODO: Implement actual preview generation
     // TODO: Add zoom/pan controls
     // TODO: Show image info (dimensions, size, format)
 }
+
+/// Outline what the trim keeps with a dashed line
+fn draw_trim_box(cr: &cairo::Context, trim_box: Crop, scale: f64, offset_x: f64, offset_y: f64) {
+    cr.set_source_rgb(0.21, 0.52, 0.89);
+    cr.set_line_width(1.5);
+    cr.set_dash(&[6.0, 4.0], 0.0);
+    cr.rectangle(
+        offset_x + trim_box.x as f64 * scale,
+        offset_y + trim_box.y as f64 * scale,
+        trim_box.width as f64 * scale,
+        trim_box.height as f64 * scale,
+    );
+    cr.stroke().ok();
+    cr.set_dash(&[], 0.0);
+}
+
+/// Dim everything outside the crop, then draw its border, rule-of-thirds
+/// guides and resize handles
+fn draw_crop(cr: &cairo::Context, crop: &CropState, scale: f64, offset_x: f64, offset_y: f64) {
+    let (Some(bounds), true) = (crop.bounds(), crop.enabled) else {
+        return;
+    };
+    let rect = crop.rect.unwrap_or(bounds);
+    let (x, y) = (offset_x + rect.x * scale, offset_y + rect.y * scale);
+    let (width, height) = (rect.width * scale, rect.height * scale);
+
+    cr.set_fill_rule(cairo::FillRule::EvenOdd);
+    cr.rectangle(
+        offset_x,
+        offset_y,
+        bounds.width * scale,
+        bounds.height * scale,
+    );
+    cr.rectangle(x, y, width, height);
+    cr.set_source_rgba(0.0, 0.0, 0.0, 0.5);
+    cr.fill().ok();
+    cr.set_fill_rule(cairo::FillRule::Winding);
+
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.5);
+    cr.set_line_width(1.0);
+    for third in [1.0 / 3.0, 2.0 / 3.0] {
+        cr.move_to(x + width * third, y);
+        cr.line_to(x + width * third, y + height);
+        cr.move_to(x, y + height * third);
+        cr.line_to(x + width, y + height * third);
+    }
+    cr.stroke().ok();
+
+    cr.set_source_rgb(1.0, 1.0, 1.0);
+    cr.set_line_width(1.5);
+    cr.rectangle(x, y, width, height);
+    cr.stroke().ok();
+
+    for handle in Handle::RESIZE {
+        let (fx, fy) = handle.position().unwrap_or_default();
+        cr.rectangle(
+            x + width * fx - HANDLE_SIZE / 2.0,
+            y + height * fy - HANDLE_SIZE / 2.0,
+            HANDLE_SIZE,
+            HANDLE_SIZE,
+        );
+    }
+    cr.fill().ok();
+}
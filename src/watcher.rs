@@ -0,0 +1,194 @@
+use gtk4::prelude::*;
+use gtk4::{gio, glib};
+use pixelconvert_core::watch::{Debouncer, HistoryEntry, WatchFolder, WatchHistory};
+use pixelconvert_core::{BatchJob, BatchProgress, Execution};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+/// How often files waiting to settle are checked while there are any
+const SETTLE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Converts images dropped into watched folders while it's alive: a
+/// `gio::FileMonitor` per folder feeds a `Debouncer`, and settled files
+/// are converted one batch at a time and logged to the watch history
+#[derive(Debug)]
+pub struct FolderWatcher {
+    monitors: Vec<gio::FileMonitor>,
+    state: Rc<RefCell<WatchState>>,
+}
+
+/// What the monitors, the settle timer and the running batch share
+struct WatchState {
+    watch: WatchFolder,
+    execution: Execution,
+    history: Option<WatchHistory>,
+    on_entry: Rc<dyn Fn(&HistoryEntry)>,
+    debouncer: Debouncer,
+    /// Armed only while files are waiting to settle and no batch is running
+    settle_timer: Option<glib::SourceId>,
+    /// The batch converting settled files; files that settle meanwhile wait
+    /// for it to finish
+    batch: Option<glib::JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for WatchState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchState")
+            .field("watch", &self.watch)
+            .field("running", &self.batch.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl FolderWatcher {
+    /// Start watching. Images already in the folders are converted first if
+    /// their output is missing or out of date. `on_entry` is called on the
+    /// main thread for every file that was converted, skipped or failed.
+    pub fn start(
+        watch: WatchFolder,
+        execution: Execution,
+        on_entry: impl Fn(&HistoryEntry) + 'static,
+    ) -> Result<Self, glib::Error> {
+        let existing = watch.existing_jobs();
+        let state = Rc::new(RefCell::new(WatchState {
+            watch,
+            execution,
+            history: WatchHistory::default_path().map(WatchHistory::new),
+            on_entry: Rc::new(on_entry),
+            debouncer: Debouncer::new(pixelconvert_core::watch::DEFAULT_SETTLE_TIME),
+            settle_timer: None,
+            batch: None,
+        }));
+
+        let mut monitors = Vec::new();
+        for folder in &state.borrow().watch.folders {
+            let monitor = gio::File::for_path(folder).monitor_directory(
+                gio::FileMonitorFlags::WATCH_MOVES,
+                gio::Cancellable::NONE,
+            )?;
+            monitor.connect_changed(glib::clone!(
+                #[weak]
+                state,
+                move |_, file, other_file, event| {
+                    let changed = match event {
+                        gio::FileMonitorEvent::Created
+                        | gio::FileMonitorEvent::Changed
+                        | gio::FileMonitorEvent::ChangesDoneHint
+                        | gio::FileMonitorEvent::MovedIn => file.path(),
+                        // Renamed within the folder, e.g. a finished download
+                        gio::FileMonitorEvent::Renamed => other_file.and_then(|f| f.path()),
+                        _ => None,
+                    };
+                    if let Some(path) = changed {
+                        state.borrow_mut().debouncer.notice(path);
+                        arm_settle_timer(&state);
+                    }
+                }
+            ));
+            monitors.push(monitor);
+        }
+
+        match existing {
+            Ok(jobs) => {
+                for job in jobs {
+                    state.borrow_mut().debouncer.notice(job.input_path);
+                }
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+        arm_settle_timer(&state);
+
+        Ok(Self { monitors, state })
+    }
+}
+
+impl Drop for FolderWatcher {
+    /// Stop watching. A batch that is already running still finishes, but
+    /// isn't logged.
+    fn drop(&mut self) {
+        for monitor in &self.monitors {
+            monitor.cancel();
+        }
+        let mut state = self.state.borrow_mut();
+        if let Some(timer) = state.settle_timer.take() {
+            timer.remove();
+        }
+        if let Some(batch) = state.batch.take() {
+            batch.abort();
+        }
+    }
+}
+
+/// Start checking for settled files, unless there are none waiting, a check
+/// is already armed or a batch is running
+fn arm_settle_timer(state: &Rc<RefCell<WatchState>>) {
+    let mut guard = state.borrow_mut();
+    if guard.settle_timer.is_some() || guard.batch.is_some() || guard.debouncer.is_empty() {
+        return;
+    }
+    let weak = Rc::downgrade(state);
+    guard.settle_timer = Some(glib::timeout_add_local(SETTLE_CHECK_INTERVAL, move || {
+        let Some(state) = weak.upgrade() else {
+            return glib::ControlFlow::Break;
+        };
+        let jobs: Vec<BatchJob> = {
+            let mut guard = state.borrow_mut();
+            let settled = guard.debouncer.settled();
+            settled
+                .iter()
+                .filter_map(|path| guard.watch.job_for(path))
+                .collect()
+        };
+        if !jobs.is_empty() {
+            state.borrow_mut().settle_timer = None;
+            start_batch(&state, jobs);
+            return glib::ControlFlow::Break;
+        }
+        if state.borrow().debouncer.is_empty() {
+            state.borrow_mut().settle_timer = None;
+            return glib::ControlFlow::Break;
+        }
+        glib::ControlFlow::Continue
+    }));
+}
+
+/// Convert `jobs`, logging each file as its progress arrives, and go back to
+/// waiting for settled files once the batch finishes
+fn start_batch(state: &Rc<RefCell<WatchState>>, jobs: Vec<BatchJob>) {
+    let execution = state.borrow().execution.clone();
+    let receiver = pixelconvert_core::run_batch_with(jobs.clone(), execution).progress;
+    let weak: Weak<RefCell<WatchState>> = Rc::downgrade(state);
+    let batch = glib::spawn_future_local(async move {
+        while let Some(updates) = receiver.updates().await {
+            let Some(state) = weak.upgrade() else {
+                return;
+            };
+            for progress in updates {
+                match &progress {
+                    BatchProgress::Finished { .. } => {
+                        state.borrow_mut().batch = None;
+                        arm_settle_timer(&state);
+                        return;
+                    }
+                    BatchProgress::CacheNotSaved { error } => {
+                        eprintln!("Output cache not saved: {}", error);
+                    }
+                    _ => {}
+                }
+                if let Some(entry) = HistoryEntry::from_progress(&jobs, &progress) {
+                    let on_entry = {
+                        let state = state.borrow();
+                        if let Some(history) = state.history.as_ref() {
+                            if let Err(e) = history.append(&entry) {
+                                eprintln!("{}", e);
+                            }
+                        }
+                        state.on_entry.clone()
+                    };
+                    on_entry(&entry);
+                }
+            }
+        }
+    });
+    state.borrow_mut().batch = Some(batch);
+}
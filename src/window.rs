@@ -1,3 +1,4 @@
+use crate::file_row::{FileRow, FileStatus};
 use adw::subclass::prelude::*;
 use gtk4::prelude::*;
 use gtk4::{gdk, gio, glib};
@@ -14,16 +15,70 @@ mod imp {
         pub content_stack: gtk4::Stack,
         pub status_page: adw::StatusPage,
         pub main_view: gtk4::Box,
-        pub selected_files: RefCell<Vec<std::path::PathBuf>>,
+        /// The files in the list, in the order they are converted
+        pub files: gio::ListStore,
         pub format_dropdown: gtk4::DropDown,
         pub quality_scale: gtk4::Scale,
         pub convert_button: gtk4::Button,
+        pub retry_button: gtk4::Button,
+        pub report_button: gtk4::Button,
+        pub export_button: gtk4::Button,
+        pub last_report: RefCell<Option<pixelconvert_core::BatchReport>>,
         pub file_list: gtk4::ListBox,
         pub progress_bar: gtk4::ProgressBar,
+        pub pause_button: gtk4::ToggleButton,
         pub status_label: gtk4::Label,
         pub is_converting: RefCell<bool>,
+        pub batch_queue: RefCell<Option<pixelconvert_core::BatchQueue>>,
         pub output_dir: RefCell<Option<std::path::PathBuf>>,
         pub output_dir_label: gtk4::Label,
+        pub png_row: adw::ExpanderRow,
+        pub png_level_row: adw::SpinRow,
+        pub png_filter_row: adw::SwitchRow,
+        pub png_reduce_row: adw::SwitchRow,
+        pub png_strip_row: adw::SwitchRow,
+        pub png_zopfli_row: adw::SwitchRow,
+        pub palette_row: adw::ExpanderRow,
+        pub palette_colors_row: adw::SpinRow,
+        pub palette_dither_row: adw::ComboRow,
+        pub palette_alpha_row: adw::SpinRow,
+        pub alpha_row: adw::ComboRow,
+        pub background_button: gtk4::ColorDialogButton,
+        pub trim_row: adw::ExpanderRow,
+        pub trim_mode_row: adw::ComboRow,
+        pub trim_tolerance_row: adw::SpinRow,
+        pub trim_padding_row: adw::SpinRow,
+        pub orientation_row: adw::ExpanderRow,
+        pub rotation_row: adw::ComboRow,
+        pub angle_row: adw::SpinRow,
+        pub auto_crop_row: adw::SwitchRow,
+        pub flip_horizontal_row: adw::SwitchRow,
+        pub flip_vertical_row: adw::SwitchRow,
+        pub canvas_row: adw::ExpanderRow,
+        pub canvas_size_row: adw::ComboRow,
+        pub canvas_width_row: adw::SpinRow,
+        pub canvas_height_row: adw::SpinRow,
+        pub canvas_gravity_row: adw::ComboRow,
+        pub canvas_fill_row: adw::ComboRow,
+        pub watermark_row: adw::ExpanderRow,
+        pub watermark_path: RefCell<Option<std::path::PathBuf>>,
+        pub watermark_label: gtk4::Label,
+        pub watermark_gravity_row: adw::ComboRow,
+        pub watermark_offset_x_row: adw::SpinRow,
+        pub watermark_offset_y_row: adw::SpinRow,
+        pub watermark_size_row: adw::SpinRow,
+        pub watermark_opacity_row: adw::SpinRow,
+        pub watermark_tile_row: adw::SwitchRow,
+        pub watermark_blend_row: adw::ComboRow,
+        pub isolate_row: adw::ExpanderRow,
+        pub timeout_row: adw::SpinRow,
+        pub incremental_row: adw::SwitchRow,
+        pub watch_row: adw::ExpanderRow,
+        pub watch_folders: RefCell<Vec<std::path::PathBuf>>,
+        pub watch_folders_label: gtk4::Label,
+        pub watch_preset: RefCell<Option<pixelconvert_core::ConversionOptions>>,
+        pub watch_preset_label: gtk4::Label,
+        pub watcher: RefCell<Option<crate::watcher::FolderWatcher>>,
     }
 
     #[glib::object_subclass]
@@ -38,7 +93,7 @@ mod imp {
                 content_stack: gtk4::Stack::new(),
                 status_page: adw::StatusPage::new(),
                 main_view: gtk4::Box::new(gtk4::Orientation::Vertical, 0),
-                selected_files: RefCell::new(Vec::new()),
+                files: gio::ListStore::new::<FileRow>(),
                 format_dropdown: gtk4::DropDown::from_strings(&[]),
                 quality_scale: gtk4::Scale::with_range(
                     gtk4::Orientation::Horizontal,
@@ -47,12 +102,65 @@ mod imp {
                     1.0,
                 ),
                 convert_button: gtk4::Button::new(),
+                retry_button: gtk4::Button::with_label("Retry Failed"),
+                report_button: gtk4::Button::with_label("Copy Error Report"),
+                export_button: gtk4::Button::with_label("Export Report"),
+                last_report: RefCell::new(None),
                 file_list: gtk4::ListBox::new(),
                 progress_bar: gtk4::ProgressBar::new(),
+                pause_button: gtk4::ToggleButton::new(),
                 status_label: gtk4::Label::new(None),
                 is_converting: RefCell::new(false),
+                batch_queue: RefCell::new(None),
                 output_dir: RefCell::new(None),
                 output_dir_label: gtk4::Label::new(Some("Same as source")),
+                png_row: adw::ExpanderRow::new(),
+                png_level_row: adw::SpinRow::with_range(0.0, 6.0, 1.0),
+                png_filter_row: adw::SwitchRow::new(),
+                png_reduce_row: adw::SwitchRow::new(),
+                png_strip_row: adw::SwitchRow::new(),
+                png_zopfli_row: adw::SwitchRow::new(),
+                palette_row: adw::ExpanderRow::new(),
+                palette_colors_row: adw::SpinRow::with_range(2.0, 256.0, 1.0),
+                palette_dither_row: adw::ComboRow::new(),
+                palette_alpha_row: adw::SpinRow::with_range(0.0, 255.0, 1.0),
+                alpha_row: adw::ComboRow::new(),
+                background_button: gtk4::ColorDialogButton::new(Some(gtk4::ColorDialog::new())),
+                trim_row: adw::ExpanderRow::new(),
+                trim_mode_row: adw::ComboRow::new(),
+                trim_tolerance_row: adw::SpinRow::with_range(0.0, 255.0, 1.0),
+                trim_padding_row: adw::SpinRow::with_range(0.0, 1000.0, 1.0),
+                orientation_row: adw::ExpanderRow::new(),
+                rotation_row: adw::ComboRow::new(),
+                angle_row: adw::SpinRow::with_range(-180.0, 180.0, 0.5),
+                auto_crop_row: adw::SwitchRow::new(),
+                flip_horizontal_row: adw::SwitchRow::new(),
+                flip_vertical_row: adw::SwitchRow::new(),
+                canvas_row: adw::ExpanderRow::new(),
+                canvas_size_row: adw::ComboRow::new(),
+                canvas_width_row: adw::SpinRow::with_range(1.0, 16384.0, 1.0),
+                canvas_height_row: adw::SpinRow::with_range(1.0, 16384.0, 1.0),
+                canvas_gravity_row: adw::ComboRow::new(),
+                canvas_fill_row: adw::ComboRow::new(),
+                watermark_row: adw::ExpanderRow::new(),
+                watermark_path: RefCell::new(None),
+                watermark_label: gtk4::Label::new(Some("None")),
+                watermark_gravity_row: adw::ComboRow::new(),
+                watermark_offset_x_row: adw::SpinRow::with_range(-50.0, 50.0, 0.5),
+                watermark_offset_y_row: adw::SpinRow::with_range(-50.0, 50.0, 0.5),
+                watermark_size_row: adw::SpinRow::with_range(1.0, 100.0, 1.0),
+                watermark_opacity_row: adw::SpinRow::with_range(0.0, 100.0, 5.0),
+                watermark_tile_row: adw::SwitchRow::new(),
+                watermark_blend_row: adw::ComboRow::new(),
+                isolate_row: adw::ExpanderRow::new(),
+                timeout_row: adw::SpinRow::with_range(0.0, 3600.0, 5.0),
+                incremental_row: adw::SwitchRow::new(),
+                watch_row: adw::ExpanderRow::new(),
+                watch_folders: RefCell::new(Vec::new()),
+                watch_folders_label: gtk4::Label::new(Some("None")),
+                watch_preset: RefCell::new(None),
+                watch_preset_label: gtk4::Label::new(Some("Current settings")),
+                watcher: RefCell::new(None),
             }
         }
     }
@@ -126,6 +234,61 @@ mod imp {
             ));
             header_box.append(&clear_button);
 
+            let favicon_button = gtk4::Button::with_label("Favicon Bundle");
+            favicon_button.set_icon_name("applications-internet-symbolic");
+            favicon_button.set_tooltip_text(Some(
+                "Generate favicon.ico, touch icons and a web manifest for each file",
+            ));
+            favicon_button.connect_clicked(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().generate_favicon_bundles();
+                }
+            ));
+            header_box.append(&favicon_button);
+
+            // Failure actions, shown once a conversion has failures
+            self.retry_button.set_icon_name("view-refresh-symbolic");
+            self.retry_button
+                .set_tooltip_text(Some("Convert the failed files again"));
+            self.retry_button.set_visible(false);
+            self.retry_button.connect_clicked(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().retry_failed();
+                }
+            ));
+            header_box.append(&self.retry_button);
+
+            self.report_button.set_icon_name("edit-copy-symbolic");
+            self.report_button
+                .set_tooltip_text(Some("Copy the failed files and their errors"));
+            self.report_button.set_visible(false);
+            self.report_button.connect_clicked(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().copy_error_report();
+                }
+            ));
+            header_box.append(&self.report_button);
+
+            // Shown once a conversion has finished
+            self.export_button.set_icon_name("document-save-symbolic");
+            self.export_button
+                .set_tooltip_text(Some("Save sizes, timings and errors of the last conversion"));
+            self.export_button.set_visible(false);
+            self.export_button.connect_clicked(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().export_report();
+                }
+            ));
+            header_box.append(&self.export_button);
+
             self.main_view.append(&header_box);
 
             // File list in scrolled window
@@ -137,6 +300,10 @@ mod imp {
 
             self.file_list.set_css_classes(&["boxed-list"]);
             self.file_list.set_selection_mode(gtk4::SelectionMode::None);
+            self.file_list.bind_model(Some(&self.files), |item| {
+                let file = item.downcast_ref::<FileRow>().unwrap();
+                file.widget().clone().upcast()
+            });
             scrolled.set_child(Some(&self.file_list));
             self.main_view.append(&scrolled);
 
@@ -170,6 +337,369 @@ mod imp {
             quality_row.add_suffix(&self.quality_scale);
             controls_group.add(&quality_row);
 
+            // PNG optimization (only shown for PNG output)
+            let png_defaults = pixelconvert_core::PngOptimization::default();
+            self.png_row.set_title("Optimize PNG");
+            self.png_row
+                .set_subtitle("Lossless recompression, replaces running oxipng afterwards");
+            self.png_row.set_show_enable_switch(true);
+            self.png_row.set_enable_expansion(true);
+            self.png_row.set_expanded(false);
+
+            self.png_level_row.set_title("Optimization Level");
+            self.png_level_row
+                .set_subtitle("Higher levels try more strategies but take longer");
+            self.png_level_row.set_value(png_defaults.level as f64);
+            self.png_row.add_row(&self.png_level_row);
+
+            self.png_filter_row.set_title("Search All Filters");
+            self.png_filter_row
+                .set_subtitle("Try every row filter strategy and keep the smallest");
+            self.png_filter_row.set_active(png_defaults.filter_search);
+            self.png_row.add_row(&self.png_filter_row);
+
+            self.png_reduce_row.set_title("Reduce Colour Type");
+            self.png_reduce_row
+                .set_subtitle("Drop unused alpha, convert to grayscale or palette when lossless");
+            self.png_reduce_row.set_active(png_defaults.reduce);
+            self.png_row.add_row(&self.png_reduce_row);
+
+            self.png_strip_row.set_title("Strip Metadata");
+            self.png_strip_row
+                .set_subtitle("Remove ancillary chunks that don't affect display");
+            self.png_strip_row.set_active(png_defaults.strip_metadata);
+            self.png_row.add_row(&self.png_strip_row);
+
+            self.png_zopfli_row.set_title("Zopfli Compression");
+            self.png_zopfli_row
+                .set_subtitle("Smallest files, but much slower");
+            self.png_zopfli_row.set_active(png_defaults.zopfli);
+            self.png_row.add_row(&self.png_zopfli_row);
+
+            controls_group.add(&self.png_row);
+
+            // Palette quantization (PNG-8 and GIF)
+            let palette_defaults = pixelconvert_core::Quantization::default();
+            self.palette_row.set_title("Reduce to Palette");
+            self.palette_row
+                .set_subtitle("Indexed PNG-8 or GIF with a limited number of colours");
+            self.palette_row.set_show_enable_switch(true);
+            self.palette_row.set_enable_expansion(false);
+
+            self.palette_colors_row.set_title("Maximum Colours");
+            self.palette_colors_row
+                .set_value(palette_defaults.max_colors as f64);
+            self.palette_row.add_row(&self.palette_colors_row);
+
+            let dither_modes: Vec<String> = pixelconvert_core::DitherMode::all()
+                .iter()
+                .map(|mode| mode.display_name().to_string())
+                .collect();
+            let dither_names: Vec<&str> = dither_modes.iter().map(String::as_str).collect();
+            self.palette_dither_row.set_title("Dithering");
+            self.palette_dither_row
+                .set_model(Some(&gtk4::StringList::new(&dither_names)));
+            self.palette_dither_row.set_selected(
+                pixelconvert_core::DitherMode::all()
+                    .iter()
+                    .position(|mode| *mode == palette_defaults.dither)
+                    .unwrap_or(0) as u32,
+            );
+            self.palette_row.add_row(&self.palette_dither_row);
+
+            self.palette_alpha_row.set_title("Alpha Threshold");
+            self.palette_alpha_row
+                .set_subtitle("Pixels less opaque than this become fully transparent");
+            self.palette_alpha_row
+                .set_value(palette_defaults.alpha_threshold as f64);
+            self.palette_row.add_row(&self.palette_alpha_row);
+
+            controls_group.add(&self.palette_row);
+
+            // Transparency handling
+            let alpha_policies: Vec<String> = pixelconvert_core::AlphaPolicy::all()
+                .iter()
+                .map(|policy| policy.display_name().to_string())
+                .collect();
+            let alpha_names: Vec<&str> = alpha_policies.iter().map(String::as_str).collect();
+            self.alpha_row.set_title("Transparency");
+            self.alpha_row
+                .set_model(Some(&gtk4::StringList::new(&alpha_names)));
+            self.alpha_row.connect_selected_notify(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().update_alpha_subtitle();
+                }
+            ));
+            self.update_alpha_subtitle();
+            controls_group.add(&self.alpha_row);
+
+            let background_row = adw::ActionRow::new();
+            background_row.set_title("Background Colour");
+            background_row.set_subtitle("Used when flattening transparent images and for canvases");
+            if let Some(dialog) = self.background_button.dialog() {
+                dialog.set_with_alpha(false);
+                dialog.set_title("Background Colour");
+            }
+            self.background_button
+                .set_rgba(&gdk::RGBA::new(1.0, 1.0, 1.0, 1.0));
+            self.background_button.set_valign(gtk4::Align::Center);
+            background_row.add_suffix(&self.background_button);
+            controls_group.add(&background_row);
+
+            // Border trimming
+            let trim_defaults = pixelconvert_core::Trim::default();
+            self.trim_row.set_title("Trim Borders");
+            self.trim_row
+                .set_subtitle("Remove uniform margins, after each file's crop");
+            self.trim_row.set_show_enable_switch(true);
+            self.trim_row.set_enable_expansion(false);
+
+            let trim_modes: Vec<String> = pixelconvert_core::TrimMode::all()
+                .iter()
+                .map(|mode| mode.display_name().to_string())
+                .collect();
+            let trim_mode_names: Vec<&str> = trim_modes.iter().map(String::as_str).collect();
+            self.trim_mode_row.set_title("Border");
+            self.trim_mode_row
+                .set_model(Some(&gtk4::StringList::new(&trim_mode_names)));
+            self.trim_row.add_row(&self.trim_mode_row);
+
+            self.trim_tolerance_row.set_title("Tolerance");
+            self.trim_tolerance_row
+                .set_subtitle("How far colours may stray from the border and still be trimmed");
+            self.trim_tolerance_row
+                .set_value(trim_defaults.tolerance as f64);
+            self.trim_row.add_row(&self.trim_tolerance_row);
+
+            self.trim_padding_row.set_title("Padding");
+            self.trim_padding_row
+                .set_subtitle("Pixels of border added back on each side");
+            self.trim_padding_row
+                .set_value(trim_defaults.padding as f64);
+            self.trim_row.add_row(&self.trim_padding_row);
+
+            controls_group.add(&self.trim_row);
+
+            // Rotation and flips for the whole batch, on top of each
+            // file's own quarter turns
+            self.orientation_row.set_title("Rotate and Flip");
+            self.orientation_row
+                .set_subtitle("Applied to every file, after its crop");
+
+            self.rotation_row.set_title("Rotation");
+            self.rotation_row.set_model(Some(&gtk4::StringList::new(&[
+                "None",
+                "90° Clockwise",
+                "180°",
+                "90° Counter-clockwise",
+            ])));
+            self.orientation_row.add_row(&self.rotation_row);
+
+            self.angle_row.set_title("Angle");
+            self.angle_row.set_subtitle(
+                "Degrees clockwise; uncovered corners are transparent or the background colour",
+            );
+            self.angle_row.set_digits(1);
+            self.orientation_row.add_row(&self.angle_row);
+
+            self.auto_crop_row.set_title("Crop to Fit");
+            self.auto_crop_row
+                .set_subtitle("Trim an angled image to the largest rectangle inside it");
+            self.orientation_row.add_row(&self.auto_crop_row);
+
+            self.flip_horizontal_row.set_title("Flip Horizontally");
+            self.orientation_row.add_row(&self.flip_horizontal_row);
+            self.flip_vertical_row.set_title("Flip Vertically");
+            self.orientation_row.add_row(&self.flip_vertical_row);
+
+            controls_group.add(&self.orientation_row);
+
+            // Canvas extension
+            self.canvas_row.set_title("Pad to Canvas");
+            self.canvas_row
+                .set_subtitle("Letterbox to a ratio or size without scaling or cropping");
+            self.canvas_row.set_show_enable_switch(true);
+            self.canvas_row.set_enable_expansion(false);
+
+            self.canvas_size_row.set_title("Canvas");
+            self.canvas_size_row
+                .set_subtitle("Width and height are a ratio or pixels");
+            self.canvas_size_row
+                .set_model(Some(&gtk4::StringList::new(&[
+                    "Aspect Ratio",
+                    "Exact Size",
+                ])));
+            self.canvas_row.add_row(&self.canvas_size_row);
+
+            self.canvas_width_row.set_title("Width");
+            self.canvas_width_row.set_value(1.0);
+            self.canvas_row.add_row(&self.canvas_width_row);
+            self.canvas_height_row.set_title("Height");
+            self.canvas_height_row.set_value(1.0);
+            self.canvas_row.add_row(&self.canvas_height_row);
+
+            let gravities: Vec<String> = pixelconvert_core::Gravity::all()
+                .iter()
+                .map(|gravity| gravity.display_name().to_string())
+                .collect();
+            let gravity_names: Vec<&str> = gravities.iter().map(String::as_str).collect();
+            self.canvas_gravity_row.set_title("Position");
+            self.canvas_gravity_row
+                .set_model(Some(&gtk4::StringList::new(&gravity_names)));
+            self.canvas_gravity_row.set_selected(
+                pixelconvert_core::Gravity::all()
+                    .iter()
+                    .position(|gravity| *gravity == pixelconvert_core::Gravity::Center)
+                    .unwrap_or(0) as u32,
+            );
+            self.canvas_row.add_row(&self.canvas_gravity_row);
+
+            let canvas_fills: Vec<String> = pixelconvert_core::CanvasFill::all()
+                .iter()
+                .map(|fill| fill.display_name().to_string())
+                .collect();
+            let canvas_fill_names: Vec<&str> = canvas_fills.iter().map(String::as_str).collect();
+            self.canvas_fill_row.set_title("Fill");
+            self.canvas_fill_row
+                .set_model(Some(&gtk4::StringList::new(&canvas_fill_names)));
+            self.canvas_row.add_row(&self.canvas_fill_row);
+
+            controls_group.add(&self.canvas_row);
+
+            // Watermark, sized and placed relative to the image width
+            let watermark_defaults = pixelconvert_core::Watermark::default();
+            self.watermark_row.set_title("Watermark");
+            self.watermark_row
+                .set_subtitle("Place a logo on every image, after the other edits");
+            self.watermark_row.set_show_enable_switch(true);
+            self.watermark_row.set_enable_expansion(false);
+
+            let watermark_file_row = adw::ActionRow::new();
+            watermark_file_row.set_title("Logo");
+            watermark_file_row.set_subtitle("PNG or SVG, ideally with transparency");
+            self.watermark_label
+                .set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+            self.watermark_label.set_max_width_chars(30);
+            self.watermark_label.set_valign(gtk4::Align::Center);
+            self.watermark_label.set_css_classes(&["dim-label"]);
+            let choose_watermark_button = gtk4::Button::with_label("Browse");
+            choose_watermark_button.set_valign(gtk4::Align::Center);
+            choose_watermark_button.set_css_classes(&["flat"]);
+            choose_watermark_button.connect_clicked(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().pick_watermark();
+                }
+            ));
+            watermark_file_row.add_suffix(&self.watermark_label);
+            watermark_file_row.add_suffix(&choose_watermark_button);
+            self.watermark_row.add_row(&watermark_file_row);
+
+            self.watermark_gravity_row.set_title("Position");
+            self.watermark_gravity_row
+                .set_model(Some(&gtk4::StringList::new(&gravity_names)));
+            self.watermark_gravity_row.set_selected(
+                pixelconvert_core::Gravity::all()
+                    .iter()
+                    .position(|gravity| *gravity == watermark_defaults.gravity)
+                    .unwrap_or(0) as u32,
+            );
+            self.watermark_row.add_row(&self.watermark_gravity_row);
+
+            self.watermark_offset_x_row.set_title("Horizontal Offset");
+            self.watermark_offset_x_row
+                .set_subtitle("Percent of the image width from the edge");
+            self.watermark_offset_x_row.set_digits(1);
+            self.watermark_offset_x_row
+                .set_value(watermark_defaults.offset_x as f64 * 100.0);
+            self.watermark_row.add_row(&self.watermark_offset_x_row);
+
+            self.watermark_offset_y_row.set_title("Vertical Offset");
+            self.watermark_offset_y_row
+                .set_subtitle("Percent of the image width from the edge");
+            self.watermark_offset_y_row.set_digits(1);
+            self.watermark_offset_y_row
+                .set_value(watermark_defaults.offset_y as f64 * 100.0);
+            self.watermark_row.add_row(&self.watermark_offset_y_row);
+
+            self.watermark_size_row.set_title("Size");
+            self.watermark_size_row
+                .set_subtitle("Percent of the image width");
+            self.watermark_size_row
+                .set_value(watermark_defaults.scale as f64 * 100.0);
+            self.watermark_row.add_row(&self.watermark_size_row);
+
+            self.watermark_opacity_row.set_title("Opacity");
+            self.watermark_opacity_row.set_subtitle("Percent");
+            self.watermark_opacity_row
+                .set_value(watermark_defaults.opacity as f64 * 100.0);
+            self.watermark_row.add_row(&self.watermark_opacity_row);
+
+            self.watermark_tile_row.set_title("Tile");
+            self.watermark_tile_row
+                .set_subtitle("Repeat the logo across the whole image");
+            self.watermark_row.add_row(&self.watermark_tile_row);
+
+            let blend_modes: Vec<String> = pixelconvert_core::BlendMode::all()
+                .iter()
+                .map(|mode| mode.display_name().to_string())
+                .collect();
+            let blend_names: Vec<&str> = blend_modes.iter().map(String::as_str).collect();
+            self.watermark_blend_row.set_title("Blend Mode");
+            self.watermark_blend_row
+                .set_model(Some(&gtk4::StringList::new(&blend_names)));
+            self.watermark_row.add_row(&self.watermark_blend_row);
+
+            let watermark_preview_row = adw::ActionRow::new();
+            watermark_preview_row.set_title("Preview");
+            watermark_preview_row.set_subtitle("The first file with the current settings");
+            let preview_button = gtk4::Button::with_label("Show");
+            preview_button.set_valign(gtk4::Align::Center);
+            preview_button.set_css_classes(&["flat"]);
+            preview_button.connect_clicked(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().preview_first_file();
+                }
+            ));
+            watermark_preview_row.add_suffix(&preview_button);
+            self.watermark_row.add_row(&watermark_preview_row);
+
+            controls_group.add(&self.watermark_row);
+
+            // Worker processes
+            self.isolate_row.set_title("Isolate Conversions");
+            self.isolate_row
+                .set_subtitle("Run each file in its own process so a crash fails only that file");
+            self.isolate_row.set_show_enable_switch(true);
+            self.isolate_row.set_enable_expansion(false);
+
+            self.timeout_row.set_title("Timeout");
+            self.timeout_row
+                .set_subtitle("Seconds before a conversion is stopped, 0 for no limit");
+            self.timeout_row.set_value(120.0);
+            self.isolate_row.add_row(&self.timeout_row);
+
+            controls_group.add(&self.isolate_row);
+
+            self.incremental_row.set_title("Skip Up-to-Date Files");
+            self.incremental_row
+                .set_subtitle("Only convert new or changed files");
+            controls_group.add(&self.incremental_row);
+
+            self.format_dropdown.connect_selected_notify(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().update_format_rows();
+                }
+            ));
+            self.update_format_rows();
+
             // Output directory row
             let output_dir_row = adw::ActionRow::new();
             output_dir_row.set_title("Output Directory");
@@ -210,6 +740,118 @@ mod imp {
             output_dir_row.add_suffix(&clear_dir_button);
             controls_group.add(&output_dir_row);
 
+            // Watch folders: convert images as they're dropped into folders
+            self.watch_row.set_title("Watch Folders");
+            self.watch_row
+                .set_subtitle("Convert new and changed images automatically");
+            self.watch_row.set_show_enable_switch(true);
+            self.watch_row.set_enable_expansion(false);
+            self.watch_row.connect_enable_expansion_notify(glib::clone!(
+                #[weak]
+                obj,
+                move |row| {
+                    if row.enables_expansion() {
+                        obj.imp().start_watching();
+                    } else {
+                        obj.imp().stop_watching();
+                    }
+                }
+            ));
+
+            let watch_folders_row = adw::ActionRow::new();
+            watch_folders_row.set_title("Folders");
+            self.watch_folders_label
+                .set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+            self.watch_folders_label.set_max_width_chars(30);
+            self.watch_folders_label.set_valign(gtk4::Align::Center);
+            self.watch_folders_label.set_css_classes(&["dim-label"]);
+
+            let add_folder_button = gtk4::Button::with_label("Add");
+            add_folder_button.set_valign(gtk4::Align::Center);
+            add_folder_button.set_css_classes(&["flat"]);
+            add_folder_button.connect_clicked(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().pick_watch_folder();
+                }
+            ));
+
+            let clear_folders_button = gtk4::Button::from_icon_name("edit-clear-symbolic");
+            clear_folders_button.set_valign(gtk4::Align::Center);
+            clear_folders_button.set_css_classes(&["flat", "circular"]);
+            clear_folders_button.set_tooltip_text(Some("Stop watching all folders"));
+            clear_folders_button.connect_clicked(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().watch_folders.borrow_mut().clear();
+                    obj.imp().watch_folders_changed();
+                }
+            ));
+
+            watch_folders_row.add_suffix(&self.watch_folders_label);
+            watch_folders_row.add_suffix(&add_folder_button);
+            watch_folders_row.add_suffix(&clear_folders_button);
+            self.watch_row.add_row(&watch_folders_row);
+
+            let watch_preset_row = adw::ActionRow::new();
+            watch_preset_row.set_title("Preset");
+            watch_preset_row.set_subtitle("Settings for watched images");
+            self.watch_preset_label
+                .set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+            self.watch_preset_label.set_max_width_chars(30);
+            self.watch_preset_label.set_valign(gtk4::Align::Center);
+            self.watch_preset_label.set_css_classes(&["dim-label"]);
+
+            let choose_preset_button = gtk4::Button::with_label("Browse");
+            choose_preset_button.set_valign(gtk4::Align::Center);
+            choose_preset_button.set_css_classes(&["flat"]);
+            choose_preset_button.connect_clicked(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().pick_watch_preset();
+                }
+            ));
+
+            let clear_preset_button = gtk4::Button::from_icon_name("edit-clear-symbolic");
+            clear_preset_button.set_valign(gtk4::Align::Center);
+            clear_preset_button.set_css_classes(&["flat", "circular"]);
+            clear_preset_button.set_tooltip_text(Some("Use the current settings"));
+            clear_preset_button.connect_clicked(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    *obj.imp().watch_preset.borrow_mut() = None;
+                    obj.imp().watch_preset_label.set_text("Current settings");
+                    obj.imp().restart_watching();
+                }
+            ));
+
+            watch_preset_row.add_suffix(&self.watch_preset_label);
+            watch_preset_row.add_suffix(&choose_preset_button);
+            watch_preset_row.add_suffix(&clear_preset_button);
+            self.watch_row.add_row(&watch_preset_row);
+
+            let watch_history_row = adw::ActionRow::new();
+            watch_history_row.set_title("History");
+            watch_history_row.set_subtitle("Recently converted watched images");
+            let history_button = gtk4::Button::with_label("Show");
+            history_button.set_valign(gtk4::Align::Center);
+            history_button.set_css_classes(&["flat"]);
+            history_button.connect_clicked(glib::clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().show_watch_history();
+                }
+            ));
+            watch_history_row.add_suffix(&history_button);
+            self.watch_row.add_row(&watch_history_row);
+
+            controls_group.add(&self.watch_row);
+
             self.main_view.append(&controls_group);
 
             // Convert button
@@ -232,12 +874,33 @@ mod imp {
             button_box.append(&self.convert_button);
             self.main_view.append(&button_box);
 
-            // Progress bar
+            // Progress bar, with a pause toggle while converting
+            let progress_box = gtk4::Box::new(gtk4::Orientation::Horizontal, 12);
+            progress_box.set_margin_top(12);
+            progress_box.set_margin_start(24);
+            progress_box.set_margin_end(24);
             self.progress_bar.set_visible(false);
-            self.progress_bar.set_margin_top(12);
-            self.progress_bar.set_margin_start(24);
-            self.progress_bar.set_margin_end(24);
-            self.main_view.append(&self.progress_bar);
+            self.progress_bar.set_hexpand(true);
+            self.progress_bar.set_valign(gtk4::Align::Center);
+            self.progress_bar
+                .bind_property("visible", &progress_box, "visible")
+                .sync_create()
+                .build();
+            progress_box.append(&self.progress_bar);
+
+            self.pause_button.set_icon_name("media-playback-pause-symbolic");
+            self.pause_button.set_tooltip_text(Some("Pause after the running files"));
+            self.pause_button.set_css_classes(&["flat", "circular"]);
+            self.pause_button.set_visible(false);
+            self.pause_button.connect_toggled(glib::clone!(
+                #[weak]
+                obj,
+                move |button| {
+                    obj.imp().toggle_pause(button.is_active());
+                }
+            ));
+            progress_box.append(&self.pause_button);
+            self.main_view.append(&progress_box);
 
             // Status label
             self.status_label.set_visible(false);
@@ -288,35 +951,291 @@ mod imp {
     impl AdwApplicationWindowImpl for PixelConvertWindow {}
 
     impl PixelConvertWindow {
-        fn setup_drag_drop(&self, window: &super::PixelConvertWindow) {
-            let drop_target =
-                gtk4::DropTarget::new(gio::File::static_type(), gtk4::gdk::DragAction::COPY);
+        fn selected_format(&self) -> pixelconvert_core::SupportedFormat {
+            use pixelconvert_core::SupportedFormat;
+            match self.format_dropdown.selected() {
+                0 => SupportedFormat::Png,
+                1 => SupportedFormat::Jpeg,
+                2 => SupportedFormat::WebP,
+                3 => SupportedFormat::Avif,
+                4 => SupportedFormat::Gif,
+                5 => SupportedFormat::Bmp,
+                6 => SupportedFormat::Tiff,
+                7 => SupportedFormat::Ico,
+                _ => SupportedFormat::WebP,
+            }
+        }
 
-            drop_target.connect_drop(glib::clone!(
-                #[weak]
-                window,
-                #[upgrade_or]
-                false,
-                move |_, value, _, _| {
-                    if let Ok(file) = value.get::<gio::File>() {
-                        if let Some(path) = file.path() {
-                            window.imp().add_file(path);
-                            return true;
+        /// Show only the settings rows relevant to the selected output format
+        fn update_format_rows(&self) {
+            use pixelconvert_core::SupportedFormat;
+            let format = self.selected_format();
+            self.png_row.set_visible(format == SupportedFormat::Png);
+            self.palette_row.set_visible(matches!(
+                format,
+                SupportedFormat::Png | SupportedFormat::Gif
+            ));
+        }
+
+        /// Describe what the selected transparency policy does
+        fn update_alpha_subtitle(&self) {
+            use pixelconvert_core::AlphaPolicy;
+            let subtitle = match self.alpha_policy() {
+                AlphaPolicy::Keep => "JPEG and BMP are flattened onto the background colour",
+                AlphaPolicy::Flatten => "Every image is flattened onto the background colour",
+                AlphaPolicy::Checkerboard => "Every image is flattened onto a light checkerboard",
+                AlphaPolicy::Fail => "Transparent images fail to convert to JPEG and BMP",
+            };
+            self.alpha_row.set_subtitle(subtitle);
+        }
+
+        fn alpha_policy(&self) -> pixelconvert_core::AlphaPolicy {
+            use pixelconvert_core::AlphaPolicy;
+            AlphaPolicy::all()
+                .get(self.alpha_row.selected() as usize)
+                .copied()
+                .unwrap_or(AlphaPolicy::Keep)
+        }
+
+        /// Conversion options from the current settings rows
+        fn conversion_options(&self) -> pixelconvert_core::ConversionOptions {
+            use pixelconvert_core::{
+                BlendMode, Canvas, CanvasFill, CanvasSize, ConversionOptions, Gravity, Orientation,
+                Trim, TrimMode, Watermark,
+            };
+
+            let rgba = self.background_button.rgba();
+            let to_u8 = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+            ConversionOptions {
+                quality: self.quality_scale.value() as u8,
+                format: self.selected_format(),
+                png_optimization: self.png_optimization(),
+                quantization: self.quantization(),
+                alpha_policy: self.alpha_policy(),
+                background: [to_u8(rgba.red()), to_u8(rgba.green()), to_u8(rgba.blue())],
+                trim: self.trim_row.enables_expansion().then(|| Trim {
+                    mode: TrimMode::all()
+                        .get(self.trim_mode_row.selected() as usize)
+                        .copied()
+                        .unwrap_or(TrimMode::Corner),
+                    tolerance: self.trim_tolerance_row.value() as u8,
+                    padding: self.trim_padding_row.value() as u32,
+                }),
+                orientation: Orientation {
+                    quarter_turns: self.rotation_row.selected().min(3) as u8,
+                    angle: self.angle_row.value() as f32,
+                    auto_crop: self.auto_crop_row.is_active(),
+                    flip_horizontal: self.flip_horizontal_row.is_active(),
+                    flip_vertical: self.flip_vertical_row.is_active(),
+                },
+                canvas: self.canvas_row.enables_expansion().then(|| {
+                    let (width, height) = (
+                        self.canvas_width_row.value() as u32,
+                        self.canvas_height_row.value() as u32,
+                    );
+                    Canvas {
+                        size: match self.canvas_size_row.selected() {
+                            0 => CanvasSize::Ratio { width, height },
+                            _ => CanvasSize::Exact { width, height },
+                        },
+                        gravity: Gravity::all()
+                            .get(self.canvas_gravity_row.selected() as usize)
+                            .copied()
+                            .unwrap_or_default(),
+                        fill: CanvasFill::all()
+                            .get(self.canvas_fill_row.selected() as usize)
+                            .copied()
+                            .unwrap_or_default(),
+                    }
+                }),
+                watermark: self
+                    .watermark_path
+                    .borrow()
+                    .clone()
+                    .filter(|_| self.watermark_row.enables_expansion())
+                    .map(|path| Watermark {
+                        path,
+                        gravity: Gravity::all()
+                            .get(self.watermark_gravity_row.selected() as usize)
+                            .copied()
+                            .unwrap_or_default(),
+                        offset_x: self.watermark_offset_x_row.value() as f32 / 100.0,
+                        offset_y: self.watermark_offset_y_row.value() as f32 / 100.0,
+                        scale: self.watermark_size_row.value() as f32 / 100.0,
+                        opacity: self.watermark_opacity_row.value() as f32 / 100.0,
+                        tile: self.watermark_tile_row.is_active(),
+                        blend: BlendMode::all()
+                            .get(self.watermark_blend_row.selected() as usize)
+                            .copied()
+                            .unwrap_or_default(),
+                    }),
+                ..Default::default()
+            }
+        }
+
+        /// Conversion options for `file`: the current settings with the
+        /// file's own crop and quarter turns
+        fn file_options(&self, file: &FileRow) -> pixelconvert_core::ConversionOptions {
+            let options = self.conversion_options();
+            pixelconvert_core::ConversionOptions {
+                crop: file.crop(),
+                orientation: options.orientation.turned(file.quarter_turns() as i32),
+                ..options
+            }
+        }
+
+        /// Where batch jobs run, from the isolation settings
+        fn execution(&self) -> pixelconvert_core::Execution {
+            use pixelconvert_core::{worker::WorkerCommand, Execution};
+
+            if !self.isolate_row.enables_expansion() {
+                return Execution::InProcess;
+            }
+            match WorkerCommand::current_exe() {
+                Ok(worker) => Execution::Isolated {
+                    worker,
+                    timeout: (self.timeout_row.value() > 0.0)
+                        .then(|| std::time::Duration::from_secs_f64(self.timeout_row.value())),
+                },
+                Err(e) => {
+                    eprintln!("Can't isolate conversions: {}", e);
+                    Execution::InProcess
+                }
+            }
+        }
+
+        fn quantization(&self) -> Option<pixelconvert_core::Quantization> {
+            if !self.palette_row.enables_expansion() {
+                return None;
+            }
+
+            let dither = pixelconvert_core::DitherMode::all()
+                .get(self.palette_dither_row.selected() as usize)
+                .copied()
+                .unwrap_or(pixelconvert_core::DitherMode::FloydSteinberg);
+
+            Some(pixelconvert_core::Quantization {
+                max_colors: self.palette_colors_row.value() as u16,
+                dither,
+                alpha_threshold: self.palette_alpha_row.value() as u8,
+            })
+        }
+
+        fn png_optimization(&self) -> Option<pixelconvert_core::PngOptimization> {
+            if !self.png_row.enables_expansion() {
+                return None;
+            }
+
+            Some(pixelconvert_core::PngOptimization {
+                level: self.png_level_row.value() as u8,
+                filter_search: self.png_filter_row.is_active(),
+                reduce: self.png_reduce_row.is_active(),
+                strip_metadata: self.png_strip_row.is_active(),
+                zopfli: self.png_zopfli_row.is_active(),
+            })
+        }
+
+        /// Start converting images dropped into the watched folders, with
+        /// the chosen preset or the current settings
+        fn start_watching(&self) {
+            use pixelconvert_core::watch::{WatchFolder, WatchOutcome};
+
+            let folders = self.watch_folders.borrow().clone();
+            if folders.is_empty() {
+                self.toast_overlay
+                    .add_toast(adw::Toast::new("Add a folder to watch first"));
+                self.watch_row.set_enable_expansion(false);
+                return;
+            }
+
+            let watch = WatchFolder {
+                folders,
+                output_dir: self.output_dir.borrow().clone(),
+                options: self
+                    .watch_preset
+                    .borrow()
+                    .clone()
+                    .unwrap_or_else(|| self.conversion_options()),
+            };
+            let window = self.obj().downgrade();
+            let watcher = crate::watcher::FolderWatcher::start(
+                watch,
+                self.execution(),
+                move |entry| {
+                    let Some(window) = window.upgrade() else {
+                        return;
+                    };
+                    let imp = window.imp();
+                    let name = entry
+                        .input_path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy();
+                    match &entry.outcome {
+                        WatchOutcome::Converted { .. } => {
+                            imp.watch_row.set_subtitle(&format!("Converted {}", name));
+                        }
+                        WatchOutcome::Unchanged => {}
+                        WatchOutcome::Failed(error) => {
+                            eprintln!("Failed to convert {}: {}", name, error);
+                            imp.toast_overlay.add_toast(adw::Toast::new(&format!(
+                                "Failed to convert {}: {}",
+                                name, error
+                            )));
                         }
                     }
-                    false
+                },
+            );
+            match watcher {
+                Ok(watcher) => {
+                    *self.watcher.borrow_mut() = Some(watcher);
+                    self.watch_row.set_subtitle("Watching for new images");
                 }
-            ));
+                Err(e) => {
+                    self.toast_overlay
+                        .add_toast(adw::Toast::new(&format!("Can't watch folders: {}", e)));
+                    self.watch_row.set_enable_expansion(false);
+                }
+            }
+        }
 
-            window.add_controller(drop_target);
+        fn stop_watching(&self) {
+            self.watcher.borrow_mut().take();
+            self.watch_row
+                .set_subtitle("Convert new and changed images automatically");
         }
 
-        fn pick_output_dir(&self) {
+        /// Pick up changed folders or preset if watching
+        fn restart_watching(&self) {
+            if self.watcher.borrow().is_some() {
+                self.stop_watching();
+                self.start_watching();
+            }
+        }
+
+        fn watch_folders_changed(&self) {
+            let names: Vec<String> = self
+                .watch_folders
+                .borrow()
+                .iter()
+                .map(|folder| folder.to_string_lossy().into_owned())
+                .collect();
+            if names.is_empty() {
+                self.watch_folders_label.set_text("None");
+                self.watch_row.set_enable_expansion(false);
+            } else {
+                self.watch_folders_label.set_text(&names.join(", "));
+                self.restart_watching();
+            }
+        }
+
+        fn pick_watch_folder(&self) {
             let window = self.obj();
             let window_ref = window.upcast_ref::<gtk4::Window>();
 
             let dialog = gtk4::FileDialog::builder()
-                .title("Select Output Directory")
+                .title("Select Folder to Watch")
                 .modal(true)
                 .build();
 
@@ -327,317 +1246,1309 @@ mod imp {
                     #[weak(rename_to = imp)]
                     self,
                     move |result| {
-                        if let Ok(folder) = result {
-                            if let Some(path) = folder.path() {
-                                imp.output_dir_label.set_text(&path.to_string_lossy());
-                                *imp.output_dir.borrow_mut() = Some(path);
-                            }
+                        let Some(path) = result.ok().and_then(|folder| folder.path()) else {
+                            return;
+                        };
+                        if imp.watch_folders.borrow().contains(&path) {
+                            return;
                         }
+                        imp.watch_folders.borrow_mut().push(path);
+                        imp.watch_folders_changed();
                     }
                 ),
             );
         }
 
-        pub fn open_file_chooser(&self) {
+        fn pick_watch_preset(&self) {
             let window = self.obj();
             let window_ref = window.upcast_ref::<gtk4::Window>();
 
             let filter = gtk4::FileFilter::new();
-            filter.set_name(Some("Image Files"));
-            // Add individual MIME types for broader compatibility
-            filter.add_mime_type("image/png");
-            filter.add_mime_type("image/jpeg");
-            filter.add_mime_type("image/webp");
-            filter.add_mime_type("image/avif");
-            filter.add_mime_type("image/gif");
-            filter.add_mime_type("image/bmp");
-            filter.add_mime_type("image/tiff");
-            filter.add_mime_type("image/x-icon");
-            // Also add glob patterns as fallback
-            filter.add_suffix("png");
-            filter.add_suffix("jpg");
-            filter.add_suffix("jpeg");
-            filter.add_suffix("webp");
-            filter.add_suffix("avif");
-            filter.add_suffix("gif");
-            filter.add_suffix("bmp");
-            filter.add_suffix("tiff");
-            filter.add_suffix("tif");
-            filter.add_suffix("ico");
-
-            // Also add an "All Files" filter
-            let all_filter = gtk4::FileFilter::new();
-            all_filter.set_name(Some("All Files"));
-            all_filter.add_pattern("*");
-
+            filter.set_name(Some("Presets"));
+            filter.add_mime_type("application/json");
+            filter.add_suffix("json");
             let filters = gio::ListStore::new::<gtk4::FileFilter>();
             filters.append(&filter);
-            filters.append(&all_filter);
 
             let dialog = gtk4::FileDialog::builder()
-                .title("Select Images")
+                .title("Select Preset")
                 .modal(true)
                 .filters(&filters)
-                .default_filter(&filter)
                 .build();
 
-            dialog.open_multiple(
+            dialog.open(
                 Some(window_ref),
                 gio::Cancellable::NONE,
                 glib::clone!(
                     #[weak(rename_to = imp)]
                     self,
                     move |result| {
-                        if let Ok(files) = result {
-                            for i in 0..files.n_items() {
-                                if let Some(file) = files.item(i).and_downcast::<gio::File>() {
-                                    if let Some(path) = file.path() {
-                                        imp.add_file(path);
-                                    }
-                                }
+                        let Some(path) = result.ok().and_then(|file| file.path()) else {
+                            return;
+                        };
+                        match pixelconvert_core::ConversionOptions::load_preset(&path) {
+                            Ok(options) => {
+                                imp.watch_preset_label.set_text(&format!(
+                                    "{} ({})",
+                                    path.file_stem().unwrap_or_default().to_string_lossy(),
+                                    options.format.display_name()
+                                ));
+                                *imp.watch_preset.borrow_mut() = Some(options);
+                                imp.restart_watching();
                             }
+                            Err(e) => imp.toast_overlay.add_toast(adw::Toast::new(&format!(
+                                "Can't load {}: {}",
+                                path.display(),
+                                e
+                            ))),
                         }
                     }
                 ),
             );
         }
 
-        fn add_file(&self, path: std::path::PathBuf) {
-            // Check if it's an image file
-            if let Some(ext) = path.extension() {
-                let ext_str = ext.to_string_lossy().to_lowercase();
-                let valid_extensions = [
-                    "png", "jpg", "jpeg", "gif", "webp", "avif", "bmp", "tiff", "ico",
-                ];
-
-                if !valid_extensions.contains(&ext_str.as_str()) {
-                    return;
-                }
-            } else {
-                return;
+        fn pick_watermark(&self) {
+            let window = self.obj();
+            let window_ref = window.upcast_ref::<gtk4::Window>();
+
+            let filter = gtk4::FileFilter::new();
+            filter.set_name(Some("Logos"));
+            for mime_type in ["image/png", "image/svg+xml", "image/webp"] {
+                filter.add_mime_type(mime_type);
+            }
+            for suffix in ["png", "svg", "svgz", "webp"] {
+                filter.add_suffix(suffix);
             }
+            let filters = gio::ListStore::new::<gtk4::FileFilter>();
+            filters.append(&filter);
 
-            // Add to list if not already there
-            let mut files = self.selected_files.borrow_mut();
-            if !files.contains(&path) {
-                files.push(path.clone());
-
-                // Create row for file
-                let row = adw::ActionRow::new();
-                row.set_title(&path.file_name().unwrap().to_string_lossy());
-                row.set_subtitle(
-                    &path
-                        .parent()
-                        .unwrap_or(std::path::Path::new(""))
-                        .to_string_lossy(),
-                );
-
-                let remove_button = gtk4::Button::from_icon_name("user-trash-symbolic");
-                remove_button.set_valign(gtk4::Align::Center);
-                remove_button.set_css_classes(&["flat", "circular"]);
-
-                let path_clone = path.clone();
-                remove_button.connect_clicked(glib::clone!(
+            let dialog = gtk4::FileDialog::builder()
+                .title("Select Watermark")
+                .modal(true)
+                .filters(&filters)
+                .build();
+
+            dialog.open(
+                Some(window_ref),
+                gio::Cancellable::NONE,
+                glib::clone!(
                     #[weak(rename_to = imp)]
                     self,
-                    move |_| {
-                        imp.remove_file(&path_clone);
+                    move |result| {
+                        let Some(path) = result.ok().and_then(|file| file.path()) else {
+                            return;
+                        };
+                        imp.watermark_label
+                            .set_text(&path.file_name().unwrap_or_default().to_string_lossy());
+                        *imp.watermark_path.borrow_mut() = Some(path);
+                        imp.watermark_row.set_enable_expansion(true);
                     }
-                ));
+                ),
+            );
+        }
+
+        /// Show the first file as it will be converted with the current
+        /// settings, its crop and its rotation
+        fn preview_first_file(&self) {
+            use crate::preview::PreviewWidget;
+
+            let Some(file) = self.file_rows().into_iter().next() else {
+                self.toast_overlay
+                    .add_toast(adw::Toast::new("Add an image to preview"));
+                return;
+            };
+
+            let preview = PreviewWidget::new();
+            preview.set_crop(file.crop());
+            preview.set_options(self.conversion_options());
+            preview.set_turns(file.quarter_turns());
+            preview.load_original(file.path());
+            preview.widget().set_vexpand(true);
+            preview.widget().set_margin_top(12);
+            preview.widget().set_margin_bottom(12);
+            preview.widget().set_margin_start(12);
+            preview.widget().set_margin_end(12);
+
+            let toolbar = adw::ToolbarView::new();
+            toolbar.add_top_bar(&adw::HeaderBar::new());
+            toolbar.set_content(Some(preview.widget()));
+
+            let dialog = adw::Dialog::builder()
+                .title(format!(
+                    "Preview {}",
+                    file.path()
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                ))
+                .content_width(960)
+                .content_height(560)
+                .child(&toolbar)
+                .build();
+            dialog.present(Some(&*self.obj()));
+        }
+
+        /// Show the most recent watch-mode conversions
+        fn show_watch_history(&self) {
+            use pixelconvert_core::watch::{WatchHistory, WatchOutcome};
+
+            let entries = WatchHistory::default_path()
+                .map(WatchHistory::new)
+                .map(|history| history.recent(20))
+                .unwrap_or(Ok(Vec::new()));
+            let body = match entries {
+                Ok(entries) if entries.is_empty() => "Nothing has been converted yet".to_string(),
+                Ok(entries) => entries
+                    .iter()
+                    .rev()
+                    .map(|entry| {
+                        let time = entry
+                            .time
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .ok()
+                            .and_then(|since| {
+                                glib::DateTime::from_unix_local(since.as_secs() as i64).ok()
+                            })
+                            .and_then(|time| time.format("%x %X").ok())
+                            .map(|time| time.to_string())
+                            .unwrap_or_default();
+                        let name = entry
+                            .input_path
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy();
+                        let outcome = match &entry.outcome {
+                            WatchOutcome::Converted { output_bytes } => {
+                                format!("converted ({})", glib::format_size(*output_bytes))
+                            }
+                            WatchOutcome::Unchanged => "up to date".to_string(),
+                            WatchOutcome::Failed(error) => format!("failed: {}", error),
+                        };
+                        format!("{}  {} — {}", time, name, outcome)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                Err(e) => format!("Can't read the history: {}", e),
+            };
+
+            let dialog = adw::AlertDialog::new(Some("Watch History"), Some(&body));
+            dialog.add_response("close", "Close");
+            dialog.present(Some(&*self.obj()));
+        }
+
+        fn setup_drag_drop(&self, window: &super::PixelConvertWindow) {
+            let drop_target =
+                gtk4::DropTarget::new(gio::File::static_type(), gtk4::gdk::DragAction::COPY);
+
+            drop_target.connect_drop(glib::clone!(
+                #[weak]
+                window,
+                #[upgrade_or]
+                false,
+                move |_, value, _, _| {
+                    if let Ok(file) = value.get::<gio::File>() {
+                        if let Some(path) = file.path() {
+                            window.imp().add_file(path);
+                            return true;
+                        }
+                    }
+                    false
+                }
+            ));
+
+            window.add_controller(drop_target);
+        }
+
+        fn pick_output_dir(&self) {
+            let window = self.obj();
+            let window_ref = window.upcast_ref::<gtk4::Window>();
+
+            let dialog = gtk4::FileDialog::builder()
+                .title("Select Output Directory")
+                .modal(true)
+                .build();
+
+            dialog.select_folder(
+                Some(window_ref),
+                gio::Cancellable::NONE,
+                glib::clone!(
+                    #[weak(rename_to = imp)]
+                    self,
+                    move |result| {
+                        if let Ok(folder) = result {
+                            if let Some(path) = folder.path() {
+                                imp.output_dir_label.set_text(&path.to_string_lossy());
+                                *imp.output_dir.borrow_mut() = Some(path);
+                            }
+                        }
+                    }
+                ),
+            );
+        }
+
+        pub fn open_file_chooser(&self) {
+            let window = self.obj();
+            let window_ref = window.upcast_ref::<gtk4::Window>();
+
+            let filter = gtk4::FileFilter::new();
+            filter.set_name(Some("Image Files"));
+            // Add individual MIME types for broader compatibility
+            filter.add_mime_type("image/png");
+            filter.add_mime_type("image/jpeg");
+            filter.add_mime_type("image/webp");
+            filter.add_mime_type("image/avif");
+            filter.add_mime_type("image/gif");
+            filter.add_mime_type("image/bmp");
+            filter.add_mime_type("image/tiff");
+            filter.add_mime_type("image/x-icon");
+            // Also add glob patterns as fallback
+            filter.add_suffix("png");
+            filter.add_suffix("jpg");
+            filter.add_suffix("jpeg");
+            filter.add_suffix("webp");
+            filter.add_suffix("avif");
+            filter.add_suffix("gif");
+            filter.add_suffix("bmp");
+            filter.add_suffix("tiff");
+            filter.add_suffix("tif");
+            filter.add_suffix("ico");
+
+            // Also add an "All Files" filter
+            let all_filter = gtk4::FileFilter::new();
+            all_filter.set_name(Some("All Files"));
+            all_filter.add_pattern("*");
 
-                row.add_suffix(&remove_button);
-                self.file_list.append(&row);
+            let filters = gio::ListStore::new::<gtk4::FileFilter>();
+            filters.append(&filter);
+            filters.append(&all_filter);
+
+            let dialog = gtk4::FileDialog::builder()
+                .title("Select Images")
+                .modal(true)
+                .filters(&filters)
+                .default_filter(&filter)
+                .build();
+
+            dialog.open_multiple(
+                Some(window_ref),
+                gio::Cancellable::NONE,
+                glib::clone!(
+                    #[weak(rename_to = imp)]
+                    self,
+                    move |result| {
+                        if let Ok(files) = result {
+                            for i in 0..files.n_items() {
+                                if let Some(file) = files.item(i).and_downcast::<gio::File>() {
+                                    if let Some(path) = file.path() {
+                                        imp.add_file(path);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                ),
+            );
+        }
+
+        fn add_file(&self, path: std::path::PathBuf) {
+            use pixelconvert_core::{ImageConverter, SupportedFormat};
+
+            // Trust the contents over the extension, but fall back to the
+            // extension for files whose header we can't read. A readable
+            // header that isn't an image is rejected whatever the extension.
+            let by_extension = path
+                .extension()
+                .and_then(|ext| SupportedFormat::from_extension(&ext.to_string_lossy()));
+            let detected = match ImageConverter::detect_format(&path) {
+                Ok(Some(detected)) => Some(detected),
+                Ok(None) => return,
+                Err(e) => {
+                    eprintln!("Failed to read {}: {}", path.display(), e);
+                    None
+                }
+            };
+            let Some(format) = detected.or(by_extension) else {
+                return;
+            };
+
+            // Add to list if not already there
+            if self.file_rows().iter().any(|file| file.path() == path) {
+                return;
+            }
+            let file = FileRow::new(path.clone(), format);
+            let row = file.widget();
+
+            // Warn when the extension claims a different format than the contents
+            if let (Some(detected), Some(by_extension)) = (detected, by_extension) {
+                if detected != by_extension {
+                    let warning = gtk4::Image::from_icon_name("dialog-warning-symbolic");
+                    warning.set_css_classes(&["warning"]);
+                    warning.set_tooltip_text(Some(&format!(
+                        "Extension says {} but the file contains {}",
+                        by_extension.display_name(),
+                        detected.display_name()
+                    )));
+                    row.add_prefix(&warning);
+
+                    self.toast_overlay.add_toast(adw::Toast::new(&format!(
+                        "{} is actually a {} file",
+                        path.file_name().unwrap_or_default().to_string_lossy(),
+                        detected.display_name()
+                    )));
+                }
             }
 
-            // Show main view if we have files
-            if !files.is_empty() {
-                self.content_stack.set_visible_child_name("main");
+            let next_button = gtk4::Button::from_icon_name("go-top-symbolic");
+            next_button.set_valign(gtk4::Align::Center);
+            next_button.set_css_classes(&["flat", "circular"]);
+            next_button.set_tooltip_text(Some("Convert Next"));
+            next_button.connect_clicked(glib::clone!(
+                #[weak(rename_to = imp)]
+                self,
+                #[weak]
+                file,
+                move |_| {
+                    imp.promote_file(&file);
+                }
+            ));
+            file.add_queued_suffix(&next_button);
+
+            let crop_button = gtk4::Button::from_icon_name("edit-cut-symbolic");
+            crop_button.set_valign(gtk4::Align::Center);
+            crop_button.set_css_classes(&["flat", "circular"]);
+            crop_button.set_tooltip_text(Some("Crop"));
+            crop_button.connect_clicked(glib::clone!(
+                #[weak(rename_to = imp)]
+                self,
+                #[weak]
+                file,
+                move |_| {
+                    imp.crop_file(&file);
+                }
+            ));
+            row.add_suffix(&crop_button);
+
+            for (icon, tooltip, turns) in [
+                ("object-rotate-left-symbolic", "Rotate Left", -1),
+                ("object-rotate-right-symbolic", "Rotate Right", 1),
+            ] {
+                let rotate_button = gtk4::Button::from_icon_name(icon);
+                rotate_button.set_valign(gtk4::Align::Center);
+                rotate_button.set_css_classes(&["flat", "circular"]);
+                rotate_button.set_tooltip_text(Some(tooltip));
+                rotate_button.connect_clicked(glib::clone!(
+                    #[weak]
+                    file,
+                    move |_| file.rotate(turns)
+                ));
+                row.add_suffix(&rotate_button);
             }
-        }
 
-        fn remove_file(&self, path: &std::path::Path) {
-            let mut files = self.selected_files.borrow_mut();
-            if let Some(pos) = files.iter().position(|p| p == path) {
-                files.remove(pos);
+            let remove_button = gtk4::Button::from_icon_name("user-trash-symbolic");
+            remove_button.set_valign(gtk4::Align::Center);
+            remove_button.set_css_classes(&["flat", "circular"]);
+            remove_button.connect_clicked(glib::clone!(
+                #[weak(rename_to = imp)]
+                self,
+                #[weak]
+                file,
+                move |_| {
+                    imp.remove_file(&file);
+                }
+            ));
+            row.add_suffix(&remove_button);
+
+            // Drag rows onto each other to change the conversion order
+            let drag_source = gtk4::DragSource::new();
+            drag_source.set_actions(gdk::DragAction::MOVE);
+            drag_source.connect_prepare(glib::clone!(
+                #[weak]
+                file,
+                #[upgrade_or]
+                None,
+                move |_, _, _| Some(gdk::ContentProvider::for_value(&file.to_value()))
+            ));
+            row.add_controller(drag_source);
 
-                // Remove from UI
-                if let Some(row) = self.file_list.row_at_index(pos as i32) {
-                    self.file_list.remove(&row);
+            let drop_target = gtk4::DropTarget::new(FileRow::static_type(), gdk::DragAction::MOVE);
+            drop_target.connect_drop(glib::clone!(
+                #[weak(rename_to = imp)]
+                self,
+                #[weak]
+                file,
+                #[upgrade_or]
+                false,
+                move |_, value, _, _| {
+                    let Ok(dragged) = value.get::<FileRow>() else {
+                        return false;
+                    };
+                    imp.move_file(&dragged, &file);
+                    true
+                }
+            ));
+            row.add_controller(drop_target);
+
+            self.files.append(&file);
+            self.content_stack.set_visible_child_name("main");
+
+            // Files added during a conversion join it
+            if *self.is_converting.borrow() {
+                let queue = self.batch_queue.borrow().clone();
+                if let Some(index) = queue.and_then(|queue| queue.push(self.job_for(&file))) {
+                    file.set_job(Some(index));
+                    file.set_status(FileStatus::Queued);
                 }
             }
+        }
+
+        /// The files in the list, in order
+        fn file_rows(&self) -> Vec<FileRow> {
+            self.files
+                .iter::<FileRow>()
+                .filter_map(Result::ok)
+                .collect()
+        }
+
+        fn remove_file(&self, file: &FileRow) {
+            self.dequeue(file);
+            if let Some(position) = self.files.find(file) {
+                self.files.remove(position);
+            }
 
             // Switch back to empty view if no files
-            if files.is_empty() {
+            if self.files.n_items() == 0 {
                 self.content_stack.set_visible_child_name("empty");
             }
+            self.update_failure_actions();
         }
 
         pub fn clear_files(&self) {
-            self.selected_files.borrow_mut().clear();
-
-            // Remove all rows
-            while let Some(row) = self.file_list.row_at_index(0) {
-                self.file_list.remove(&row);
+            for file in self.file_rows() {
+                self.dequeue(&file);
             }
+            self.files.remove_all();
 
             self.content_stack.set_visible_child_name("empty");
+            self.update_failure_actions();
+            self.last_report.borrow_mut().take();
+            self.export_button.set_visible(false);
         }
 
-        pub fn start_conversion(&self) {
-            // Check if already converting
-            if *self.is_converting.borrow() {
-                return;
+        /// Take a queued file off the running batch
+        fn dequeue(&self, file: &FileRow) {
+            if let (Some(queue), Some(job)) = (self.batch_queue.borrow().as_ref(), file.job()) {
+                if file.is_queued() {
+                    queue.remove(job);
+                }
             }
+        }
 
-            let files = self.selected_files.borrow().clone();
-            if files.is_empty() {
+        /// Move `file` to where `target` is in the list, and run the
+        /// running batch's queued files in the new order
+        fn move_file(&self, file: &FileRow, target: &FileRow) {
+            let (Some(from), Some(to)) = (self.files.find(file), self.files.find(target)) else {
+                return;
+            };
+            if from == to {
                 return;
             }
+            self.files.remove(from);
+            self.files.insert(to, file);
+            self.reorder_queue();
+        }
 
-            // Get conversion settings
-            let format_idx = self.format_dropdown.selected();
-            let quality = self.quality_scale.value() as u8;
+        /// Make a queued file the next one converted, and move it up to
+        /// the first queued row
+        fn promote_file(&self, file: &FileRow) {
+            let (Some(queue), Some(job)) = (self.batch_queue.borrow().clone(), file.job()) else {
+                return;
+            };
+            if !queue.promote(job) {
+                return;
+            }
+            // The file itself is queued, so this is never below it
+            let first_queued = self.file_rows().iter().position(FileRow::is_queued);
+            if let (Some(from), Some(to)) = (self.files.find(file), first_queued) {
+                self.files.remove(from);
+                self.files.insert(to as u32, file);
+            }
+        }
 
-            // Map format index to SupportedFormat
-            use crate::converter::SupportedFormat;
-            let format = match format_idx {
-                0 => SupportedFormat::Png,
-                1 => SupportedFormat::Jpeg,
-                2 => SupportedFormat::WebP,
-                3 => SupportedFormat::Avif,
-                4 => SupportedFormat::Gif,
-                5 => SupportedFormat::Bmp,
-                6 => SupportedFormat::Tiff,
-                7 => SupportedFormat::Ico,
-                _ => SupportedFormat::WebP,
+        /// Hand the list order of the queued files to the running batch
+        fn reorder_queue(&self) {
+            let Some(queue) = self.batch_queue.borrow().clone() else {
+                return;
             };
+            let order: Vec<usize> = self
+                .file_rows()
+                .iter()
+                .filter(|file| file.is_queued())
+                .filter_map(FileRow::job)
+                .collect();
+            queue.reorder(&order);
+        }
 
-            // Mark as converting and disable button
-            *self.is_converting.borrow_mut() = true;
-            self.convert_button.set_sensitive(false);
-            self.progress_bar.set_visible(true);
-            self.progress_bar.set_fraction(0.0);
-            self.status_label.set_visible(true);
-            self.status_label.set_text("Starting conversion...");
+        /// Open the crop tool for a file, next to a preview with its
+        /// rotation. Both are kept with the file and applied whenever it's
+        /// converted.
+        fn crop_file(&self, file: &FileRow) {
+            use crate::preview::PreviewWidget;
+            use pixelconvert_core::{AspectRatio, Crop};
+            use std::rc::Rc;
+
+            let preview = Rc::new(PreviewWidget::new());
+            preview.set_crop_enabled(true);
+            preview.set_rotate_enabled(true);
+            preview.set_crop(file.crop());
+            preview.set_options(self.conversion_options());
+            preview.set_turns(file.quarter_turns());
+            preview.load_original(file.path());
+            preview.widget().set_vexpand(true);
+
+            // Aspect ratio presets, with spin buttons for a custom ratio
+            let ratios: Vec<String> = AspectRatio::all()
+                .iter()
+                .map(AspectRatio::display_name)
+                .collect();
+            let ratio_names: Vec<&str> = ratios.iter().map(String::as_str).collect();
+            let ratio_dropdown = gtk4::DropDown::from_strings(&ratio_names);
+            ratio_dropdown.set_tooltip_text(Some("Aspect Ratio"));
+
+            let custom_width = gtk4::SpinButton::with_range(1.0, 100.0, 1.0);
+            let custom_height = gtk4::SpinButton::with_range(1.0, 100.0, 1.0);
+            if let Some(AspectRatio::Custom { width, height }) = AspectRatio::all().last() {
+                custom_width.set_value(*width as f64);
+                custom_height.set_value(*height as f64);
+            }
+            let custom_box = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+            custom_box.append(&custom_width);
+            custom_box.append(&gtk4::Label::new(Some(":")));
+            custom_box.append(&custom_height);
+            custom_box.set_visible(false);
 
-            // Build batch jobs
-            use crate::batch::BatchJob;
-            use crate::converter::ConversionOptions;
+            let update_ratio = Rc::new(glib::clone!(
+                #[weak]
+                ratio_dropdown,
+                #[weak]
+                custom_width,
+                #[weak]
+                custom_height,
+                #[weak]
+                custom_box,
+                #[strong]
+                preview,
+                move || {
+                    let ratio = match AspectRatio::all().get(ratio_dropdown.selected() as usize) {
+                        Some(AspectRatio::Custom { .. }) => AspectRatio::Custom {
+                            width: custom_width.value() as u32,
+                            height: custom_height.value() as u32,
+                        },
+                        Some(ratio) => *ratio,
+                        None => AspectRatio::Free,
+                    };
+                    custom_box.set_visible(matches!(ratio, AspectRatio::Custom { .. }));
+                    preview.set_aspect_ratio(ratio.ratio());
+                }
+            ));
+            ratio_dropdown.connect_selected_notify(glib::clone!(
+                #[strong]
+                update_ratio,
+                move |_| update_ratio()
+            ));
+            for spin in [&custom_width, &custom_height] {
+                spin.connect_value_changed(glib::clone!(
+                    #[strong]
+                    update_ratio,
+                    move |_| update_ratio()
+                ));
+            }
 
-            let out_dir = self.output_dir.borrow().clone();
+            let size_label = gtk4::Label::new(None);
+            size_label.set_css_classes(&["dim-label", "numeric"]);
+            size_label.set_hexpand(true);
+            size_label.set_halign(gtk4::Align::End);
+            preview.connect_crop_changed(glib::clone!(
+                #[weak]
+                size_label,
+                move |crop: Option<Crop>| {
+                    size_label.set_label(&match crop {
+                        Some(crop) => format!("{} × {}", crop.width, crop.height),
+                        None => "Whole image".to_string(),
+                    });
+                }
+            ));
 
-            let jobs: Vec<BatchJob> = files
-                .iter()
-                .map(|path| {
-                    let output_path = if let Some(ref dir) = out_dir {
-                        let file_stem = path.file_stem().unwrap_or_default();
-                        let mut out = dir.join(file_stem);
-                        out.set_extension(format.extension());
-                        out
-                    } else {
-                        path.with_extension(format.extension())
-                    };
-                    BatchJob {
-                        input_path: path.clone(),
-                        output_path,
-                        options: ConversionOptions { quality, format },
-                    }
-                })
+            let controls = gtk4::Box::new(gtk4::Orientation::Horizontal, 12);
+            controls.set_margin_top(12);
+            controls.set_margin_bottom(12);
+            controls.set_margin_start(12);
+            controls.set_margin_end(12);
+            controls.append(&gtk4::Label::new(Some("Aspect Ratio")));
+            controls.append(&ratio_dropdown);
+            controls.append(&custom_box);
+            controls.append(&size_label);
+
+            let content = gtk4::Box::new(gtk4::Orientation::Vertical, 12);
+            content.set_margin_top(12);
+            content.set_margin_start(12);
+            content.set_margin_end(12);
+            content.append(preview.widget());
+
+            let reset_button = gtk4::Button::with_label("Reset");
+            reset_button.set_tooltip_text(Some("Keep the whole image, unrotated"));
+            reset_button.connect_clicked(glib::clone!(
+                #[weak]
+                ratio_dropdown,
+                #[strong]
+                preview,
+                move |_| {
+                    ratio_dropdown.set_selected(0);
+                    preview.set_crop(None);
+                    preview.set_turns(0);
+                }
+            ));
+            let apply_button = gtk4::Button::with_label("Apply");
+            apply_button.set_css_classes(&["suggested-action"]);
+
+            let header = adw::HeaderBar::new();
+            header.pack_start(&reset_button);
+            header.pack_end(&apply_button);
+
+            let toolbar = adw::ToolbarView::new();
+            toolbar.add_top_bar(&header);
+            toolbar.set_content(Some(&content));
+            toolbar.add_bottom_bar(&controls);
+
+            let dialog = adw::Dialog::builder()
+                .title(format!(
+                    "Crop and Rotate {}",
+                    file.path()
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                ))
+                .content_width(960)
+                .content_height(560)
+                .child(&toolbar)
+                .build();
+            apply_button.connect_clicked(glib::clone!(
+                #[weak]
+                file,
+                #[weak]
+                dialog,
+                #[strong]
+                preview,
+                move |_| {
+                    file.set_crop(preview.crop());
+                    file.set_quarter_turns(preview.turns());
+                    dialog.close();
+                }
+            ));
+            dialog.present(Some(&*self.obj()));
+        }
+
+        /// Show the retry and report buttons only while there are failed files
+        fn update_failure_actions(&self) {
+            let any_failed = self.file_rows().iter().any(FileRow::is_failed);
+            self.retry_button.set_visible(any_failed);
+            self.report_button.set_visible(any_failed);
+        }
+
+        /// Convert the files whose last conversion failed again
+        pub fn retry_failed(&self) {
+            let failed: Vec<FileRow> = self
+                .file_rows()
+                .into_iter()
+                .filter(FileRow::is_failed)
                 .collect();
+            self.convert_files(failed);
+        }
 
-            let total = jobs.len();
+        /// Copy a plain-text list of failed files and their errors to the clipboard
+        pub fn copy_error_report(&self) {
+            let files = self.file_rows();
+            let entries: Vec<String> = files.iter().filter_map(FileRow::error_report).collect();
+            if entries.is_empty() {
+                return;
+            }
 
-            // Create a std::sync::mpsc channel for thread-safe progress
-            let (sender, receiver) = std::sync::mpsc::channel::<crate::batch::BatchProgress>();
+            let report = format!(
+                "PixelConvert {}: {} of {} files failed\n\n{}\n",
+                env!("CARGO_PKG_VERSION"),
+                entries.len(),
+                files.len(),
+                entries.join("\n\n")
+            );
+            self.obj().clipboard().set_text(&report);
+            self.toast_overlay
+                .add_toast(adw::Toast::new("Error report copied to clipboard"));
+        }
 
-            // Poll the receiver from the GLib main loop
-            let window = self.obj().clone();
-            let completed = std::rc::Rc::new(std::cell::Cell::new(0usize));
-            let failed = std::rc::Rc::new(std::cell::Cell::new(0usize));
+        /// Save the last batch's report as JSON or CSV, by the chosen extension
+        pub fn export_report(&self) {
+            if self.last_report.borrow().is_none() {
+                return;
+            }
+            let window = self.obj();
+            let window_ref = window.upcast_ref::<gtk4::Window>();
+
+            let json_filter = gtk4::FileFilter::new();
+            json_filter.set_name(Some("JSON"));
+            json_filter.add_suffix("json");
+            let csv_filter = gtk4::FileFilter::new();
+            csv_filter.set_name(Some("CSV"));
+            csv_filter.add_suffix("csv");
+            let filters = gio::ListStore::new::<gtk4::FileFilter>();
+            filters.append(&json_filter);
+            filters.append(&csv_filter);
+
+            let dialog = gtk4::FileDialog::builder()
+                .title("Export Report")
+                .modal(true)
+                .initial_name("pixelconvert-report.json")
+                .filters(&filters)
+                .build();
+
+            dialog.save(
+                Some(window_ref),
+                gio::Cancellable::NONE,
+                glib::clone!(
+                    #[weak(rename_to = imp)]
+                    self,
+                    move |result| {
+                        let Some(path) = result.ok().and_then(|file| file.path()) else {
+                            return;
+                        };
+                        let Some(mut report) = imp.last_report.borrow().clone() else {
+                            return;
+                        };
+                        // Saving reads every input, which may be slow
+                        let window = imp.obj().clone();
+                        glib::spawn_future_local(glib::clone!(
+                            #[weak]
+                            window,
+                            async move {
+                                let saved =
+                                    gio::spawn_blocking(move || report.save(&path).map(|_| path))
+                                        .await;
+                                let message = match saved {
+                                    Ok(Ok(path)) => format!("Report saved to {}", path.display()),
+                                    Ok(Err(e)) => format!("Can't save the report: {}", e),
+                                    Err(_) => return,
+                                };
+                                window
+                                    .imp()
+                                    .toast_overlay
+                                    .add_toast(adw::Toast::new(&message));
+                            }
+                        ));
+                    }
+                ),
+            );
+        }
+
+        /// Write a favicon bundle next to each selected file (or into the
+        /// output directory), in a `<name>-favicon` folder. Files whose
+        /// bundle fails are marked failed, with the reason in their row.
+        pub fn generate_favicon_bundles(&self) {
+            let rows = self.file_rows();
+            let files: Vec<(std::path::PathBuf, pixelconvert_core::ConversionOptions)> = rows
+                .iter()
+                .map(|file| (file.path().to_path_buf(), self.file_options(file)))
+                .collect();
+            if files.is_empty() {
+                return;
+            }
+
+            let out_dir = self.output_dir.borrow().clone();
+            let (sender, receiver) =
+                std::sync::mpsc::channel::<(usize, pixelconvert_core::error::Result<()>)>();
+
+            std::thread::spawn(move || {
+                for (index, (path, options)) in files.into_iter().enumerate() {
+                    let converter = pixelconvert_core::ImageConverter::new(options);
+                    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                    let parent = out_dir
+                        .clone()
+                        .or_else(|| path.parent().map(|p| p.to_path_buf()))
+                        .unwrap_or_default();
+                    let bundle_dir = parent.join(format!("{}-favicon", stem));
+
+                    let result = converter
+                        .write_favicon_bundle(&path, &bundle_dir)
+                        .map(|_| ());
+                    let _ = sender.send((index, result));
+                }
+            });
 
+            let window = self.obj().clone();
+            let (mut succeeded, mut failed) = (0usize, 0usize);
             glib::timeout_add_local(
                 std::time::Duration::from_millis(50),
                 glib::clone!(
                     #[weak]
                     window,
-                    #[strong]
-                    completed,
-                    #[strong]
-                    failed,
                     #[upgrade_or]
                     glib::ControlFlow::Break,
                     move || {
-                        use crate::batch::BatchProgress;
-                        let imp = window.imp();
-
-                        // Drain all available messages
-                        while let Ok(progress) = receiver.try_recv() {
-                            match progress {
-                                BatchProgress::Processing { file } => {
-                                    imp.status_label
-                                        .set_text(&format!("Converting {}...", file));
+                        loop {
+                            match receiver.try_recv() {
+                                Ok((index, Ok(()))) => {
+                                    rows[index].set_status(FileStatus::Done);
+                                    succeeded += 1;
                                 }
-                                BatchProgress::Completed { .. } => {
-                                    completed.set(completed.get() + 1);
-                                    imp.progress_bar
-                                        .set_fraction(completed.get() as f64 / total as f64);
+                                Ok((index, Err(error))) => {
+                                    rows[index].set_status(FileStatus::Failed(error));
+                                    failed += 1;
                                 }
-                                BatchProgress::Failed { file, error, .. } => {
-                                    failed.set(failed.get() + 1);
-                                    completed.set(completed.get() + 1);
-                                    eprintln!("Failed to convert {}: {}", file, error);
-                                    imp.progress_bar
-                                        .set_fraction(completed.get() as f64 / total as f64);
+                                Err(std::sync::mpsc::TryRecvError::Empty) => {
+                                    return glib::ControlFlow::Continue;
                                 }
-                                BatchProgress::Finished { successful, failed } => {
-                                    // Reset UI state
-                                    *imp.is_converting.borrow_mut() = false;
-                                    imp.convert_button.set_sensitive(true);
-                                    imp.status_label.set_text(&format!(
-                                        "Completed: {} succeeded, {} failed",
-                                        successful, failed
-                                    ));
+                                Err(std::sync::mpsc::TryRecvError::Disconnected) => break,
+                            }
+                        }
 
-                                    // Show completion toast
-                                    let toast = if failed == 0 {
-                                        adw::Toast::new(&format!(
-                                            "Successfully converted {} images",
-                                            successful
-                                        ))
-                                    } else {
-                                        adw::Toast::new(&format!(
-                                            "Converted {} images ({} failed)",
-                                            successful, failed
-                                        ))
-                                    };
-                                    toast.set_timeout(5);
-                                    imp.toast_overlay.add_toast(toast);
-
-                                    // Hide progress after a delay
-                                    glib::timeout_add_seconds_local_once(
-                                        3,
-                                        glib::clone!(
-                                            #[weak]
-                                            window,
-                                            move || {
-                                                let imp = window.imp();
-                                                imp.progress_bar.set_visible(false);
-                                                imp.status_label.set_visible(false);
-                                            }
-                                        ),
-                                    );
-
-                                    return glib::ControlFlow::Break;
-                                }
+                        let toast = if failed == 0 {
+                            adw::Toast::new(&format!("Created {} favicon bundles", succeeded))
+                        } else {
+                            adw::Toast::new(&format!(
+                                "Created {} favicon bundles ({} failed)",
+                                succeeded, failed
+                            ))
+                        };
+                        toast.set_timeout(5);
+                        window.imp().toast_overlay.add_toast(toast);
+                        window.imp().update_failure_actions();
+
+                        glib::ControlFlow::Break
+                    }
+                ),
+            );
+        }
+
+        pub fn start_conversion(&self) {
+            self.convert_files(self.file_rows());
+        }
+
+        fn convert_files(&self, files: Vec<FileRow>) {
+            // Check if already converting
+            if *self.is_converting.borrow() {
+                return;
+            }
+
+            if files.is_empty() {
+                return;
+            }
+
+            // Files deleted or moved since they were added can't be converted
+            let (files, missing): (Vec<FileRow>, Vec<FileRow>) =
+                files.into_iter().partition(|file| file.path().exists());
+            for file in &missing {
+                file.set_status(FileStatus::Skipped("The file no longer exists".to_string()));
+            }
+            let skipped = missing.len();
+
+            // Build batch jobs
+            use pixelconvert_core::{BatchJob, Journal};
+
+            let jobs: Vec<BatchJob> = files.iter().map(|file| self.job_for(file)).collect();
+
+            // Journal the batch so it can be resumed if the app closes mid-way
+            let journal = Journal::default_path().and_then(|path| {
+                Journal::create(&path, &jobs)
+                    .map_err(|e| eprintln!("Failed to create {}: {}", path.display(), e))
+                    .ok()
+            });
+
+            self.run_jobs(files, jobs, journal, skipped);
+        }
+
+        /// The batch job converting `file` with the current settings
+        fn job_for(&self, file: &FileRow) -> pixelconvert_core::BatchJob {
+            let options = self.file_options(file);
+            pixelconvert_core::BatchJob {
+                input_path: file.path().to_path_buf(),
+                output_path: pixelconvert_core::output_path_for(
+                    file.path(),
+                    self.output_dir.borrow().as_deref(),
+                    options.format,
+                ),
+                options,
+                incremental: self.incremental_row.is_active(),
+            }
+        }
+
+        /// Offer to resume a batch that was interrupted the last time the app ran
+        pub fn offer_resume(&self) {
+            use pixelconvert_core::Journal;
+
+            let Some(path) = Journal::default_path() else {
+                return;
+            };
+            let batch = match Journal::load(&path) {
+                Ok(Some(batch)) => batch,
+                Ok(None) => return,
+                Err(e) => {
+                    eprintln!("Failed to read {}: {}", path.display(), e);
+                    return;
+                }
+            };
+
+            let dialog = adw::AlertDialog::new(
+                Some("Resume Unfinished Batch?"),
+                Some(&format!(
+                    "PixelConvert closed before converting {} of {} files. \
+                     Files that were already converted will be skipped.",
+                    batch.pending(),
+                    batch.jobs.len()
+                )),
+            );
+            dialog.add_responses(&[("discard", "Discard"), ("resume", "Resume")]);
+            dialog.set_response_appearance("discard", adw::ResponseAppearance::Destructive);
+            dialog.set_response_appearance("resume", adw::ResponseAppearance::Suggested);
+            dialog.set_default_response(Some("resume"));
+
+            dialog.choose(
+                &*self.obj(),
+                None::<&gio::Cancellable>,
+                glib::clone!(
+                    #[weak(rename_to = imp)]
+                    self,
+                    move |response| match response.as_str() {
+                        "resume" => imp.resume_batch(batch),
+                        "discard" => {
+                            if let Err(e) = Journal::discard(&path) {
+                                eprintln!("{}", e);
                             }
                         }
-                        glib::ControlFlow::Continue
+                        // Closing the dialog asks again next time
+                        _ => {}
                     }
                 ),
             );
+        }
+
+        /// Show the files of a journaled batch with their recorded status
+        /// and convert the ones that haven't been
+        fn resume_batch(&self, batch: pixelconvert_core::JournaledBatch) {
+            use pixelconvert_core::{JobState, Journal};
+
+            if *self.is_converting.borrow() {
+                return;
+            }
+            let Some(path) = Journal::default_path() else {
+                return;
+            };
+
+            for (job, _) in &batch.jobs {
+                self.add_file(job.input_path.clone());
+            }
+            let listed = self.file_rows();
+            let row_for = |input: &std::path::Path| {
+                listed.iter().find(|file| file.path() == input).cloned()
+            };
 
-            // Kick off conversion on background threads
-            crate::batch::run_batch(jobs, sender);
+            // Files the list rejected can't be tracked, so leave them out
+            // before the journal numbers the jobs
+            let (files, jobs): (Vec<FileRow>, Vec<_>) = batch
+                .jobs
+                .into_iter()
+                .filter_map(|(job, state)| Some((row_for(&job.input_path)?, (job, state))))
+                .unzip();
+            let batch = pixelconvert_core::JournaledBatch { jobs };
+            for (file, (_, state)) in files.iter().zip(&batch.jobs) {
+                match state {
+                    JobState::Pending => {}
+                    JobState::Completed { .. } => file.set_status(FileStatus::Done),
+                    JobState::Failed(error) => file.set_status(FileStatus::Failed(error.clone())),
+                }
+            }
+            self.update_failure_actions();
+
+            let (journal, jobs) = match Journal::resume(&path, &batch) {
+                Ok(resumed) => resumed,
+                Err(e) => {
+                    self.toast_overlay
+                        .add_toast(adw::Toast::new(&format!("Can't resume the batch: {}", e)));
+                    return;
+                }
+            };
+            if jobs.is_empty() {
+                if let Err(e) = journal.finish() {
+                    eprintln!("{}", e);
+                }
+                return;
+            }
+
+            // The journal hands back the pending jobs in batch order
+            let files = files
+                .into_iter()
+                .zip(&batch.jobs)
+                .filter(|(_, (_, state))| *state == JobState::Pending)
+                .map(|(file, _)| file)
+                .collect();
+            self.run_jobs(files, jobs, Some(journal), 0);
+        }
+
+        /// Pause or resume the running batch from the pause toggle
+        fn toggle_pause(&self, paused: bool) {
+            if paused {
+                self.pause_button.set_icon_name("media-playback-start-symbolic");
+                self.pause_button.set_tooltip_text(Some("Resume"));
+            } else {
+                self.pause_button.set_icon_name("media-playback-pause-symbolic");
+                self.pause_button.set_tooltip_text(Some("Pause after the running files"));
+            }
+            if let Some(queue) = self.batch_queue.borrow().as_ref() {
+                if paused {
+                    queue.pause();
+                } else {
+                    queue.resume();
+                }
+            }
+        }
+
+        /// Run `jobs` in the background, showing progress on `files` (one row
+        /// per job) and recording it in `journal`. Files added to the list
+        /// meanwhile join the batch.
+        fn run_jobs(
+            &self,
+            files: Vec<FileRow>,
+            jobs: Vec<pixelconvert_core::BatchJob>,
+            mut journal: Option<pixelconvert_core::Journal>,
+            skipped: usize,
+        ) {
+            for (index, file) in files.iter().enumerate() {
+                file.set_job(Some(index));
+                file.set_status(FileStatus::Queued);
+            }
+            let mut files: std::collections::HashMap<usize, FileRow> =
+                files.into_iter().enumerate().collect();
+            self.update_failure_actions();
+
+            // Mark as converting and disable button
+            *self.is_converting.borrow_mut() = true;
+            self.convert_button.set_sensitive(false);
+            self.progress_bar.set_visible(true);
+            self.progress_bar.set_fraction(0.0);
+            self.status_label.set_visible(true);
+            self.status_label.set_text("Starting conversion...");
+
+            let mut report = Some(pixelconvert_core::BatchReport::new(&jobs));
+            let mut stats = pixelconvert_core::BatchStats::new(jobs.len());
+            self.export_button.set_visible(false);
+
+            // Kick off conversion on background threads, or worker processes
+            let batch = pixelconvert_core::run_batch_with(jobs, self.execution());
+            let receiver = batch.progress;
+            self.pause_button.set_active(false);
+            self.pause_button.set_visible(true);
+            *self.batch_queue.borrow_mut() = Some(batch.queue);
+
+            // Handle progress on the main loop as it arrives; each wakeup
+            // delivers everything queued since the last one
+            let window = self.obj().downgrade();
+            glib::MainContext::default().spawn_local(async move {
+                use pixelconvert_core::BatchProgress;
+
+                let mut up_to_date = 0usize;
+                let mut paused = false;
+                let mut bytes_saved = 0u64;
+                let mut palette_sizes = Vec::<usize>::new();
+                let mut first_error = None::<(String, pixelconvert_core::ConvertError)>;
+
+                while let Some(updates) = receiver.updates().await {
+                    let Some(window) = window.upgrade() else {
+                        return;
+                    };
+                    let imp = window.imp();
+                    let mut converting = None;
+
+                    for progress in updates {
+                        if let Some(journal) = journal.as_mut() {
+                            if let Err(e) = journal.record(&progress) {
+                                eprintln!("{}", e);
+                            }
+                        }
+                        if let Some(report) = report.as_mut() {
+                            report.record(&progress);
+                        }
+                        stats.record(&progress);
+                        match progress {
+                            BatchProgress::Processing { index, file } => {
+                                if let Some(row) = files.get(&index) {
+                                    row.set_status(FileStatus::Running);
+                                }
+                                converting = Some(file);
+                            }
+                            BatchProgress::Completed {
+                                index,
+                                stats: job_stats,
+                                ..
+                            } => {
+                                if let Some(file) = files.get(&index) {
+                                    file.set_status(FileStatus::Done);
+                                }
+                                bytes_saved += job_stats.optimization_savings();
+                                if let Some(size) = job_stats.palette_size {
+                                    palette_sizes.push(size);
+                                }
+                            }
+                            BatchProgress::Failed {
+                                index, file, error, ..
+                            } => {
+                                if let Some(row) = files.get(&index) {
+                                    row.set_status(FileStatus::Failed(error.clone()));
+                                }
+                                eprintln!("Failed to convert {}: {}", file, error);
+                                if first_error.is_none() {
+                                    first_error = Some((file, error));
+                                }
+                            }
+                            BatchProgress::Skipped { index, .. } => {
+                                if let Some(file) = files.get(&index) {
+                                    file.set_status(FileStatus::Skipped(
+                                        "The output is already up to date".to_string(),
+                                    ));
+                                }
+                                up_to_date += 1;
+                            }
+                            BatchProgress::Added { index, .. } => {
+                                let added = imp
+                                    .file_rows()
+                                    .into_iter()
+                                    .find(|file| file.job() == Some(index));
+                                if let Some(file) = added {
+                                    files.insert(index, file);
+                                }
+                            }
+                            BatchProgress::Removed { index, .. } => {
+                                if let Some(file) = files.remove(&index) {
+                                    file.set_job(None);
+                                    file.set_status(FileStatus::Idle);
+                                }
+                            }
+                            BatchProgress::Measured { .. } => {}
+                            BatchProgress::Paused => paused = true,
+                            BatchProgress::Resumed => paused = false,
+                            BatchProgress::CacheNotSaved { error } => {
+                                imp.toast_overlay.add_toast(adw::Toast::new(&format!(
+                                    "Output cache not saved: {}",
+                                    error
+                                )));
+                            }
+                            BatchProgress::Finished {
+                                successful, failed, ..
+                            } => {
+                                if let Some(journal) = journal.take() {
+                                    if let Err(e) = journal.finish() {
+                                        eprintln!("{}", e);
+                                    }
+                                }
+
+                                // Reset UI state
+                                *imp.is_converting.borrow_mut() = false;
+                                imp.batch_queue.take();
+                                for file in files.values() {
+                                    file.set_job(None);
+                                }
+                                imp.pause_button.set_active(false);
+                                imp.pause_button.set_visible(false);
+                                imp.convert_button.set_sensitive(true);
+                                imp.update_failure_actions();
+                                *imp.last_report.borrow_mut() = report.take();
+                                imp.export_button.set_visible(true);
+                                imp.progress_bar.set_fraction(1.0);
+                                let mut summary = format!(
+                                    "Completed: {} succeeded, {} failed",
+                                    successful, failed
+                                );
+                                if skipped > 0 {
+                                    summary.push_str(&format!(", {} skipped", skipped));
+                                }
+                                if up_to_date > 0 {
+                                    summary.push_str(&format!(", {} up to date", up_to_date));
+                                }
+                                if bytes_saved > 0 {
+                                    summary.push_str(&format!(
+                                        " — PNG optimization saved {}",
+                                        glib::format_size(bytes_saved)
+                                    ));
+                                }
+                                if successful > 0 {
+                                    summary.push_str(&format!(" — {}", stats.summary()));
+                                }
+                                match (palette_sizes.iter().min(), palette_sizes.iter().max()) {
+                                    (Some(min), Some(max)) if min == max => {
+                                        summary.push_str(&format!(" — palette: {} colours", max));
+                                    }
+                                    (Some(min), Some(max)) => {
+                                        summary.push_str(&format!(
+                                            " — palettes: {}–{} colours",
+                                            min, max
+                                        ));
+                                    }
+                                    _ => {}
+                                }
+                                // Name the first failure and what to do about it
+                                if let Some((file, error)) = first_error.as_ref() {
+                                    summary.push_str(&format!(" — {}: {}", file, error));
+                                }
+                                imp.status_label.set_text(&summary);
+
+                                // Show completion toast
+                                let hint = first_error.as_ref().and_then(|(_, e)| e.hint());
+                                let toast = match (failed, hint) {
+                                    (0, _) => adw::Toast::new(&format!(
+                                        "Successfully converted {} images",
+                                        successful
+                                    )),
+                                    (_, Some(hint)) => adw::Toast::new(&format!(
+                                        "Converted {} images ({} failed). {}",
+                                        successful, failed, hint
+                                    )),
+                                    (_, None) => adw::Toast::new(&format!(
+                                        "Converted {} images ({} failed)",
+                                        successful, failed
+                                    )),
+                                };
+                                toast.set_timeout(5);
+                                imp.toast_overlay.add_toast(toast);
+
+                                // Hide progress after a delay
+                                glib::timeout_add_seconds_local_once(
+                                    3,
+                                    glib::clone!(
+                                        #[weak]
+                                        window,
+                                        move || {
+                                            let imp = window.imp();
+                                            imp.progress_bar.set_visible(false);
+                                            imp.status_label.set_visible(false);
+                                        }
+                                    ),
+                                );
+
+                                return;
+                            }
+                        }
+                    }
+
+                    // One widget update per wakeup, however many jobs it covered
+                    imp.progress_bar.set_fraction(stats.fraction());
+                    match converting {
+                        _ if paused => {
+                            imp.status_label.set_text(&format!("Paused ({})", stats.progress_line()))
+                        }
+                        Some(file) => imp.status_label.set_text(&format!(
+                            "Converting {}... ({})",
+                            file,
+                            stats.progress_line()
+                        )),
+                        None => imp.status_label.set_text(&stats.progress_line()),
+                    }
+                }
+            });
         }
     }
 }
@@ -665,4 +2576,8 @@ impl PixelConvertWindow {
     pub fn clear(&self) {
         self.imp().clear_files();
     }
+
+    pub fn offer_resume(&self) {
+        self.imp().offer_resume();
+    }
 }